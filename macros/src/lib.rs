@@ -0,0 +1,183 @@
+//! Procedural macros for the garden crate.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, spanned::Spanned, Data, DeriveInput, Fields, Path, Token};
+
+/// Generate a `reduce` method that builds the next state by running each field
+/// through its reducer. The action type is named on the struct, and every field must
+/// name its reducer, so that a new field can't be forgotten.
+///
+/// ```
+/// use garden_macros::Reducer;
+///
+/// enum Action {
+///     Add(i32),
+///     Rename(&'static str),
+/// }
+///
+/// fn total(state: i32, action: &Action) -> i32 {
+///     match action {
+///         Action::Add(amount) => state + amount,
+///         _ => state,
+///     }
+/// }
+///
+/// fn name(state: &'static str, action: &Action) -> &'static str {
+///     match action {
+///         Action::Rename(name) => name,
+///         _ => state,
+///     }
+/// }
+///
+/// #[derive(Reducer)]
+/// #[reducer(action = Action)]
+/// struct State {
+///     #[reducer(total)]
+///     total: i32,
+///     #[reducer(name)]
+///     name: &'static str,
+/// }
+///
+/// let state = State { total: 0, name: "" };
+/// let state = state.reduce(&Action::Add(2)).reduce(&Action::Rename("two"));
+/// assert_eq!(state.total, 2);
+/// assert_eq!(state.name, "two");
+/// ```
+///
+/// Each reducer is a function of the form `fn(FieldType, &Action) -> FieldType`, and
+/// is passed a clone of the field. The derive above generates:
+///
+/// ```ignore
+/// impl State {
+///     pub fn reduce(&self, action: &Action) -> Self {
+///         State {
+///             total: total(self.total.clone(), action),
+///             name: name(self.name.clone(), action),
+///         }
+///     }
+/// }
+/// ```
+///
+/// A field without a reducer is a compile error:
+///
+/// ```compile_fail
+/// use garden_macros::Reducer;
+///
+/// fn count(state: i32, action: &()) -> i32 {
+///     state + 1
+/// }
+///
+/// #[derive(Reducer)]
+/// #[reducer(action = ())]
+/// struct State {
+///     #[reducer(count)]
+///     count: i32,
+///     forgotten: i32,
+/// }
+/// ```
+#[proc_macro_derive(Reducer, attributes(reducer))]
+pub fn derive_reducer(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand_reducer(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand_reducer(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    let action = parse_action_type(&input)?;
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input,
+                    "Reducer can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input,
+                "Reducer can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut members = Vec::new();
+    for field in fields {
+        let member = field
+            .ident
+            .as_ref()
+            .expect("Named fields have identifiers.");
+        let reducer = parse_reducer_path(field.attrs.iter(), field.span())?.ok_or_else(
+            || {
+                syn::Error::new_spanned(
+                    field,
+                    format!(
+                        "the field `{}` has no reducer, add #[reducer(path::to::reducer)]",
+                        member
+                    ),
+                )
+            },
+        )?;
+        members.push(quote! {
+            #member: #reducer(self.#member.clone(), action)
+        });
+    }
+
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #name #type_generics #where_clause {
+            /// Build the next state by running each field through its reducer.
+            pub fn reduce(&self, action: &#action) -> Self {
+                #name {
+                    #( #members ),*
+                }
+            }
+        }
+    })
+}
+
+/// Parse the `#[reducer(action = Type)]` attribute on the struct.
+fn parse_action_type(input: &DeriveInput) -> syn::Result<syn::Type> {
+    for attr in &input.attrs {
+        if !attr.path.is_ident("reducer") {
+            continue;
+        }
+        return attr.parse_args_with(|input: syn::parse::ParseStream| {
+            let key: syn::Ident = input.parse()?;
+            if key != "action" {
+                return Err(syn::Error::new(key.span(), "expected `action = Type`"));
+            }
+            input.parse::<Token![=]>()?;
+            input.parse::<syn::Type>()
+        });
+    }
+    Err(syn::Error::new_spanned(
+        &input.ident,
+        "add #[reducer(action = Type)] to name the action type",
+    ))
+}
+
+/// Parse the `#[reducer(path)]` attribute on a field.
+fn parse_reducer_path<'a>(
+    attrs: impl Iterator<Item = &'a syn::Attribute>,
+    span: Span,
+) -> syn::Result<Option<Path>> {
+    let mut reducer = None;
+    for attr in attrs {
+        if !attr.path.is_ident("reducer") {
+            continue;
+        }
+        if reducer.is_some() {
+            return Err(syn::Error::new(span, "a field can only have one reducer"));
+        }
+        reducer = Some(attr.parse_args::<Path>()?);
+    }
+    Ok(reducer)
+}
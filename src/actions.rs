@@ -4,10 +4,15 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     block_chain::SerializedBytes,
-    game::{garden::DrawableGarden, input_device::InputDevice, primitives::Position},
+    chain_store::ChainStoreError,
+    game::{
+        game_state, garden::DrawableGarden, input_device::InputDevice,
+        primitives::Position,
+    },
     garden::GardenPlot,
     selectors,
     utils::get_timestamp,
+    world::WorldMetadata,
     State, Store,
 };
 
@@ -29,20 +34,63 @@ impl From<ChainAction> for Action {
     }
 }
 
+/// Ephemeral actions for the current session. They change the State, but are not
+/// added to the block chain.
 #[derive(Clone, PartialEq, Debug)]
 pub enum GameAction {
     TickGame(i64),
+    MovePlayer(
+        (
+            Position, // position
+            Position, // move intent
+        ),
+    ),
+    /// Where the top left of the view is in the world, see `follow_player`.
+    MoveCamera(Position),
+    /// The tile in the world under the mouse, see `maybe_move_cursor`.
+    MoveCursor(Option<Position>),
 }
 
+/// Durable actions, which are recorded as blocks in the chain.
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub enum ChainAction {
     CreatePlot(GardenPlot),
+    /// A checkpoint of where the player was when the game was saved. Walking around
+    /// is a GameAction, and only the final position is recorded, see `save`.
     MovePlayer(
         (
             Position, // position
             Position, // move intent
         ),
     ),
+    /// The first block of a chain, which names its world, see `garden_dir::init`. New
+    /// actions are added at the end, so the hashes of existing blocks stay the same.
+    Genesis(WorldMetadata),
+}
+
+impl ChainAction {
+    /// The names of every action, see `name`.
+    pub const NAMES: &'static [&'static str] = &["CreatePlot", "MovePlayer", "Genesis"];
+
+    /// The name of the action, for display to a user.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChainAction::CreatePlot(_) => "CreatePlot",
+            ChainAction::MovePlayer(_) => "MovePlayer",
+            ChainAction::Genesis(_) => "Genesis",
+        }
+    }
+
+    /// A sentence describing what the action did, for display to a user.
+    pub fn describe(&self) -> String {
+        match self {
+            ChainAction::CreatePlot(plot) => format!("Created the plot {:?}", plot.name),
+            ChainAction::MovePlayer((position, _)) => {
+                format!("Saved the player at ({}, {})", position.x, position.y)
+            }
+            ChainAction::Genesis(world) => format!("Created the world {:?}", world.name),
+        }
+    }
 }
 
 impl SerializedBytes for ChainAction {
@@ -68,7 +116,7 @@ pub fn maybe_move_player(store: &mut Store, input_device: &InputDevice) {
 
     let mut next_position = position + input_device.move_intent;
 
-    for garden in &(*selectors::get_drawable_gardens(store.state())) {
+    for garden in &selectors::get_drawable_gardens(store.state()) {
         if garden.bbox.intersects_point(position) {
             if garden.bbox.left() == next_position.x
                 || garden.bbox.right() == next_position.x
@@ -83,7 +131,68 @@ pub fn maybe_move_player(store: &mut Store, input_device: &InputDevice) {
 
     if next_position != position {
         store.dispatch(
-            ChainAction::MovePlayer((next_position, input_device.move_intent)).into(),
+            GameAction::MovePlayer((next_position, input_device.move_intent)).into(),
         );
+        follow_player(store);
     }
 }
+
+/// How close the player can get to the edge of the view before the camera follows.
+pub const CAMERA_MARGIN: i32 = 4;
+
+/// Move the camera when the player gets close to the edge of the view, so that they
+/// never walk out of it.
+pub fn follow_player(store: &mut Store) {
+    let position = match selectors::get_player_position(store.state()) {
+        Some(position) => position,
+        None => return,
+    };
+    let camera = selectors::get_camera(store.state());
+    let view = game_state::world_view();
+    let on_screen = position - camera;
+    let mut next_camera = camera;
+    if on_screen.x < view.left() + CAMERA_MARGIN {
+        next_camera.x = position.x - view.left() - CAMERA_MARGIN;
+    } else if on_screen.x > view.right() - CAMERA_MARGIN {
+        next_camera.x = position.x - view.right() + CAMERA_MARGIN;
+    }
+    if on_screen.y < view.top() + CAMERA_MARGIN {
+        next_camera.y = position.y - view.top() - CAMERA_MARGIN;
+    } else if on_screen.y > view.bottom() - CAMERA_MARGIN {
+        next_camera.y = position.y - view.bottom() + CAMERA_MARGIN;
+    }
+    if next_camera != camera {
+        store.dispatch(GameAction::MoveCamera(next_camera).into());
+    }
+}
+
+/// Point the cursor at the tile under the mouse, or at nothing when the mouse is
+/// outside of the world view.
+pub fn maybe_move_cursor(store: &mut Store, input_device: &InputDevice) {
+    let camera = selectors::get_camera(store.state());
+    let cursor = Some(input_device.mouse)
+        .filter(|mouse| game_state::world_view().intersects_point(*mouse))
+        .map(|mouse| mouse + camera);
+    if cursor != selectors::get_cursor(store.state()) {
+        store.dispatch(GameAction::MoveCursor(cursor).into());
+    }
+}
+
+/// Record where the player left off, if they've moved since the last checkpoint.
+/// The camera and cursor are not recorded.
+pub fn checkpoint(store: &mut Store) {
+    let state = store.state();
+    if let Some(position) = selectors::get_player_position(state.clone()) {
+        if selectors::get_checkpoint_position(state) != Some(position) {
+            store.dispatch(
+                ChainAction::MovePlayer((position, Position::new(0, 0))).into(),
+            );
+        }
+    }
+}
+
+/// Record where the player left off, see `checkpoint`, and then persist the chain.
+pub fn save(store: &mut Store) -> Result<(), ChainStoreError> {
+    checkpoint(store);
+    store.persist()
+}
@@ -0,0 +1,71 @@
+//! Create and import bundle files, to move a garden between machines without a
+//! network.
+
+use anyhow::{Context, Result};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::PathBuf,
+};
+
+use garden::{bundle::Bundle, chain_store::HeadRef, revision::Revision, ChainAction};
+use structopt::StructOpt;
+
+use crate::GlobalOptions;
+
+#[derive(Debug, StructOpt)]
+pub enum Options {
+    /// Write the blocks of the head to a bundle file.
+    Create {
+        /// The bundle file to write.
+        file: PathBuf,
+        /// Only bundle the blocks after this revision, which the importing garden must
+        /// already have, e.g. my-garden~10.
+        #[structopt(long)]
+        base: Option<Revision>,
+    },
+    /// Add the blocks of a bundle file to the head, and save it. The head defaults to
+    /// the one the bundle was created from.
+    Import {
+        /// The bundle file to read.
+        file: PathBuf,
+    },
+}
+
+pub fn run(global: &GlobalOptions, options: Options) -> Result<()> {
+    match options {
+        Options::Create { file, base } => {
+            let mut chain_store = global.existing_chain_store()?;
+            let base = match base {
+                Some(revision) => Some(revision.resolve(&mut chain_store)?),
+                None => None,
+            };
+            let bundle = Bundle::create(&mut chain_store, base.as_ref())?;
+            global.warn_index_error(&mut chain_store);
+            let writer = BufWriter::new(
+                File::create(&file)
+                    .with_context(|| format!("Failed to create {:?}", file))?,
+            );
+            bundle.write(writer)?;
+            println!("Bundled {} blocks into {:?}", bundle.blocks.len(), file);
+        }
+        Options::Import { file } => {
+            let garden_dir = global.garden_dir()?;
+            let reader = BufReader::new(
+                File::open(&file)
+                    .with_context(|| format!("Failed to open {:?}", file))?,
+            );
+            let bundle = Bundle::<ChainAction>::read(reader)
+                .with_context(|| format!("Failed to read the bundle {:?}", file))?;
+            let head_ref = match global.head {
+                Some(_) => global.head_ref()?,
+                None => HeadRef::try_from(bundle.head_ref.clone())
+                    .context("The bundle has an invalid head ref.")?,
+            };
+            let added = garden_dir.import_bundle(head_ref.clone(), &bundle)?;
+            println!("Imported {} blocks into {:?}", added, head_ref.str());
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,113 @@
+//! List the blocks of a chain, similar to the Unix cat command.
+
+use anyhow::{bail, Context, Result};
+use std::io;
+
+use garden::{
+    chain_query::{parse_time, BlockQuery, Format},
+    revision::Revision,
+    ChainAction, ChainStore,
+};
+use structopt::StructOpt;
+
+use crate::GlobalOptions;
+
+#[derive(Debug, StructOpt)]
+pub struct Options {
+    /// Print the chain that ends at this revision, rather than the chain of the head,
+    /// e.g. my-garden~3, 3fa9c1, or my-garden@{2021-12-31}.
+    #[structopt()]
+    revision: Option<Revision>,
+
+    /// Only print the hash of the last block, without loading the chain.
+    #[structopt(long)]
+    hash: bool,
+
+    /// Only print the block of this revision.
+    #[structopt(long)]
+    block: Option<Revision>,
+
+    /// Start from the block of this revision.
+    #[structopt(long)]
+    from: Option<Revision>,
+
+    /// Only print the last N blocks.
+    #[structopt(long)]
+    last: Option<usize>,
+
+    /// Only print blocks with this action, e.g. CreatePlot. This can be repeated.
+    #[structopt(long = "action")]
+    actions: Vec<String>,
+
+    /// Only print blocks from this time on, e.g. 2021-12-31.
+    #[structopt(long, parse(try_from_str = parse_time))]
+    since: Option<i64>,
+
+    /// Only print blocks before this time, e.g. 2021-12-31T18:00:00Z.
+    #[structopt(long, parse(try_from_str = parse_time))]
+    until: Option<i64>,
+
+    /// The output format.
+    #[structopt(long, default_value = "json", possible_values = Format::NAMES)]
+    format: Format,
+}
+
+pub fn run(global: &GlobalOptions, options: Options) -> Result<()> {
+    // Revisions can name blocks of any head, so the head only has to exist when the
+    // chain of the head is printed.
+    let mut chain_store = match options.revision.as_ref().or(options.block.as_ref()) {
+        Some(_) => global
+            .garden_dir()?
+            .chain_store::<ChainAction>(global.head_ref()?)?,
+        None => global.existing_chain_store()?,
+    };
+
+    if let Some(revision) = &options.block {
+        let hash = revision.resolve(&mut chain_store)?;
+        let block = chain_store
+            .get_block(&hash)?
+            .with_context(|| format!("The block {} does not exist.", hash))?;
+        options.format.write(&mut io::stdout().lock(), &[&block])?;
+        global.warn_index_error(&mut chain_store);
+        return Ok(());
+    }
+
+    let tip = match &options.revision {
+        Some(revision) => Some(revision.resolve(&mut chain_store)?),
+        None => chain_store.head_hash()?,
+    };
+    if options.hash {
+        match tip {
+            Some(hash) => println!("{}", hash),
+            None => bail!("The head {:?} has no blocks.", chain_store.head_ref.str()),
+        }
+        return Ok(());
+    }
+
+    let query = BlockQuery {
+        from: match &options.from {
+            Some(revision) => Some(revision.resolve(&mut chain_store)?),
+            None => None,
+        },
+        last: options.last,
+        actions: options.actions,
+        since: options.since,
+        until: options.until,
+    };
+    let revision_blocks = match (&options.revision, &tip) {
+        (Some(_), Some(hash)) => Some(
+            chain_store
+                .blocks_to(hash)?
+                .with_context(|| format!("The block {} does not exist.", hash))?,
+        ),
+        _ => None,
+    };
+    let blocks = match &revision_blocks {
+        Some(blocks) => query.select(blocks)?,
+        None => query.select(chain_store.iter_all()?)?,
+    };
+    options.format.write(&mut io::stdout().lock(), &blocks)?;
+    global.warn_index_error(&mut chain_store);
+
+    Ok(())
+}
@@ -0,0 +1,105 @@
+//! Compare two revisions, which can be in different .garden directories.
+
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+
+use garden::{
+    block_chain::{Block, BlockChain},
+    chain_diff::ChainDiff,
+    chain_query::format_time,
+    garden_dir::GardenDir,
+    revision::Revision,
+    ChainAction,
+};
+use structopt::StructOpt;
+
+use crate::GlobalOptions;
+
+#[derive(Debug, StructOpt)]
+pub struct Options {
+    /// The revision the left chain ends at, e.g. garden-1 or garden-1~3.
+    #[structopt()]
+    left: Revision,
+
+    /// The revision the right chain ends at, which defaults to the left one. This is
+    /// useful when comparing the same head in two directories.
+    #[structopt()]
+    right: Option<Revision>,
+
+    /// The .garden directory of the left chain, instead of the current one.
+    #[structopt(long, parse(from_os_str))]
+    left_dir: Option<PathBuf>,
+
+    /// The .garden directory of the right chain, instead of the current one.
+    #[structopt(long, parse(from_os_str))]
+    right_dir: Option<PathBuf>,
+}
+
+fn load_chain(
+    global: &GlobalOptions,
+    path: Option<PathBuf>,
+    revision: Revision,
+) -> Result<(String, BlockChain<ChainAction>)> {
+    let garden_dir = match path {
+        Some(path) => GardenDir::open(path)?,
+        None => global.garden_dir()?,
+    };
+    let mut chain_store = garden_dir.chain_store::<ChainAction>(global.head_ref()?)?;
+    let hash = revision.resolve(&mut chain_store).with_context(|| {
+        format!("Failed to resolve it in {}", garden_dir.path.display())
+    })?;
+    let blocks = chain_store
+        .blocks_to(&hash)?
+        .with_context(|| format!("The block {} does not exist.", hash))?;
+    global.warn_index_error(&mut chain_store);
+    let title = format!("{} ({})", revision, garden_dir.path.display());
+    Ok((
+        title,
+        BlockChain {
+            blocks: blocks.into(),
+        },
+    ))
+}
+
+fn print_blocks(title: &str, blocks: &[&Block<ChainAction>]) {
+    println!("Only in {} ({} blocks):", title, blocks.len());
+    for block in blocks {
+        println!(
+            "  {} {} {}",
+            block.hash.short(),
+            format_time(block.payload.timestamp),
+            block.payload.data.describe()
+        );
+    }
+}
+
+pub fn run(global: &GlobalOptions, options: Options) -> Result<()> {
+    let right = options.right.unwrap_or_else(|| options.left.clone());
+    let (left_title, left_chain) = load_chain(global, options.left_dir, options.left)?;
+    let (right_title, right_chain) = load_chain(global, options.right_dir, right)?;
+
+    let diff = ChainDiff::new(&left_chain, &right_chain);
+    match diff.common_ancestor {
+        Some((block, left_index, right_index)) => println!(
+            "Common ancestor: {} {} (block {} on the left, {} on the right)",
+            block.hash.short(),
+            block.payload.data.describe(),
+            left_index,
+            right_index
+        ),
+        None => println!("The chains have no blocks in common."),
+    }
+    println!();
+    print_blocks(&left_title, &diff.only_left);
+    println!();
+    print_blocks(&right_title, &diff.only_right);
+    println!();
+    if diff.state.is_empty() {
+        println!("The states are the same.");
+    } else {
+        println!("From the left state to the right:");
+        print!("{}", diff.state);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,51 @@
+//! Check that every head loads, and that its blocks are intact.
+
+use anyhow::{bail, Result};
+
+use garden::ChainAction;
+
+use crate::GlobalOptions;
+
+pub fn run(global: &GlobalOptions) -> Result<()> {
+    let garden_dir = global.garden_dir()?;
+    let report = garden_dir.fsck::<ChainAction>()?;
+    let genesis = garden_dir.config()?.map(|config| config.genesis);
+    for head in &report.heads {
+        match &head.result {
+            Ok(count) => println!("ok      {} ({} blocks)", head.head_ref.str(), count),
+            Err(err) => println!("broken  {}: {}", head.head_ref.str(), err),
+        }
+        if let (Some(genesis), Some(root)) = (&genesis, &head.root) {
+            if genesis != root {
+                println!(
+                    "        {} starts from {} rather than the genesis block {}, so it is \
+                     from another world.",
+                    head.head_ref.str(),
+                    root.short(),
+                    genesis.short()
+                );
+            }
+        }
+    }
+
+    match &report.unreachable {
+        Some(unreachable) if !unreachable.is_empty() => {
+            println!(
+                "{} chunks are unreachable, `garden gc` removes them.",
+                unreachable.len()
+            );
+            if global.verbose > 0 {
+                for hash in unreachable {
+                    println!("  {}", hash);
+                }
+            }
+        }
+        Some(_) => {}
+        None => println!("Unreachable chunks can't be found while a head is broken."),
+    }
+
+    if !report.is_ok() {
+        bail!("The garden at {} is corrupted.", garden_dir.path.display());
+    }
+    Ok(())
+}
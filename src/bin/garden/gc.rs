@@ -0,0 +1,31 @@
+//! Remove the chain chunks that no head leads to.
+
+use anyhow::Result;
+use structopt::StructOpt;
+
+use garden::ChainAction;
+
+use crate::GlobalOptions;
+
+#[derive(Debug, StructOpt)]
+pub struct Options {
+    /// Only list what would be removed.
+    #[structopt(long)]
+    dry_run: bool,
+}
+
+pub fn run(global: &GlobalOptions, options: Options) -> Result<()> {
+    let garden_dir = global.garden_dir()?;
+    let removed = garden_dir.gc::<ChainAction>(options.dry_run)?;
+    if global.verbose > 0 || options.dry_run {
+        for hash in &removed {
+            println!("  {}", hash);
+        }
+    }
+    if options.dry_run {
+        println!("Would remove {} chunks", removed.len());
+    } else {
+        println!("Removed {} chunks", removed.len());
+    }
+    Ok(())
+}
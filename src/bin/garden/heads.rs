@@ -0,0 +1,27 @@
+//! List the heads, and the blocks they point to.
+
+use anyhow::Result;
+
+use garden::ChainAction;
+
+use crate::GlobalOptions;
+
+pub fn run(global: &GlobalOptions) -> Result<()> {
+    let garden_dir = global.garden_dir()?;
+    let current = global.head_ref()?;
+    let (head_refs, strays) = garden_dir.head_refs_and_strays()?;
+    for stray in strays {
+        eprintln!("Skipped a file in the heads directory: {}", stray);
+    }
+    for head_ref in head_refs {
+        let chain_store = garden_dir.chain_store::<ChainAction>(head_ref.clone())?;
+        let hash = match chain_store.head_hash() {
+            Ok(Some(hash)) => hash.short(),
+            Ok(None) => "(none)".into(),
+            Err(_) => "(broken)".into(),
+        };
+        let marker = if head_ref == current { '*' } else { ' ' };
+        println!("{} {} {}", marker, hash, head_ref.str());
+    }
+    Ok(())
+}
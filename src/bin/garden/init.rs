@@ -0,0 +1,62 @@
+//! Create a garden, starting with the genesis block of its world.
+
+use anyhow::{Context, Result};
+use std::{env, path::Path};
+
+use garden::{
+    garden_dir::{GardenDir, GARDEN_DIR_NAME},
+    peers,
+    world::WorldMetadata,
+};
+use libp2p::{identity, PeerId};
+use structopt::StructOpt;
+
+use crate::GlobalOptions;
+
+#[derive(Debug, StructOpt)]
+pub struct Options {
+    /// The name of the world. Defaults to the name of the directory the garden is
+    /// created in.
+    #[structopt(long)]
+    name: Option<String>,
+}
+
+pub fn run(global: &GlobalOptions, options: Options) -> Result<()> {
+    let path = match &global.garden_dir {
+        Some(path) => path.clone(),
+        None => env::current_dir()
+            .context("Failed to read the working directory.")?
+            .join(GARDEN_DIR_NAME),
+    };
+    let name = options
+        .name
+        .or_else(|| {
+            let parent = match path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => Path::new("."),
+            };
+            let parent = parent.canonicalize().ok()?;
+            Some(parent.file_name()?.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| "Garden".into());
+    // The creator is the peer the garden is known by on the network.
+    let keypair = identity::Keypair::generate_ed25519();
+    let creator = PeerId::from(keypair.public());
+    let world = WorldMetadata::new(name, creator.to_base58());
+
+    let garden_dir = GardenDir::init(path, world.clone())?;
+    peers::write_identity(&garden_dir.identity_path(), &keypair)?;
+    let config = garden_dir
+        .config()?
+        .expect("The config was written by init.");
+    println!(
+        "Created the world {:?} in {}",
+        world.name,
+        garden_dir.path.display()
+    );
+    if global.verbose > 0 {
+        println!("Genesis block: {}", config.genesis);
+        println!("Peer id: {}", creator);
+    }
+    Ok(())
+}
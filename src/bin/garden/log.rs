@@ -0,0 +1,64 @@
+//! Show the history of the heads as a graph, similar to `git log --graph`. Every head
+//! is shown, unless --head or revisions pick which.
+
+use anyhow::{bail, Context, Result};
+use structopt::StructOpt;
+
+use garden::{
+    block_chain::Block,
+    chain_log::{self, HeadHistory},
+    revision::Revision,
+    ChainAction,
+};
+
+use crate::GlobalOptions;
+
+#[derive(Debug, StructOpt)]
+pub struct Options {
+    /// Show the history that ends at these revisions, e.g. my-garden~3 or
+    /// my-garden@{2021-12-31}.
+    #[structopt()]
+    revisions: Vec<Revision>,
+}
+
+pub fn run(global: &GlobalOptions, options: Options) -> Result<()> {
+    let garden_dir = global.garden_dir()?;
+    let mut chains: Vec<(String, Vec<Block<ChainAction>>)> = vec![];
+    if options.revisions.is_empty() {
+        let head_refs = match global.head {
+            Some(_) => vec![global.head_ref()?],
+            None => garden_dir.head_refs()?,
+        };
+        if head_refs.is_empty() {
+            bail!("No head refs have been created yet.");
+        }
+        for head_ref in head_refs {
+            let mut chain_store = garden_dir.existing_chain_store(head_ref.clone())?;
+            chain_store.load_all_chains()?;
+            chains.push((
+                head_ref.str().into(),
+                chain_store.chain.blocks.into_iter().collect(),
+            ));
+        }
+    } else {
+        let mut chain_store = garden_dir.chain_store(global.head_ref()?)?;
+        for revision in options.revisions {
+            let hash = revision.resolve(&mut chain_store)?;
+            let blocks = chain_store
+                .blocks_to(&hash)?
+                .with_context(|| format!("The block {} does not exist.", hash))?;
+            chains.push((revision.to_string(), blocks));
+        }
+        global.warn_index_error(&mut chain_store);
+    }
+
+    let heads: Vec<HeadHistory> = chains
+        .iter()
+        .map(|(name, blocks)| HeadHistory { name, blocks })
+        .collect();
+    for line in chain_log::render(&heads) {
+        println!("{}", line);
+    }
+
+    Ok(())
+}
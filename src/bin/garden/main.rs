@@ -0,0 +1,155 @@
+//! The garden command. It works with the .garden directory of the working directory,
+//! or the closest parent that has one, similar to git.
+
+mod bundle;
+mod cat;
+mod diff;
+mod fsck;
+mod gc;
+mod heads;
+mod init;
+mod log;
+mod peers;
+mod play;
+mod remote;
+mod serve;
+
+use anyhow::{bail, Context, Result};
+use std::{env, path::PathBuf};
+
+use garden::{
+    chain_store::{FsChainStore, HeadRef},
+    garden_dir::{GardenDir, GardenDirError, DEFAULT_HEAD},
+    ChainAction,
+};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "garden",
+    about = "Grow a garden that is stored in a block chain."
+)]
+struct CliOptions {
+    #[structopt(flatten)]
+    global: GlobalOptions,
+
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+// The options that every subcommand shares.
+#[derive(Debug, StructOpt)]
+pub struct GlobalOptions {
+    /// The .garden directory to use, rather than looking for one in the working
+    /// directory and its parents.
+    #[structopt(long, global = true, parse(from_os_str))]
+    pub garden_dir: Option<PathBuf>,
+
+    /// The head ref to work with, e.g. garden-1. Defaults to my-garden.
+    #[structopt(long, global = true)]
+    pub head: Option<String>,
+
+    /// Print more about what is happening. This can be repeated.
+    #[structopt(short, long, global = true, parse(from_occurrences))]
+    pub verbose: u8,
+}
+
+impl GlobalOptions {
+    pub fn garden_dir(&self) -> Result<GardenDir> {
+        let garden_dir = match &self.garden_dir {
+            Some(path) => GardenDir::open(path.clone())?,
+            None => GardenDir::discover(
+                &env::current_dir().context("Failed to read the working directory.")?,
+            )?,
+        };
+        if self.verbose > 0 {
+            eprintln!("Using the garden at {}", garden_dir.path.display());
+        }
+        Ok(garden_dir)
+    }
+
+    pub fn head_ref(&self) -> Result<HeadRef> {
+        HeadRef::try_from(self.head.clone().unwrap_or_else(|| DEFAULT_HEAD.into()))
+            .context("An invalid head ref was provided.")
+    }
+
+    /// The chain store for the head, which must already exist. The available heads
+    /// are listed when it doesn't.
+    pub fn existing_chain_store(&self) -> Result<FsChainStore<ChainAction>> {
+        let garden_dir = self.garden_dir()?;
+        match garden_dir.existing_chain_store(self.head_ref()?) {
+            Err(GardenDirError::UnknownHead(head)) => {
+                let head_refs = garden_dir.head_refs()?;
+                if head_refs.is_empty() {
+                    bail!("No head refs have been created yet.");
+                }
+                let mut reason = format!(
+                    "The reference {:?} does not exist.\n\n\
+                     The available head references are:\n",
+                    head
+                );
+                for head_ref in head_refs {
+                    reason.push_str("  ");
+                    reason.push_str(head_ref.str());
+                    reason.push('\n');
+                }
+                bail!("{}", reason);
+            }
+            chain_store => Ok(chain_store?),
+        }
+    }
+
+    /// The block index is rebuilt when it is missing, and saving it is best effort, so
+    /// a failure only warns.
+    pub fn warn_index_error(&self, chain_store: &mut FsChainStore<ChainAction>) {
+        if let Some(err) = chain_store.take_index_error() {
+            eprintln!("Could not save the block index: {}", err);
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Create a garden in the working directory.
+    Init(init::Options),
+    /// Play the garden in a terminal window.
+    Play(play::Options),
+    /// Share the garden with peers on the network.
+    Serve(serve::Options),
+    /// List the blocks of a chain, similar to the Unix cat command.
+    Cat(cat::Options),
+    /// Show the history of the chains, and where they diverge.
+    Log(log::Options),
+    /// Compare two chains, showing the blocks and changes unique to each.
+    Diff(diff::Options),
+    /// List the heads, and the blocks they point to.
+    Heads,
+    /// Check that every head loads, and that its blocks are intact.
+    Fsck,
+    /// Remove the chain chunks that no head leads to.
+    Gc(gc::Options),
+    /// Move chains between gardens with bundle files, similar to git bundles.
+    Bundle(bundle::Options),
+    /// List, fetch and merge the heads of peers.
+    Remote(remote::Options),
+    /// Show the peer id of the garden, and manage the known peers.
+    Peers(peers::Options),
+}
+
+fn main() -> Result<()> {
+    let CliOptions { global, command } = CliOptions::from_args();
+    match command {
+        Command::Init(options) => init::run(&global, options),
+        Command::Play(options) => play::run(&global, options),
+        Command::Serve(options) => serve::run(&global, options),
+        Command::Cat(options) => cat::run(&global, options),
+        Command::Log(options) => log::run(&global, options),
+        Command::Diff(options) => diff::run(&global, options),
+        Command::Heads => heads::run(&global),
+        Command::Fsck => fsck::run(&global),
+        Command::Gc(options) => gc::run(&global, options),
+        Command::Bundle(options) => bundle::run(&global, options),
+        Command::Remote(options) => remote::run(&global, options),
+        Command::Peers(options) => peers::run(&global, options),
+    }
+}
@@ -0,0 +1,121 @@
+//! Show the peer id of the garden, and manage the address book of known peers.
+
+use anyhow::{bail, Result};
+use chrono::{TimeZone, Utc};
+use libp2p::PeerId;
+use structopt::StructOpt;
+
+use garden::{
+    garden_dir::GardenDir,
+    peers::{AddressBook, Trust},
+};
+
+use crate::GlobalOptions;
+
+#[derive(Debug, StructOpt)]
+pub enum Options {
+    /// Print the peer id the garden is known by on the network.
+    Id,
+    /// List the known peers, with where they were last seen.
+    List,
+    /// Give a peer a nickname, which can be used instead of its id. Leave the nickname
+    /// out to remove it.
+    Nickname {
+        /// The peer id, or current nickname.
+        peer: String,
+        nickname: Option<String>,
+    },
+    /// Set how much a peer is trusted: unknown, trusted or blocked. The heads of
+    /// trusted peers can be merged by `serve --merge`, and blocked peers are ignored.
+    Trust {
+        /// The peer id, or nickname.
+        peer: String,
+        #[structopt(parse(try_from_str = parse_trust))]
+        trust: Trust,
+    },
+    /// Remove a peer from the address book.
+    Forget {
+        /// The peer id, or nickname.
+        peer: String,
+    },
+}
+
+fn parse_trust(text: &str) -> Result<Trust> {
+    Ok(match text {
+        "unknown" => Trust::Unknown,
+        "trusted" => Trust::Trusted,
+        "blocked" => Trust::Blocked,
+        _ => bail!("Expected unknown, trusted or blocked."),
+    })
+}
+
+pub fn run(global: &GlobalOptions, options: Options) -> Result<()> {
+    let garden_dir = global.garden_dir()?;
+    let mut address_book = garden_dir.address_book()?;
+    match options {
+        Options::Id => {
+            println!("{}", PeerId::from(garden_dir.identity()?.public()));
+        }
+        Options::List => {
+            for (peer_id, peer) in &address_book.peers {
+                let last_seen = peer
+                    .last_seen
+                    .and_then(|timestamp| Utc.timestamp_opt(timestamp, 0).single())
+                    .map_or_else(|| "never".into(), |time| time.to_rfc2822());
+                println!(
+                    "{} {:?} {}",
+                    peer_id,
+                    peer.trust,
+                    peer.nickname.as_deref().unwrap_or("")
+                );
+                println!("    last seen {}", last_seen);
+                for address in &peer.addresses {
+                    println!("    {}", address);
+                }
+            }
+        }
+        Options::Nickname { peer, nickname } => {
+            let peer_id = known_or_new_peer(&address_book, &peer)?;
+            if let Some(nickname) = &nickname {
+                match address_book.find(nickname) {
+                    Some((other, _)) if other != peer_id => {
+                        bail!("{:?} is already used for {}.", nickname, other)
+                    }
+                    _ => {}
+                }
+            }
+            address_book.entry(&peer_id).nickname = nickname;
+            save(&garden_dir, &address_book)?;
+        }
+        Options::Trust { peer, trust } => {
+            let peer_id = known_or_new_peer(&address_book, &peer)?;
+            address_book.entry(&peer_id).trust = trust;
+            save(&garden_dir, &address_book)?;
+        }
+        Options::Forget { peer } => {
+            let peer_id = match address_book.find(&peer) {
+                Some((peer_id, _)) => peer_id.to_string(),
+                None => bail!("{:?} is not a known peer.", peer),
+            };
+            address_book.remove(&peer_id);
+            save(&garden_dir, &address_book)?;
+        }
+    }
+    Ok(())
+}
+
+/// The id of a peer in the address book, or of one that is about to be added, which
+/// must then be a valid peer id.
+fn known_or_new_peer(address_book: &AddressBook, peer: &str) -> Result<String> {
+    if let Some((peer_id, _)) = address_book.find(peer) {
+        return Ok(peer_id.into());
+    }
+    match peer.parse::<PeerId>() {
+        Ok(peer_id) => Ok(peer_id.to_base58()),
+        Err(_) => bail!("{:?} is not a known peer, or a peer id.", peer),
+    }
+}
+
+fn save(garden_dir: &GardenDir, address_book: &AddressBook) -> Result<()> {
+    Ok(garden_dir.write_address_book(address_book)?)
+}
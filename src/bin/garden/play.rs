@@ -0,0 +1,91 @@
+//! Play the garden in a terminal window.
+
+use anyhow::{anyhow, Context, Result};
+use std::{path::PathBuf, time::Duration};
+
+use garden::{
+    chain_store::LoadError,
+    game::{
+        autosave::AutosavePolicy,
+        game_state::{GameOptions, GameState},
+        keymap::KeyMap,
+    },
+    utils::path_join,
+};
+use rltk::RltkBuilder;
+use structopt::StructOpt;
+
+use crate::GlobalOptions;
+
+#[derive(Debug, StructOpt)]
+pub struct Options {
+    /// A JSON file of key bindings. Defaults to the keymap.json in the .garden
+    /// directory, if one exists.
+    #[structopt(long, parse(from_os_str))]
+    keymap: Option<PathBuf>,
+
+    /// Autosave once this many blocks haven't been saved, 0 turns it off.
+    #[structopt(long, default_value = "50")]
+    autosave_blocks: usize,
+
+    /// Autosave once unsaved blocks are this many seconds old, 0 turns it off.
+    #[structopt(long, default_value = "60")]
+    autosave_seconds: u64,
+
+    /// Ask before quitting with unsaved blocks, rather than saving them.
+    #[structopt(long)]
+    no_save_on_quit: bool,
+}
+
+impl Options {
+    fn autosave_policy(&self) -> AutosavePolicy {
+        AutosavePolicy {
+            every_blocks: Some(self.autosave_blocks).filter(|n| *n > 0),
+            every: Some(self.autosave_seconds)
+                .filter(|n| *n > 0)
+                .map(Duration::from_secs),
+            on_quit: !self.no_save_on_quit,
+        }
+    }
+}
+
+pub fn run(global: &GlobalOptions, options: Options) -> Result<()> {
+    let garden_dir = global.garden_dir()?;
+    let keymap_path = match options.keymap {
+        Some(ref path) => path.clone(),
+        None => path_join(garden_dir.path.clone(), &["keymap.json"]),
+    };
+    let keymap =
+        KeyMap::load_or_default(&keymap_path).context("Unable to load the key map.")?;
+    let autosave = options.autosave_policy();
+    let chain_store = Box::new(garden_dir.chain_store(global.head_ref()?)?);
+    let game_state = match GameState::try_new(
+        chain_store,
+        GameOptions { keymap, autosave },
+    ) {
+        Ok(game_state) => game_state,
+        Err(err) => {
+            if let Some(load_error) = err.downcast_ref::<LoadError>() {
+                if load_error.is_corrupted() {
+                    eprintln!(
+                        "The garden saved in {} is corrupted: {}",
+                        garden_dir.path.display(),
+                        load_error
+                    );
+                    eprintln!(
+                        "Restore it from a backup, or move it aside to start a new garden."
+                    );
+                    std::process::exit(1);
+                }
+            }
+            return Err(err);
+        }
+    };
+
+    // Build the terminal.
+    let context = RltkBuilder::simple80x50()
+        .with_title("Garden")
+        .build()
+        .map_err(|err| anyhow!(err))?;
+    rltk::main_loop(context, game_state).map_err(|err| anyhow!(err))
+}
@@ -0,0 +1,108 @@
+//! Work with the remote-tracking heads, which record what each peer last advertised
+//! in remotes/<peer>/<head>, without touching the local heads.
+
+use anyhow::{Context, Result};
+use std::{fs::File, io::BufReader, path::PathBuf};
+
+use garden::{
+    bundle::Bundle, chain_store::HeadRef, ref_name::REMOTES_NAMESPACE,
+    store::RemoteChanges, ChainAction, Hash,
+};
+use structopt::StructOpt;
+
+use crate::GlobalOptions;
+
+#[derive(Debug, StructOpt)]
+pub enum Options {
+    /// List the remote-tracking heads, and the blocks they point to.
+    List {
+        /// Only list the heads of this peer.
+        peer: Option<String>,
+    },
+    /// Record the blocks of a peer's bundle file in remotes/<peer>/<head>, without
+    /// merging them into a local head. The blocks are otherwise fetched by serve.
+    Fetch {
+        /// The peer id the bundle came from.
+        peer: String,
+        /// The bundle file to read.
+        file: PathBuf,
+    },
+    /// Merge a remote-tracking head into the head. The longer chain wins, as with
+    /// blocks from the network.
+    Merge {
+        /// The remote-tracking head, e.g. remotes/<peer>/my-garden, or just
+        /// <peer>/my-garden.
+        remote: String,
+    },
+}
+
+pub fn run(global: &GlobalOptions, options: Options) -> Result<()> {
+    let garden_dir = global.garden_dir()?;
+    match options {
+        Options::List { peer } => {
+            for head_ref in garden_dir.remote_head_refs(peer.as_deref())? {
+                let chain_store =
+                    garden_dir.chain_store::<ChainAction>(head_ref.clone())?;
+                let hash = match chain_store.head_hash() {
+                    Ok(Some(hash)) => hash.short(),
+                    Ok(None) => "(none)".into(),
+                    Err(_) => "(broken)".into(),
+                };
+                println!("{} {}", hash, head_ref.str());
+            }
+        }
+        Options::Fetch { peer, file } => {
+            let reader = BufReader::new(
+                File::open(&file)
+                    .with_context(|| format!("Failed to open {:?}", file))?,
+            );
+            let bundle = Bundle::<ChainAction>::read(reader)
+                .with_context(|| format!("Failed to read the bundle {:?}", file))?;
+            let remote_ref =
+                HeadRef::remote(&peer, &HeadRef::try_from(bundle.head_ref.clone())?)
+                    .context("The peer id can't be used in a ref name.")?;
+            let fetched = garden_dir.fetch(&remote_ref, &bundle.blocks)?;
+            if fetched.is_up_to_date() {
+                println!("{:?} is already up to date", remote_ref.str());
+            } else {
+                let short = |hash: Option<Hash>| {
+                    hash.map_or_else(|| "(none)".into(), |hash| hash.short())
+                };
+                println!(
+                    "Fetched {:?}: {} -> {}",
+                    remote_ref.str(),
+                    short(fetched.previous),
+                    short(fetched.tip)
+                );
+            }
+        }
+        Options::Merge { remote } => {
+            let name = if remote.starts_with(&format!("{}/", REMOTES_NAMESPACE)) {
+                remote
+            } else {
+                format!("{}/{}", REMOTES_NAMESPACE, remote)
+            };
+            let remote_ref = HeadRef::try_from(name)
+                .context("An invalid remote-tracking head was provided.")?;
+            let head_ref = global.head_ref()?;
+            let changes = garden_dir.merge::<ChainAction>(&remote_ref, &head_ref)?;
+            match changes {
+                RemoteChanges {
+                    added: 0,
+                    removed: 0,
+                    ..
+                } => println!("{:?} is already up to date", head_ref.str()),
+                RemoteChanges {
+                    added, removed: 0, ..
+                } => println!("Added {} blocks to {:?}", added, head_ref.str()),
+                RemoteChanges { added, removed, .. } => println!(
+                    "Added {} blocks to {:?}, replacing {}",
+                    added,
+                    head_ref.str(),
+                    removed
+                ),
+            }
+        }
+    }
+    Ok(())
+}
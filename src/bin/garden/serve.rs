@@ -0,0 +1,348 @@
+//! Share the garden with peers on the network.
+
+use anyhow::{anyhow, Result};
+use futures::{channel::mpsc, prelude::*};
+use garden::{
+    block_chain::Block,
+    chain_store::HeadRef,
+    garden_dir::GardenDir,
+    peers::{display_name, AddressBook, Trust},
+    store_actor::{StoreActor, StoreHandle},
+    utils::get_timestamp,
+    ChainAction, Store,
+};
+use libp2p::{
+    core::upgrade,
+    floodsub::{self, Floodsub, FloodsubEvent},
+    mdns::{Mdns, MdnsEvent},
+    mplex, noise,
+    swarm::{NetworkBehaviourEventProcess, SwarmBuilder, SwarmEvent},
+    tcp::TokioTcpConfig,
+    Multiaddr, NetworkBehaviour, PeerId, Transport,
+};
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+use tokio::io::{self, AsyncBufReadExt};
+
+use crate::GlobalOptions;
+
+#[derive(Debug, StructOpt)]
+pub struct Options {
+    /// Multi-address to listen on.
+    #[structopt(long, default_value = "/ip4/0.0.0.0/tcp/0")]
+    listen_on: String,
+
+    /// Multi-address to connect to, e.g. "/ip4/1.2.3.4/tcp/5678", or the peer id or
+    /// nickname of a known peer, to connect to where it was last seen.
+    #[structopt(long)]
+    connect_to: Option<String>,
+
+    /// Also merge the blocks that trusted peers advertise into the head, rather than
+    /// only recording them in remotes/<peer>/<head>. See `garden peers trust`.
+    #[structopt(long)]
+    merge: bool,
+}
+
+/// What a peer sends to share the chain of one of its heads, which is recorded in a
+/// remote-tracking head, see `GardenDir::fetch`.
+#[derive(Serialize, Deserialize, Debug)]
+struct HeadAdvertisement {
+    head_ref: String,
+    blocks: Vec<Block<ChainAction>>,
+}
+
+/// An advertisement that was received, and whether it should be merged as well.
+struct Received {
+    peer: PeerId,
+    advertisement: HeadAdvertisement,
+    merge: bool,
+}
+
+pub fn run(global: &GlobalOptions, options: Options) -> Result<()> {
+    let garden_dir = global.garden_dir()?;
+    let head_ref = global.head_ref()?;
+
+    // The store isn't thread safe, so it lives on its own thread, and the network
+    // task talks to it through a handle.
+    let store_garden_dir = garden_dir.clone();
+    let (store, _store_thread) = StoreActor::spawn(move || {
+        let chain_store = store_garden_dir.chain_store::<ChainAction>(head_ref)?;
+        Ok(Store::try_new(Box::new(chain_store))?)
+    })?;
+
+    tokio::runtime::Runtime::new()?.block_on(serve(garden_dir, store, options))
+}
+
+async fn serve(
+    garden_dir: GardenDir,
+    store: StoreHandle,
+    options: Options,
+) -> Result<()> {
+    let block_count = store.query(|store| store.chains.loaded_block_count())?;
+    println!("Loaded {} blocks", block_count);
+
+    // The keypair is kept in the garden, so peers see the same id every run.
+    let local_key = garden_dir.identity()?;
+    let local_peer_id = PeerId::from(local_key.public());
+    println!("Local peer id: {:?}", local_peer_id);
+    let address_book = garden_dir.address_book()?;
+
+    // Create a tokio-based TCP transport use noise for authenticated
+    // encryption and Mplex for multiplexing of substreams on a TCP stream.
+    let transport = TokioTcpConfig::new()
+        .nodelay(true)
+        .upgrade(upgrade::Version::V1)
+        .authenticate(
+            noise::NoiseConfig::xx({
+                noise::Keypair::<noise::X25519Spec>::new()
+                    .into_authentic(&local_key)
+                    .expect("Signing libp2p-noise static DH keypair failed.")
+            })
+            .into_authenticated(),
+        )
+        .multiplex(mplex::MplexConfig::new())
+        .boxed();
+
+    // Create a Floodsub topic
+    let floodsub_topic = floodsub::Topic::new("chat");
+
+    // We create a custom network behaviour that combines floodsub and mDNS.
+    // The derive generates a delegating `NetworkBehaviour` impl which in turn
+    // requires the implementations of `NetworkBehaviourEventProcess` for
+    // the events of each behaviour.
+    #[derive(NetworkBehaviour)]
+    #[behaviour(event_process = true)]
+    struct MyBehaviour {
+        floodsub: Floodsub,
+        mdns: Mdns,
+        #[behaviour(ignore)]
+        received: mpsc::UnboundedSender<Received>,
+        #[behaviour(ignore)]
+        garden_dir: GardenDir,
+        #[behaviour(ignore)]
+        address_book: AddressBook,
+        #[behaviour(ignore)]
+        merge: bool,
+    }
+
+    impl NetworkBehaviourEventProcess<FloodsubEvent> for MyBehaviour {
+        // Called when `floodsub` produces an event.
+        fn inject_event(&mut self, message: FloodsubEvent) {
+            if let FloodsubEvent::Message(message) = message {
+                let peer_id = message.source.to_base58();
+                let trust = self.address_book.trust(&peer_id);
+                if trust == Trust::Blocked {
+                    return;
+                }
+                let from = display_name(&self.address_book, &peer_id);
+                // Advertised heads are recorded for the peer, and only merged into
+                // the store when asked to. That touches the disk and the store, so it
+                // is done off of the network task, see `receive`.
+                match serde_json::from_slice::<HeadAdvertisement>(&message.data) {
+                    Ok(advertisement) => {
+                        println!(
+                            "Received {} ({} blocks) from {}",
+                            advertisement.head_ref,
+                            advertisement.blocks.len(),
+                            from
+                        );
+                        let received = Received {
+                            peer: message.source,
+                            advertisement,
+                            merge: self.merge && trust == Trust::Trusted,
+                        };
+                        if self.received.unbounded_send(received).is_err() {
+                            println!("Dropped an advertised head, the garden is closed");
+                        }
+                    }
+                    Err(_) => println!(
+                        "Received: {:?} from {}",
+                        String::from_utf8_lossy(&message.data),
+                        from
+                    ),
+                }
+            }
+        }
+    }
+
+    impl MyBehaviour {
+        /// Remember where a peer was seen in the address book.
+        fn saw(&mut self, peer: &PeerId, address: &Multiaddr) {
+            let timestamp = get_timestamp();
+            if self
+                .address_book
+                .saw(&peer.to_base58(), &address.to_string(), timestamp)
+            {
+                if let Err(err) = self.garden_dir.write_address_book(&self.address_book) {
+                    println!("Failed to save the address book: {}", err);
+                }
+            }
+        }
+    }
+
+    impl NetworkBehaviourEventProcess<MdnsEvent> for MyBehaviour {
+        // Called when `mdns` produces an event.
+        fn inject_event(&mut self, event: MdnsEvent) {
+            match event {
+                MdnsEvent::Discovered(list) => {
+                    for (peer, address) in list {
+                        self.saw(&peer, &address);
+                        if self.address_book.trust(&peer.to_base58()) != Trust::Blocked {
+                            self.floodsub.add_node_to_partial_view(peer);
+                        }
+                    }
+                }
+                MdnsEvent::Expired(list) => {
+                    for (peer, _) in list {
+                        if !self.mdns.has_node(&peer) {
+                            self.floodsub.remove_node_from_partial_view(&peer);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // A known peer can be dialed by its id or nickname.
+    let connect_to = match options.connect_to {
+        Some(connect_to) => Some(match connect_to.parse::<Multiaddr>() {
+            Ok(address) => address,
+            Err(_) => {
+                let (peer_id, peer) =
+                    address_book.find(&connect_to).ok_or_else(|| {
+                        anyhow!("{:?} is not an address or a known peer.", connect_to)
+                    })?;
+                let address = peer
+                    .addresses
+                    .first()
+                    .ok_or_else(|| anyhow!("No address is known for {}.", peer_id))?;
+                address.parse()?
+            }
+        }),
+        None => None,
+    };
+
+    // Received advertisements are handled one at a time, so that two of them can't
+    // write the same remote-tracking head at once.
+    let (received_sender, mut received_receiver) = mpsc::unbounded();
+    {
+        let garden_dir = garden_dir.clone();
+        let store = store.clone();
+        tokio::spawn(async move {
+            while let Some(received) = received_receiver.next().await {
+                let garden_dir = garden_dir.clone();
+                let store = store.clone();
+                let result = tokio::task::spawn_blocking(move || {
+                    receive(&garden_dir, &store, received)
+                })
+                .await;
+                if let Err(err) = result {
+                    println!("Failed to handle an advertised head: {}", err);
+                }
+            }
+        });
+    }
+
+    // Create a Swarm to manage peers and events.
+    let mut swarm = {
+        let mut behaviour = MyBehaviour {
+            floodsub: Floodsub::new(local_peer_id.clone()),
+            mdns: Mdns::new(Default::default()).await?,
+            received: received_sender,
+            garden_dir,
+            address_book,
+            merge: options.merge,
+        };
+
+        behaviour.floodsub.subscribe(floodsub_topic.clone());
+
+        SwarmBuilder::new(transport, behaviour, local_peer_id)
+            // We want the connection background tasks to be spawned
+            // onto the tokio runtime.
+            .executor(Box::new(|future| {
+                tokio::spawn(future);
+            }))
+            .build()
+    };
+
+    // Attempt to dial a client.
+    if let Some(addr) = connect_to {
+        println!("Dialing {}", addr);
+        swarm.dial(addr).expect("Failed to dial remote address");
+    }
+
+    // Start listening on the swarm.
+    swarm
+        .listen_on(options.listen_on.parse()?)
+        .expect("Failed to listen on the swarm.");
+
+    let mut stdin = io::BufReader::new(io::stdin()).lines();
+
+    // Event loop
+    loop {
+        tokio::select! {
+            line = stdin.next_line() => {
+                let line = line?.expect("stdin closed");
+                if line == "/save" {
+                    let store = store.clone();
+                    tokio::task::spawn_blocking(move || store.persist()).await??;
+                    println!("Saved");
+                    continue;
+                }
+                if line == "/advertise" {
+                    let store = store.clone();
+                    let advertisement = tokio::task::spawn_blocking(move || {
+                        store.query(|store| HeadAdvertisement {
+                            head_ref: store.chains.head_ref().str().into(),
+                            blocks: store.chains.iter_loaded().cloned().collect(),
+                        })
+                    })
+                    .await??;
+                    let message = serde_json::to_vec(&advertisement)?;
+                    swarm.behaviour_mut().floodsub.publish(floodsub_topic.clone(), message);
+                    println!("Advertised {}", advertisement.head_ref);
+                    continue;
+                }
+                swarm.behaviour_mut().floodsub.publish(floodsub_topic.clone(), line.as_bytes());
+            }
+            event = swarm.select_next_some() => {
+                match event {
+                    SwarmEvent::NewListenAddr { address, .. } => {
+                        println!("Listening on {:?}", address);
+                    }
+                    SwarmEvent::ConnectionEstablished { peer_id, endpoint, .. } => {
+                        swarm.behaviour_mut().saw(&peer_id, endpoint.get_remote_address());
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// Record an advertised head in remotes/<peer>/<head>, and merge it into the store if
+/// it is from a trusted peer. This blocks on the disk and the store.
+fn receive(garden_dir: &GardenDir, store: &StoreHandle, received: Received) {
+    let Received {
+        peer,
+        advertisement,
+        merge,
+    } = received;
+    let remote_ref = HeadRef::try_from(advertisement.head_ref.clone())
+        .and_then(|head_ref| HeadRef::remote(&peer.to_base58(), &head_ref));
+    let remote_ref = match remote_ref {
+        Ok(remote_ref) => remote_ref,
+        Err(err) => return println!("Ignored an advertised head: {}", err),
+    };
+    match garden_dir.fetch(&remote_ref, &advertisement.blocks) {
+        Ok(fetched) if fetched.is_up_to_date() => {}
+        Ok(_) => println!("Fetched {}", remote_ref.str()),
+        Err(err) => println!("Failed to fetch {}: {}", remote_ref.str(), err),
+    }
+    if merge {
+        match store.apply_remote_blocks(advertisement.blocks) {
+            Ok(changes) => println!("Applied remote blocks: {:?}", changes),
+            Err(err) => println!("Rejected remote blocks: {}", err),
+        }
+    }
+}
@@ -1,5 +1,8 @@
 use crate::{hash::Hash, utils::get_timestamp};
-use std::{borrow::Cow, collections::VecDeque};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, VecDeque},
+};
 use thiserror::Error;
 
 use ring::digest::{Context, SHA256};
@@ -108,6 +111,23 @@ where
         None
     }
 
+    /// Find the newest block that both chains share, returning its index in this chain
+    /// and in the other one.
+    pub fn common_ancestor(&self, other: &BlockChain<T>) -> Option<(usize, usize)> {
+        let other_indexes: HashMap<&Hash, usize> = other
+            .blocks
+            .iter()
+            .enumerate()
+            .map(|(index, block)| (&block.hash, index))
+            .collect();
+        for (index, block) in self.blocks.iter().enumerate().rev() {
+            if let Some(other_index) = other_indexes.get(&block.hash) {
+                return Some((index, *other_index));
+            }
+        }
+        None
+    }
+
     /// Returns true if the chain is not complete, e.g. there is not root block.
     pub fn is_partial(&self) -> bool {
         if let Some(block) = self.blocks.front() {
@@ -118,18 +138,36 @@ where
         true
     }
 
+    /// Reconcile foreign blocks with this chain. The longer chain wins. On success,
+    /// returns the index of the first block that was replaced or added, which is the
+    /// length of the chain if nothing changed.
     pub fn reconcile(
         &mut self,
         mut foreign_blocks: &[Block<T>],
-    ) -> Result<(), ReconcileError> {
+    ) -> Result<usize, ReconcileError> {
         if foreign_blocks.is_empty() {
             // No blocks to add. This is weird, but fine.
-            return Ok(());
+            return Ok(self.blocks.len());
+        }
+
+        if self.blocks.is_empty() {
+            // Everything is new, as long as it starts from a root.
+            if !foreign_blocks.first().unwrap().payload.parent.is_root() {
+                return Err(ReconcileError::NoMatchingParent);
+            }
+            if !verify_blocks(foreign_blocks, Hash::empty()) {
+                return Err(ReconcileError::MalformedBlocks);
+            }
+            self.blocks.extend(foreign_blocks.iter().cloned());
+            return Ok(0);
         }
 
         if foreign_blocks.first().unwrap().payload.parent.is_root() {
             // The first block appears to be a root block, ignore it.
             foreign_blocks = &foreign_blocks[1..];
+            if foreign_blocks.is_empty() {
+                return Ok(self.blocks.len());
+            }
         }
 
         // Try to find the parent block.
@@ -146,13 +184,11 @@ where
         let mut last_trusted_index = parent_block_index;
         for index in (parent_block_index + 1)..self.blocks.len() {
             let trusted_block = self.blocks.get(index).unwrap();
-            let foreign_block =
-                foreign_blocks.get(index - parent_block_index - 1).unwrap();
-
-            if trusted_block == foreign_block {
-                last_trusted_index = index;
-            } else {
-                break;
+            match foreign_blocks.get(index - parent_block_index - 1) {
+                Some(foreign_block) if foreign_block == trusted_block => {
+                    last_trusted_index = index;
+                }
+                _ => break,
             }
         }
 
@@ -175,7 +211,7 @@ where
         self.blocks.truncate(last_trusted_index + 1);
         self.blocks.extend(new_foreign_blocks.iter().cloned());
 
-        Ok(())
+        Ok(last_trusted_index + 1)
     }
 
     /// Return a slice of the blocks. This will potentially reorder the VecDeque as
@@ -209,8 +245,8 @@ fn debug_blocks(blocks: &[Block<String>]) -> Vec<&str> {
         .collect::<Vec<&str>>()
 }
 
-/// Ensure the blocks are valid in their structure.
-fn verify_blocks<T: BlockData>(blocks: &[Block<T>], mut parent: Hash) -> bool {
+/// Ensure the blocks are valid in their structure, starting from the parent hash.
+pub fn verify_blocks<T: BlockData>(blocks: &[Block<T>], mut parent: Hash) -> bool {
     for block in blocks {
         if block.payload.parent != parent {
             return false;
@@ -351,11 +387,12 @@ mod test {
 
         assert_ne!(trusted, foreign, "The two are different");
 
-        trusted
+        let fork_index = trusted
             .reconcile(&foreign.as_block_slice()[3..])
             .expect("Failed to reconcile blockchains.");
 
         assert_eq!(trusted, foreign, "The two are equal");
+        assert_eq!(fork_index, 3, "The losing block was replaced.");
 
         assert_eq!(
             debug_blocks(&trusted.as_block_slice()),
@@ -363,6 +400,23 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_common_ancestor() {
+        let mut left = BlockChain::<String>::new();
+        left.add_data("a".into());
+        left.add_data("b".into());
+
+        let mut right = left.clone();
+        left.add_data("c".into());
+        right.add_data("d".into());
+        right.add_data("e".into());
+
+        assert_eq!(left.common_ancestor(&right), Some((1, 1)));
+        assert_eq!(right.common_ancestor(&left), Some((1, 1)));
+        assert_eq!(left.common_ancestor(&left), Some((2, 2)));
+        assert_eq!(left.common_ancestor(&BlockChain::new()), None);
+    }
+
     #[test]
     fn test_trusting_wins() {
         let mut trusted = BlockChain::<String>::new();
@@ -396,6 +450,32 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_reconcile_known_blocks() {
+        let mut trusted = BlockChain::<String>::new();
+        trusted.add_data("a".into());
+        trusted.add_data("b".into());
+        trusted.add_data("c".into());
+        let mut foreign = trusted.clone();
+
+        assert_eq!(
+            trusted
+                .reconcile(&foreign.as_block_slice()[0..2])
+                .expect_err("Expected an error"),
+            ReconcileError::ShorterForeignBlocks,
+            "Blocks that only overlap the start of the chain are shorter."
+        );
+        assert_eq!(
+            trusted.reconcile(&foreign.as_block_slice()[0..1]),
+            Ok(3),
+            "A lone root block changes nothing."
+        );
+
+        let mut empty = BlockChain::<String>::new();
+        assert_eq!(empty.reconcile(foreign.as_block_slice()), Ok(0));
+        assert_eq!(empty, foreign);
+    }
+
     #[test]
     fn test_failed_reconcile() {
         let mut trusted = BlockChain::<String>::new();
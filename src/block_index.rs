@@ -0,0 +1,149 @@
+//! An index from block hashes to where the blocks are stored, so any block can be
+//! found without loading the chain it is in. The index is a text file with a line per
+//! block, which is only ever appended to, apart from being rewritten by a rebuild.
+//!
+//!   <block hash> <chunk hash> <offset>
+//!
+//! The chunk hash is the tip of the chunk file the block is in, and the offset is the
+//! position of the block in that file.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use crate::{
+    chain_store::{ChainStoreError, LoadError},
+    Hash,
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BlockLocation {
+    /// The tip hash of the chunk that has the block.
+    pub chunk: Hash,
+    /// The position of the block in the chunk.
+    pub offset: usize,
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub struct BlockIndex {
+    locations: HashMap<Hash, BlockLocation>,
+    /// How many bytes of the index file have been read.
+    read_len: u64,
+}
+
+impl BlockIndex {
+    pub fn get(&self, hash: &Hash) -> Option<&BlockLocation> {
+        self.locations.get(hash)
+    }
+
+    pub fn hashes(&self) -> impl Iterator<Item = &Hash> {
+        self.locations.keys()
+    }
+
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+
+    pub fn insert(&mut self, hash: Hash, location: BlockLocation) {
+        self.locations.insert(hash, location);
+    }
+
+    /// Read the index file.
+    pub fn read(path: &Path) -> Result<Self, LoadError> {
+        let mut index = Self::default();
+        index.refresh(path)?;
+        Ok(index)
+    }
+
+    /// Read the lines that were appended since the file was last read, e.g. by
+    /// another chain store. A missing file has nothing to read.
+    pub fn refresh(&mut self, path: &Path) -> Result<(), LoadError> {
+        let io_error = |source| LoadError::Io {
+            path: path.to_path_buf(),
+            source,
+        };
+        let mut file = match fs::File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(io_error(err)),
+        };
+        let len = file.metadata().map_err(io_error)?.len();
+        if len < self.read_len {
+            // The index was rebuilt, so start over.
+            *self = Self::default();
+        }
+        file.seek(SeekFrom::Start(self.read_len))
+            .map_err(io_error)?;
+        let mut text = String::new();
+        file.read_to_string(&mut text).map_err(io_error)?;
+
+        // Only whole lines are read, a partly written one is read next time.
+        let complete_len = text.rfind('\n').map_or(0, |end| end + 1);
+        for line in text[..complete_len].lines() {
+            let (hash, location) =
+                parse_line(line).ok_or_else(|| LoadError::MalformedIndex {
+                    path: path.to_path_buf(),
+                    line: line.into(),
+                })?;
+            self.locations.insert(hash, location);
+        }
+        self.read_len += complete_len as u64;
+        Ok(())
+    }
+
+    /// Add the locations of newly persisted blocks to the end of the index file.
+    pub fn append(
+        path: &Path,
+        entries: &[(Hash, BlockLocation)],
+    ) -> Result<(), ChainStoreError> {
+        let mut file = fs::OpenOptions::new()
+            .append(true)
+            .open(path)
+            .map_err(ChainStoreError::io("failed to open the block index", path))?;
+        file.write_all(
+            format_lines(entries.iter().map(|(hash, location)| (hash, location)))
+                .as_bytes(),
+        )
+        .map_err(ChainStoreError::io("failed to write the block index", path))
+    }
+
+    /// Replace the index file with every location in this index.
+    pub fn write(&mut self, path: &Path) -> Result<(), ChainStoreError> {
+        let text = format_lines(self.locations.iter());
+        fs::write(path, &text)
+            .map_err(ChainStoreError::io("failed to write the block index", path))?;
+        self.read_len = text.len() as u64;
+        Ok(())
+    }
+}
+
+fn format_lines<'a>(
+    entries: impl Iterator<Item = (&'a Hash, &'a BlockLocation)>,
+) -> String {
+    let mut text = String::new();
+    for (hash, location) in entries {
+        text.push_str(&format!(
+            "{} {} {}\n",
+            hash, location.chunk, location.offset
+        ));
+    }
+    text
+}
+
+fn parse_line(line: &str) -> Option<(Hash, BlockLocation)> {
+    let mut parts = line.split(' ');
+    let hash = Hash::try_from(parts.next()?).ok()?;
+    let chunk = Hash::try_from(parts.next()?).ok()?;
+    let offset = parts.next()?.parse().ok()?;
+    if parts.next().is_some() {
+        return None;
+    }
+    Some((hash, BlockLocation { chunk, offset }))
+}
@@ -0,0 +1,302 @@
+//! Bundles move blocks between gardens without a network, similar to git bundles. A
+//! bundle is a single JSON file with a range of blocks, and the hash of the block the
+//! range starts from, which the importing chain must already have.
+
+use std::io;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    block_chain::{verify_blocks, Block, BlockData, ReconcileError},
+    chain_store::{ChainStoreError, LoadError},
+    ref_name::{self, RefNameError},
+    ChainStore, Hash,
+};
+
+/// Identifies a bundle file.
+pub const BUNDLE_FORMAT: &str = "garden-bundle";
+pub const BUNDLE_VERSION: u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum BundleError {
+    #[error("the file is not a garden bundle")]
+    NotABundle,
+    #[error("version {0} bundles are not supported, expected version {BUNDLE_VERSION}")]
+    UnsupportedVersion(u32),
+    #[error("the bundle's head ref {name:?} is not a valid name")]
+    InvalidHeadRef {
+        name: String,
+        #[source]
+        reason: RefNameError,
+    },
+    #[error("the blocks of the bundle do not form a chain from its base")]
+    MalformedBlocks,
+    #[error("the block {0} is not in the chain")]
+    UnknownBlock(Hash),
+    #[error("the chain does not have the base block {0} of the bundle")]
+    MissingBase(Hash),
+    #[error("the bundle is from an unrelated chain, with the root {0}")]
+    UnrelatedChain(Hash),
+    #[error("failed to read the bundle")]
+    Read(#[source] serde_json::Error),
+    #[error("failed to write the bundle")]
+    Write(#[source] serde_json::Error),
+    #[error(transparent)]
+    Load(#[from] LoadError),
+    #[error(transparent)]
+    Reconcile(#[from] ReconcileError),
+    #[error(transparent)]
+    ChainStore(#[from] ChainStoreError),
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Bundle<T> {
+    /// This is always BUNDLE_FORMAT.
+    pub format: String,
+    pub version: u32,
+    /// The head the blocks were bundled from.
+    pub head_ref: String,
+    /// The parent of the first block. This is the root hash when the bundle has the
+    /// whole chain.
+    pub base: Hash,
+    pub blocks: Vec<Block<T>>,
+}
+
+impl<T: BlockData> Bundle<T> {
+    /// Bundle the blocks of a chain that come after the base, or the whole chain when
+    /// there is no base.
+    pub fn create(
+        chain_store: &mut dyn ChainStore<T>,
+        base: Option<&Hash>,
+    ) -> Result<Self, BundleError> {
+        let head_ref = chain_store.head_ref().str().to_string();
+        let mut blocks: Vec<Block<T>> = chain_store.iter_all()?.cloned().collect();
+        let base = match base {
+            Some(base) => {
+                let index = blocks
+                    .iter()
+                    .position(|block| block.hash == *base)
+                    .ok_or_else(|| BundleError::UnknownBlock(base.clone()))?;
+                blocks.drain(..=index);
+                base.clone()
+            }
+            None => Hash::empty(),
+        };
+        Ok(Self {
+            format: BUNDLE_FORMAT.into(),
+            version: BUNDLE_VERSION,
+            head_ref,
+            base,
+            blocks,
+        })
+    }
+
+    pub fn write(&self, writer: impl io::Write) -> Result<(), BundleError> {
+        serde_json::to_writer(writer, self).map_err(BundleError::Write)
+    }
+
+    /// Read a bundle, checking that it is one, and that its blocks are intact.
+    pub fn read(reader: impl io::Read) -> Result<Self, BundleError> {
+        let value: serde_json::Value =
+            serde_json::from_reader(reader).map_err(BundleError::Read)?;
+        if value.get("format").and_then(|format| format.as_str()) != Some(BUNDLE_FORMAT) {
+            return Err(BundleError::NotABundle);
+        }
+        let version = value.get("version").and_then(|version| version.as_u64());
+        if version != Some(BUNDLE_VERSION as u64) {
+            return Err(BundleError::UnsupportedVersion(version.unwrap_or(0) as u32));
+        }
+        let bundle: Self = serde_json::from_value(value).map_err(BundleError::Read)?;
+        // The head ref is written to disk on import, so it can't be trusted as is.
+        ref_name::validate(&bundle.head_ref).map_err(|reason| {
+            BundleError::InvalidHeadRef {
+                name: bundle.head_ref.clone(),
+                reason,
+            }
+        })?;
+        if !verify_blocks(&bundle.blocks, bundle.base.clone()) {
+            return Err(BundleError::MalformedBlocks);
+        }
+        Ok(bundle)
+    }
+
+    /// Add the blocks to the chain, and persist it. The longer chain wins, as with
+    /// blocks from the network. Returns how many blocks were added, which is 0 when
+    /// the chain already has every block of the bundle.
+    pub fn import(
+        &self,
+        chain_store: &mut dyn ChainStore<T>,
+    ) -> Result<usize, BundleError> {
+        if !verify_blocks(&self.blocks, self.base.clone()) {
+            return Err(BundleError::MalformedBlocks);
+        }
+        let tip = match self.blocks.last() {
+            Some(block) => &block.hash,
+            None => return Ok(0),
+        };
+        // This loads the whole chain, so that the bundle is reconciled against it.
+        let mut blocks = chain_store.iter_all()?;
+        let root = blocks.next().map(|block| block.hash.clone());
+        let mut has_base = root.as_ref() == Some(&self.base);
+        let mut has_tip = root.as_ref() == Some(tip);
+        for block in blocks {
+            has_base |= block.hash == self.base;
+            has_tip |= block.hash == *tip;
+        }
+        if has_tip {
+            return Ok(0);
+        }
+        if self.base.is_root() {
+            let bundle_root = &self.blocks[0].hash;
+            if root.is_some() && root.as_ref() != Some(bundle_root) {
+                return Err(BundleError::UnrelatedChain(bundle_root.clone()));
+            }
+        } else if !has_base {
+            return Err(BundleError::MissingBase(self.base.clone()));
+        }
+        let fork_index = chain_store.reconcile(&self.blocks)?;
+        let added = chain_store.loaded_block_count() - fork_index;
+        chain_store.persist()?;
+        Ok(added)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::chain_store::{FsChainStore, HeadRef};
+    use tempdir::TempDir;
+
+    fn get_store(dir: &TempDir, head: &'static str) -> FsChainStore<String> {
+        FsChainStore::try_new(
+            dir.path().join(".garden"),
+            HeadRef::try_from(head).unwrap(),
+        )
+        .expect("Failed to create the chain store.")
+    }
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let from_dir = TempDir::new("garden-bundle-from").unwrap();
+        let mut from = get_store(&from_dir, "garden-1");
+        from.add("a".into());
+        from.add("b".into());
+        from.persist().unwrap();
+
+        // Move the whole chain to another garden.
+        let mut file = vec![];
+        Bundle::create(&mut from, None)
+            .unwrap()
+            .write(&mut file)
+            .unwrap();
+        let to_dir = TempDir::new("garden-bundle-to").unwrap();
+        let mut to = get_store(&to_dir, "garden-1");
+        let bundle = Bundle::<String>::read(file.as_slice()).unwrap();
+        assert_eq!(bundle.head_ref, "garden-1");
+        assert_eq!(bundle.import(&mut to).unwrap(), 2);
+
+        // Then only the new blocks.
+        let base = from.chain.tip().unwrap().hash.clone();
+        from.add("c".into());
+        from.persist().unwrap();
+        let bundle = Bundle::create(&mut from, Some(&base)).unwrap();
+        assert_eq!(bundle.blocks.len(), 1);
+        assert_eq!(bundle.import(&mut to).unwrap(), 1);
+
+        let mut reloaded = get_store(&to_dir, "garden-1");
+        let blocks: Vec<&str> = reloaded
+            .iter_all()
+            .unwrap()
+            .map(|block| block.payload.data.as_str())
+            .collect();
+        assert_eq!(
+            blocks,
+            ["a", "b", "c"],
+            "The imported blocks were persisted."
+        );
+
+        // The blocks are already persisted for the new head, but its ref is written.
+        let mut copy = get_store(&to_dir, "garden-4");
+        let whole = Bundle::create(&mut reloaded, None).unwrap();
+        assert_eq!(whole.import(&mut copy).unwrap(), 3);
+        assert_eq!(
+            get_store(&to_dir, "garden-4").head_hash().unwrap(),
+            Some(reloaded.chain.tip().unwrap().hash.clone())
+        );
+
+        // An unrelated chain is an error, whether it is shorter or longer, and
+        // doesn't replace the persisted one.
+        let mut unrelated = get_store(&from_dir, "garden-3");
+        unrelated.add("x".into());
+        let short = Bundle::create(&mut unrelated, None).unwrap();
+        for _ in 0..4 {
+            unrelated.add("y".into());
+        }
+        let long = Bundle::create(&mut unrelated, None).unwrap();
+        let mut to = get_store(&to_dir, "garden-1");
+        for unrelated in [short, long] {
+            assert!(matches!(
+                unrelated.import(&mut to),
+                Err(BundleError::UnrelatedChain(_))
+            ));
+        }
+        assert_eq!(
+            get_store(&to_dir, "garden-1").iter_all().unwrap().count(),
+            3
+        );
+
+        // An older bundle is already contained, so it is up to date.
+        assert_eq!(bundle.import(&mut to).unwrap(), 0);
+        let mut to = get_store(&to_dir, "garden-1");
+        let older = {
+            let mut from = get_store(&from_dir, "garden-1");
+            let mut bundle = Bundle::create(&mut from, None).unwrap();
+            bundle.blocks.truncate(1);
+            bundle
+        };
+        assert_eq!(older.import(&mut to).unwrap(), 0);
+
+        // The base has to be in the chain.
+        let mut other = get_store(&to_dir, "garden-2");
+        assert!(matches!(
+            bundle.import(&mut other),
+            Err(BundleError::MissingBase(_))
+        ));
+    }
+
+    #[test]
+    fn test_read_errors() {
+        assert!(matches!(
+            Bundle::<String>::read(r#"{"blocks": []}"#.as_bytes()),
+            Err(BundleError::NotABundle)
+        ));
+        assert!(matches!(
+            Bundle::<String>::read(
+                r#"{"format": "garden-bundle", "version": 9}"#.as_bytes()
+            ),
+            Err(BundleError::UnsupportedVersion(9))
+        ));
+
+        let dir = TempDir::new("garden-bundle").unwrap();
+        let mut store = get_store(&dir, "garden-1");
+        store.add("a".into());
+        let mut bundle = Bundle::create(&mut store, None).unwrap();
+        bundle.blocks[0].payload.data = "tampered".into();
+        let mut file = vec![];
+        bundle.write(&mut file).unwrap();
+        assert!(matches!(
+            Bundle::<String>::read(file.as_slice()),
+            Err(BundleError::MalformedBlocks)
+        ));
+
+        let mut bundle = Bundle::create(&mut store, None).unwrap();
+        bundle.head_ref = "../../escaped".into();
+        let mut file = vec![];
+        bundle.write(&mut file).unwrap();
+        assert!(matches!(
+            Bundle::<String>::read(file.as_slice()),
+            Err(BundleError::InvalidHeadRef { .. })
+        ));
+    }
+}
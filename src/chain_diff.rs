@@ -0,0 +1,186 @@
+//! Compare two chains, e.g. before syncing with a teammate's garden. The blocks are
+//! split at the newest block both chains share, and each chain is reduced to a State
+//! so the effect of the differing blocks can be shown.
+
+use std::{fmt, rc::Rc};
+
+use im_rc::ordmap::DiffItem;
+
+use crate::{
+    block_chain::{Block, BlockChain},
+    game::primitives::Position,
+    garden::GardenPlot,
+    selectors, Action, ChainAction, State,
+};
+
+#[derive(Debug)]
+pub struct ChainDiff<'a> {
+    /// The newest block both chains share, and its index in the left and right chain.
+    pub common_ancestor: Option<(&'a Block<ChainAction>, usize, usize)>,
+    /// The blocks after the common ancestor in the left chain.
+    pub only_left: Vec<&'a Block<ChainAction>>,
+    /// The blocks after the common ancestor in the right chain.
+    pub only_right: Vec<&'a Block<ChainAction>>,
+    /// How the left state changes into the right one.
+    pub state: StateDiff,
+}
+
+impl<'a> ChainDiff<'a> {
+    pub fn new(
+        left: &'a BlockChain<ChainAction>,
+        right: &'a BlockChain<ChainAction>,
+    ) -> Self {
+        let common_ancestor = left.common_ancestor(right);
+        let (left_start, right_start) = match common_ancestor {
+            Some((left_index, right_index)) => (left_index + 1, right_index + 1),
+            None => (0, 0),
+        };
+        Self {
+            common_ancestor: common_ancestor.map(|(left_index, right_index)| {
+                (&left.blocks[left_index], left_index, right_index)
+            }),
+            only_left: left.blocks.iter().skip(left_start).collect(),
+            only_right: right.blocks.iter().skip(right_start).collect(),
+            state: StateDiff::new(reduce_chain(left), reduce_chain(right)),
+        }
+    }
+}
+
+fn reduce_chain(chain: &BlockChain<ChainAction>) -> Rc<State> {
+    let mut state = State::new();
+    for block in &chain.blocks {
+        state = state.reduce(&Action::Chain(block.payload.data.clone()));
+    }
+    Rc::new(state)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlotChange {
+    Added(Rc<GardenPlot>),
+    Removed(Rc<GardenPlot>),
+    Changed {
+        old: Rc<GardenPlot>,
+        new: Rc<GardenPlot>,
+    },
+}
+
+/// The difference between two states.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateDiff {
+    pub plots: Vec<PlotChange>,
+    /// The old and new player position, if it changed.
+    pub player_position: Option<(Option<Position>, Option<Position>)>,
+}
+
+impl StateDiff {
+    pub fn new(old: Rc<State>, new: Rc<State>) -> Self {
+        let old_plots = selectors::get_plots(old.clone());
+        let new_plots = selectors::get_plots(new.clone());
+        // The maps are persistent, so only the plots that differ are visited.
+        let plots = old_plots
+            .diff(&new_plots)
+            .map(|item| match item {
+                DiffItem::Add(_, plot) => PlotChange::Added(plot.clone()),
+                DiffItem::Remove(_, plot) => PlotChange::Removed(plot.clone()),
+                DiffItem::Update { old, new } => PlotChange::Changed {
+                    old: old.1.clone(),
+                    new: new.1.clone(),
+                },
+            })
+            .collect();
+
+        let old_position = selectors::get_checkpoint_position(old);
+        let new_position = selectors::get_checkpoint_position(new);
+        Self {
+            plots,
+            player_position: if old_position == new_position {
+                None
+            } else {
+                Some((old_position, new_position))
+            },
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plots.is_empty() && self.player_position.is_none()
+    }
+}
+
+impl fmt::Display for StateDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn position(position: &Option<Position>) -> String {
+            match position {
+                Some(position) => format!("({}, {})", position.x, position.y),
+                None => "nowhere".into(),
+            }
+        }
+
+        for change in &self.plots {
+            match change {
+                PlotChange::Added(plot) => writeln!(f, "+ plot {:?}", plot.name)?,
+                PlotChange::Removed(plot) => writeln!(f, "- plot {:?}", plot.name)?,
+                PlotChange::Changed { old, new } => {
+                    writeln!(f, "~ plot {:?} is now {:?}", old.name, new.name)?
+                }
+            }
+        }
+        if let Some((old, new)) = &self.player_position {
+            writeln!(
+                f,
+                "~ player moved from {} to {}",
+                position(old),
+                position(new)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::TimeStampScope;
+
+    fn move_player(x: i32) -> ChainAction {
+        ChainAction::MovePlayer((Position::new(x, 0), Position::new(0, 0)))
+    }
+
+    fn describe(blocks: &[&Block<ChainAction>]) -> Vec<String> {
+        blocks
+            .iter()
+            .map(|block| block.payload.data.describe())
+            .collect()
+    }
+
+    #[test]
+    fn test_chain_diff() {
+        let _scope = TimeStampScope::new();
+        let mut left = BlockChain::new();
+        left.add_data(ChainAction::CreatePlot(GardenPlot::new("Shared".into())));
+        left.add_data(move_player(1));
+
+        let mut right = left.clone();
+        left.add_data(move_player(2));
+        right.add_data(ChainAction::CreatePlot(GardenPlot::new("Mine".into())));
+        right.add_data(move_player(3));
+
+        let diff = ChainDiff::new(&left, &right);
+        let (ancestor, left_index, right_index) = diff.common_ancestor.unwrap();
+        assert_eq!(ancestor.payload.data, move_player(1));
+        assert_eq!((left_index, right_index), (1, 1));
+        assert_eq!(describe(&diff.only_left), ["Saved the player at (2, 0)"]);
+        assert_eq!(
+            describe(&diff.only_right),
+            ["Created the plot \"Mine\"", "Saved the player at (3, 0)"]
+        );
+        assert_eq!(
+            diff.state.to_string(),
+            "+ plot \"Mine\"\n~ player moved from (2, 0) to (3, 0)\n"
+        );
+
+        let diff = ChainDiff::new(&left, &left);
+        assert!(diff.only_left.is_empty());
+        assert!(diff.only_right.is_empty());
+        assert!(diff.state.is_empty());
+    }
+}
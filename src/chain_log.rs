@@ -0,0 +1,241 @@
+//! Render the history of one or more heads as a graph, similar to `git log --graph`.
+//!
+//! ```text
+//! * 5d3a9c0b 2021-12-31 18:02:00 (garden-2) Saved the player at (3, 4)
+//! | * 0a1b2c3d 2021-12-31 18:01:00 (garden-1) Saved the player at (1, 2)
+//! |/
+//! * 9f8e7d6c 2021-12-31 18:00:00 Created the plot "Greg's plot"
+//! ```
+
+use std::collections::HashMap;
+
+use crate::{block_chain::Block, chain_query::format_time, ChainAction, Hash};
+
+/// The blocks of a head, from the root to the tip.
+pub struct HeadHistory<'a> {
+    pub name: &'a str,
+    pub blocks: &'a [Block<ChainAction>],
+}
+
+/// Render the history of the heads, newest first. Blocks shared between heads are
+/// only shown once, and every head gets its own lane from where it diverges.
+pub fn render(heads: &[HeadHistory]) -> Vec<String> {
+    let mut blocks: HashMap<&Hash, &Block<ChainAction>> = HashMap::new();
+    let mut labels: HashMap<&Hash, Vec<&str>> = HashMap::new();
+    for head in heads {
+        for block in head.blocks {
+            blocks.insert(&block.hash, block);
+        }
+        if let Some(tip) = head.blocks.last() {
+            labels.entry(&tip.hash).or_default().push(head.name);
+        }
+    }
+
+    // A block is shown once all of its children have been.
+    let mut pending_children: HashMap<&Hash, usize> = HashMap::new();
+    for block in blocks.values() {
+        *pending_children.entry(&block.payload.parent).or_default() += 1;
+    }
+
+    // Heads that are behind another head are reached through that head's lane.
+    let mut lanes: Vec<&Hash> = vec![];
+    for head in heads {
+        if let Some(tip) = head.blocks.last() {
+            if !pending_children.contains_key(&tip.hash) && !lanes.contains(&&tip.hash) {
+                lanes.push(&tip.hash);
+            }
+        }
+    }
+
+    let mut lines = vec![];
+    while !lanes.is_empty() {
+        let lane = lanes
+            .iter()
+            .enumerate()
+            .filter(|(_, hash)| pending_children.get(*hash).copied().unwrap_or(0) == 0)
+            // Show the newest block first, and prefer the leftmost lane on a tie.
+            .max_by_key(|(index, hash)| {
+                (blocks[*hash].payload.timestamp, -(*index as i64))
+            })
+            .map(|(index, _)| index)
+            .expect("Logic error, the blocks form a cycle.");
+        let block = blocks[lanes[lane]];
+
+        let mut line = String::new();
+        for (index, hash) in lanes.iter().enumerate() {
+            line.push(if index == lane { '*' } else { '|' });
+            line.push(' ');
+        }
+        line.push_str(&block.hash.short());
+        line.push(' ');
+        line.push_str(&format_time(block.payload.timestamp));
+        if let Some(names) = labels.get(&block.hash) {
+            line.push_str(&format!(" ({})", names.join(", ")));
+        }
+        line.push(' ');
+        line.push_str(&block.payload.data.describe());
+        lines.push(line);
+
+        let parent = &block.payload.parent;
+        if let Some(count) = pending_children.get_mut(parent) {
+            *count -= 1;
+        }
+        if !blocks.contains_key(parent) {
+            // This is the root, or the rest of the chain was not loaded.
+            if lane + 1 < lanes.len() {
+                lines.push(collapse_line(lanes.len(), lane, None));
+            }
+            lanes.remove(lane);
+        } else if let Some(other) = lanes.iter().position(|hash| *hash == parent) {
+            // Another lane already reached the parent, so the lanes join.
+            let (keep, remove) = (lane.min(other), lane.max(other));
+            lanes[keep] = parent;
+            lines.push(collapse_line(lanes.len(), remove, Some(keep)));
+            lanes.remove(remove);
+        } else {
+            lanes[lane] = parent;
+        }
+    }
+    lines
+}
+
+/// Draw the lanes to the right of a removed lane moving one to the left. When the
+/// removed lane merges into an earlier one, the merge is drawn across the lanes in
+/// between, e.g. `|/` for neighbours, or `|_|/` when the third lane merges into the
+/// first.
+fn collapse_line(
+    lane_count: usize,
+    removed: usize,
+    merged_into: Option<usize>,
+) -> String {
+    let mut chars = vec![' '; lane_count * 2];
+    for index in 0..lane_count {
+        if index < removed {
+            chars[index * 2] = '|';
+        } else if index > removed {
+            chars[index * 2 - 1] = '/';
+        }
+    }
+    if let Some(keep) = merged_into {
+        for ch in &mut chars[keep * 2 + 1..removed * 2 - 1] {
+            if *ch == ' ' {
+                *ch = '_';
+            }
+        }
+        chars[removed * 2 - 1] = '/';
+    }
+    chars.into_iter().collect::<String>().trim_end().into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        block_chain::BlockChain, game::primitives::Position, garden::GardenPlot,
+        utils::TimeStampScope,
+    };
+
+    fn move_player(x: i32) -> ChainAction {
+        ChainAction::MovePlayer((Position::new(x, 0), Position::new(0, 0)))
+    }
+
+    /// Remove the hashes and times, which are arbitrary.
+    fn strip(lines: Vec<String>) -> Vec<String> {
+        lines
+            .iter()
+            .map(|line| {
+                let graph_end = line.find(|c: char| c.is_ascii_hexdigit());
+                match graph_end {
+                    Some(end) => format!("{}{}", &line[..end], &line[end + 29..]),
+                    None => line.clone(),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_render_forks() {
+        let _scope = TimeStampScope::new();
+        let mut trunk = BlockChain::new();
+        trunk.add_data(ChainAction::CreatePlot(GardenPlot::new("Plot".into())));
+        trunk.add_data(move_player(1));
+
+        let mut fork_a = trunk.clone();
+        let mut fork_b = trunk.clone();
+        fork_a.add_data(move_player(2));
+        fork_b.add_data(move_player(3));
+        fork_b.add_data(move_player(4));
+        fork_a.add_data(move_player(5));
+
+        let heads = [
+            HeadHistory {
+                name: "a",
+                blocks: fork_a.as_block_slice(),
+            },
+            HeadHistory {
+                name: "b",
+                blocks: fork_b.as_block_slice(),
+            },
+            HeadHistory {
+                name: "trunk",
+                blocks: trunk.as_block_slice(),
+            },
+        ];
+        assert_eq!(
+            strip(render(&heads)),
+            [
+                "* | (a) Saved the player at (5, 0)",
+                "| * (b) Saved the player at (4, 0)",
+                "| * Saved the player at (3, 0)",
+                "* | Saved the player at (2, 0)",
+                "|/",
+                "* (trunk) Saved the player at (1, 0)",
+                "* Created the plot \"Plot\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_merge_across_a_lane() {
+        let _scope = TimeStampScope::new();
+        let mut trunk = BlockChain::new();
+        trunk.add_data(ChainAction::CreatePlot(GardenPlot::new("Plot".into())));
+        trunk.add_data(move_player(1));
+
+        let mut fork_a = trunk.clone();
+        let mut fork_b = trunk.clone();
+        fork_a.add_data(move_player(2));
+        let mut fork_c = fork_a.clone();
+        fork_b.add_data(move_player(3));
+        fork_a.add_data(move_player(4));
+        fork_c.add_data(move_player(5));
+
+        let heads = [
+            HeadHistory {
+                name: "a",
+                blocks: fork_a.as_block_slice(),
+            },
+            HeadHistory {
+                name: "b",
+                blocks: fork_b.as_block_slice(),
+            },
+            HeadHistory {
+                name: "c",
+                blocks: fork_c.as_block_slice(),
+            },
+        ];
+        assert_eq!(
+            strip(render(&heads)),
+            [
+                "| | * (c) Saved the player at (5, 0)",
+                "* | | (a) Saved the player at (4, 0)",
+                "|_|/",
+                "| * (b) Saved the player at (3, 0)",
+                "* | Saved the player at (2, 0)",
+                "|/",
+                "* Saved the player at (1, 0)",
+                "* Created the plot \"Plot\"",
+            ]
+        );
+    }
+}
@@ -0,0 +1,286 @@
+//! Select and format the blocks of a chain for the command line tools.
+
+use std::{io, str::FromStr};
+
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use thiserror::Error;
+
+use crate::{block_chain::Block, ChainAction, Hash};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum QueryError {
+    #[error("the block {0} is not in the chain")]
+    UnknownBlock(Hash),
+    #[error("unknown action {0:?}, expected one of: {}", ChainAction::NAMES.join(", "))]
+    UnknownAction(String),
+    #[error(
+        "could not parse the time {0:?}, use a date (2021-12-31), an RFC 3339 time \
+        (2021-12-31T18:00:00Z), or seconds since the epoch"
+    )]
+    InvalidTime(String),
+    #[error("unknown format {0:?}, expected one of: {}", Format::NAMES.join(", "))]
+    UnknownFormat(String),
+}
+
+/// Which blocks of a chain to show. The default selects every block.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct BlockQuery {
+    /// Start from the block with this hash, including it.
+    pub from: Option<Hash>,
+    /// Only keep the last N of the blocks that match the other filters.
+    pub last: Option<usize>,
+    /// Only keep blocks with these action names, see `ChainAction::name`. An empty
+    /// list keeps every action.
+    pub actions: Vec<String>,
+    /// Only keep blocks with a timestamp at or after this one.
+    pub since: Option<i64>,
+    /// Only keep blocks with a timestamp before this one.
+    pub until: Option<i64>,
+}
+
+impl BlockQuery {
+    /// Run the query on the blocks of a chain, ordered from the root to the tip.
+    pub fn select<'a>(
+        &self,
+        blocks: impl IntoIterator<Item = &'a Block<ChainAction>>,
+    ) -> Result<Vec<&'a Block<ChainAction>>, QueryError> {
+        for action in &self.actions {
+            if !ChainAction::NAMES.contains(&action.as_str()) {
+                return Err(QueryError::UnknownAction(action.clone()));
+            }
+        }
+
+        let mut blocks: Vec<&Block<ChainAction>> = blocks.into_iter().collect();
+        if let Some(ref from) = self.from {
+            let index = blocks
+                .iter()
+                .position(|block| block.hash == *from)
+                .ok_or_else(|| QueryError::UnknownBlock(from.clone()))?;
+            blocks.drain(..index);
+        }
+
+        blocks.retain(|block| {
+            let timestamp = block.payload.timestamp;
+            (self.actions.is_empty()
+                || self.actions.iter().any(|a| a == block.payload.data.name()))
+                && self.since.is_none_or(|since| timestamp >= since)
+                && self.until.is_none_or(|until| timestamp < until)
+        });
+
+        if let Some(last) = self.last {
+            blocks.drain(..blocks.len().saturating_sub(last));
+        }
+        Ok(blocks)
+    }
+}
+
+/// Parse a time from the command line into a timestamp, in seconds.
+pub fn parse_time(text: &str) -> Result<i64, QueryError> {
+    if let Ok(seconds) = text.parse::<i64>() {
+        return Ok(seconds);
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Ok(time.timestamp());
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return Ok(date.and_hms(0, 0, 0).timestamp());
+    }
+    Err(QueryError::InvalidTime(text.into()))
+}
+
+/// Format a block timestamp in UTC, for display to a user.
+pub fn format_time(timestamp: i64) -> String {
+    match Utc.timestamp_opt(timestamp, 0).single() {
+        Some(time) => time.format("%Y-%m-%d %H:%M:%S").to_string(),
+        None => timestamp.to_string(),
+    }
+}
+
+/// How to print the blocks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// A pretty printed JSON array.
+    Json,
+    /// One JSON object per line.
+    JsonLines,
+    /// One compact line per block, with the short hash, time, and action.
+    Table,
+    /// A sentence per block describing what happened.
+    Summary,
+}
+
+impl Format {
+    pub const NAMES: &'static [&'static str] = &["json", "jsonl", "table", "summary"];
+
+    pub fn write(
+        self,
+        out: &mut impl io::Write,
+        blocks: &[&Block<ChainAction>],
+    ) -> io::Result<()> {
+        match self {
+            Format::Json => {
+                serde_json::to_writer_pretty(&mut *out, blocks)?;
+                writeln!(out)?;
+            }
+            Format::JsonLines => {
+                for block in blocks {
+                    serde_json::to_writer(&mut *out, block)?;
+                    writeln!(out)?;
+                }
+            }
+            Format::Table => {
+                for block in blocks {
+                    writeln!(
+                        out,
+                        "{} {} {:<10} {}",
+                        block.hash.short(),
+                        format_time(block.payload.timestamp),
+                        block.payload.data.name(),
+                        serde_json::to_string(&block.payload.data)?
+                    )?;
+                }
+            }
+            Format::Summary => {
+                for block in blocks {
+                    writeln!(
+                        out,
+                        "{}  {}",
+                        format_time(block.payload.timestamp),
+                        block.payload.data.describe()
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Format {
+    type Err = QueryError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "json" => Ok(Format::Json),
+            "jsonl" => Ok(Format::JsonLines),
+            "table" => Ok(Format::Table),
+            "summary" => Ok(Format::Summary),
+            _ => Err(QueryError::UnknownFormat(text.into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        block_chain::BlockChain, game::primitives::Position, garden::GardenPlot,
+        utils::TimeStampScope,
+    };
+
+    fn get_chain() -> BlockChain<ChainAction> {
+        let _scope = TimeStampScope::new();
+        let mut chain = BlockChain::new();
+        chain.add_data(ChainAction::CreatePlot(GardenPlot::new("Plot".into())));
+        for x in 1..=4 {
+            chain.add_data(ChainAction::MovePlayer((
+                Position::new(x, 0),
+                Position::new(1, 0),
+            )));
+        }
+        chain
+    }
+
+    fn select(chain: &BlockChain<ChainAction>, query: BlockQuery) -> Vec<String> {
+        query
+            .select(&chain.blocks)
+            .expect("Failed to run the query.")
+            .iter()
+            .map(|block| block.payload.data.describe())
+            .collect()
+    }
+
+    #[test]
+    fn test_select() {
+        let chain = get_chain();
+        assert_eq!(select(&chain, BlockQuery::default()).len(), 5);
+
+        let query = BlockQuery {
+            from: Some(chain.blocks[3].hash.clone()),
+            ..Default::default()
+        };
+        assert_eq!(
+            select(&chain, query),
+            ["Saved the player at (3, 0)", "Saved the player at (4, 0)"]
+        );
+
+        let query = BlockQuery {
+            actions: vec!["CreatePlot".into()],
+            ..Default::default()
+        };
+        assert_eq!(select(&chain, query), ["Created the plot \"Plot\""]);
+
+        let query = BlockQuery {
+            actions: vec!["MovePlayer".into()],
+            since: Some(1),
+            until: Some(4),
+            last: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            select(&chain, query),
+            ["Saved the player at (2, 0)", "Saved the player at (3, 0)"],
+            "The last blocks are taken after the other filters."
+        );
+
+        let query = BlockQuery {
+            actions: vec!["Dance".into()],
+            ..Default::default()
+        };
+        assert_eq!(
+            query.select(&chain.blocks),
+            Err(QueryError::UnknownAction("Dance".into()))
+        );
+    }
+
+    #[test]
+    fn test_formats() {
+        let chain = get_chain();
+        let blocks: Vec<_> = chain.blocks.iter().skip(3).collect();
+        let write = |format: Format| {
+            let mut out = vec![];
+            format.write(&mut out, &blocks).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let json_lines = write(Format::JsonLines);
+        assert_eq!(json_lines.lines().count(), 2);
+        for line in json_lines.lines() {
+            serde_json::from_str::<Block<ChainAction>>(line)
+                .expect("Each line is a block.");
+        }
+
+        assert_eq!(
+            write(Format::Table)
+                .lines()
+                .map(|line| line.split_once(' ').unwrap().1)
+                .collect::<Vec<_>>(),
+            [
+                "1970-01-01 00:00:03 MovePlayer {\"MovePlayer\":[{\"x\":3,\"y\":0},{\"x\":1,\"y\":0}]}",
+                "1970-01-01 00:00:04 MovePlayer {\"MovePlayer\":[{\"x\":4,\"y\":0},{\"x\":1,\"y\":0}]}",
+            ]
+        );
+        assert_eq!(
+            write(Format::Summary),
+            "1970-01-01 00:00:03  Saved the player at (3, 0)\n\
+             1970-01-01 00:00:04  Saved the player at (4, 0)\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(parse_time("60"), Ok(60));
+        assert_eq!(parse_time("1970-01-02"), Ok(86400));
+        assert_eq!(parse_time("1970-01-01T00:01:00+00:00"), Ok(60));
+        assert!(parse_time("yesterday").is_err());
+    }
+}
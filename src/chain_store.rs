@@ -1,24 +1,143 @@
 use crate::{
-    block_chain::{Block, BlockChain, BlockData},
+    block_chain::{Block, BlockChain, BlockData, ReconcileError},
+    block_index::{BlockIndex, BlockLocation},
     hash::{Hash, StackStringHash},
+    ref_name::{self, RefNameError},
+};
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    fs,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
 };
-use anyhow::{bail, Context, Result};
-use std::{borrow::Cow, collections::HashSet, fs, io::BufReader, path::PathBuf};
 use thiserror::Error;
 
+/// Errors from setting up and writing to a ChainStore.
+#[derive(Error, Debug)]
+pub enum ChainStoreError {
+    #[error("the head ref {name:?} is not a valid name")]
+    InvalidHeadRef {
+        name: String,
+        #[source]
+        reason: RefNameError,
+    },
+    #[error("the head ref {name:?} conflicts with the existing head {existing:?}")]
+    ConflictingHeadRef { name: String, existing: String },
+    #[error("the root path is not valid: {}", .0.display())]
+    InvalidRootPath(PathBuf),
+    #[error("a file exists at the root path: {}", .0.display())]
+    RootPathIsFile(PathBuf),
+    #[error("{message} {}", .path.display())]
+    Io {
+        message: &'static str,
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+}
+
+impl ChainStoreError {
+    pub(crate) fn io(
+        message: &'static str,
+        path: &Path,
+    ) -> impl FnOnce(io::Error) -> Self {
+        let path = path.to_path_buf();
+        move |source| ChainStoreError::Io {
+            message,
+            path,
+            source,
+        }
+    }
+}
+
+/// Errors from loading a chain. Apart from Io, these mean the stored chain is
+/// corrupted or incomplete.
+#[derive(Error, Debug)]
+pub enum LoadError {
+    #[error("block {index} has the hash {actual}, but its contents hash to {expected}")]
+    HashMismatch {
+        index: usize,
+        expected: Hash,
+        actual: Hash,
+    },
+    #[error("block {index} should follow {expected}, but its parent is {actual}")]
+    BrokenLink {
+        index: usize,
+        expected: Hash,
+        actual: Hash,
+    },
+    #[error("the chain chunk {hash} is missing from {}", .path.display())]
+    MissingChunk { hash: Hash, path: PathBuf },
+    #[error("the chain chunk at {} could not be deserialized", .path.display())]
+    MalformedChunk {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+    #[error("the chain chunk at {} has no blocks", .path.display())]
+    EmptyChunk { path: PathBuf },
+    #[error("the block index at {} has a malformed line: {line:?}", .path.display())]
+    MalformedIndex { path: PathBuf, line: String },
+    #[error("the block index at {} is out of date for the block {hash}", .path.display())]
+    StaleIndex { path: PathBuf, hash: Hash },
+    #[error("the head ref at {} could not be resolved", .path.display())]
+    Ref {
+        path: PathBuf,
+        #[source]
+        source: ResolveRefError,
+    },
+    #[error("failed to read {}", .path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+}
+
+impl LoadError {
+    /// True if the stored chain can't be trusted, rather than just being unreadable.
+    pub fn is_corrupted(&self) -> bool {
+        !matches!(self, LoadError::Io { .. })
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct HeadRef(Cow<'static, str>);
 
 impl HeadRef {
-    /// For now only allow [a-zA-Z0-9-_]. This could be made more permissive, but
-    /// security concerns should be considered, as this is used to serialize to disk.
-    fn validate_name(name: &str) -> bool {
-        for ch in name.chars() {
-            if ch.is_ascii_alphanumeric() || ch == '_' || ch == '-' {
-                return true;
-            }
-        }
-        false
+    /// Check the name against the ref name grammar, see `ref_name`. Head refs are
+    /// serialized to disk as paths under the heads directory, so this must not let
+    /// a name escape it.
+    fn validate_name(name: &str) -> Result<(), ChainStoreError> {
+        ref_name::validate(name).map_err(|reason| ChainStoreError::InvalidHeadRef {
+            name: name.into(),
+            reason,
+        })
+    }
+
+    /// The ref that tracks what a peer last advertised for one of its heads, i.e.
+    /// remotes/<peer>/<head>.
+    pub fn remote(peer: &str, head_ref: &HeadRef) -> Result<Self, ChainStoreError> {
+        HeadRef::try_from(format!(
+            "{}/{}/{}",
+            ref_name::REMOTES_NAMESPACE,
+            peer,
+            head_ref.str()
+        ))
+    }
+
+    /// The peer and head of a remote-tracking ref, or None for a local head.
+    pub fn remote_parts(&self) -> Option<(&str, &str)> {
+        let rest = self
+            .str()
+            .strip_prefix(ref_name::REMOTES_NAMESPACE)?
+            .strip_prefix('/')?;
+        rest.split_once('/')
+    }
+
+    pub fn is_remote(&self) -> bool {
+        self.remote_parts().is_some()
     }
 
     pub fn str(&self) -> &str {
@@ -27,21 +146,17 @@ impl HeadRef {
 }
 
 impl TryFrom<String> for HeadRef {
-    type Error = anyhow::Error;
+    type Error = ChainStoreError;
     fn try_from(other: String) -> Result<Self, Self::Error> {
-        if !HeadRef::validate_name(&other) {
-            bail!("The head ref did not have a valid name: {:?}", other);
-        }
+        HeadRef::validate_name(&other)?;
         Ok(Self(Cow::Owned(other)))
     }
 }
 
 impl TryFrom<&'static str> for HeadRef {
-    type Error = anyhow::Error;
+    type Error = ChainStoreError;
     fn try_from(other: &'static str) -> Result<Self, Self::Error> {
-        if !HeadRef::validate_name(&other) {
-            bail!("The head ref did not have a valid name: {:?}", other);
-        }
+        HeadRef::validate_name(other)?;
         Ok(Self(Cow::Borrowed(other)))
     }
 }
@@ -49,10 +164,10 @@ impl TryFrom<&'static str> for HeadRef {
 pub trait ChainStore<T: BlockData> {
     /// The list of known hashes without doing additional loading. Blocks can be
     /// grouped together into chain chunks.
-    fn get_known_hashes(&mut self) -> Result<HashSet<Hash>>;
+    fn get_known_hashes(&mut self) -> Result<HashSet<Hash>, ChainStoreError>;
 
     /// Persist the blocks to the backing storage.
-    fn persist(&mut self) -> Result<()>;
+    fn persist(&mut self) -> Result<(), ChainStoreError>;
 
     /// Iterate from root to tip all of the loaded blocks. The first block is not
     /// guaranteed to be rooted.
@@ -60,11 +175,29 @@ pub trait ChainStore<T: BlockData> {
 
     /// Iterate through all the chains from the root. This is fallible since the chain
     /// store may need to deserialize blocks from storage.
-    fn iter_all(&mut self)
-        -> Result<Box<dyn DoubleEndedIterator<Item = &Block<T>> + '_>>;
+    fn iter_all(
+        &mut self,
+    ) -> Result<Box<dyn DoubleEndedIterator<Item = &Block<T>> + '_>, LoadError>;
 
     fn add(&mut self, data: T) -> &Block<T>;
     fn head_ref(&self) -> &HeadRef;
+
+    /// The number of blocks that have been added, but not yet persisted.
+    fn unpersisted_block_count(&self) -> usize;
+
+    /// The number of loaded blocks, without iterating over them.
+    fn loaded_block_count(&self) -> usize;
+
+    /// Reconcile foreign blocks against the loaded chain, see `BlockChain::reconcile`.
+    /// Returns the index of the first block that was replaced or added.
+    fn reconcile(&mut self, blocks: &[Block<T>]) -> Result<usize, ReconcileError>;
+
+    /// Look up a block by its hash. This finds any block in the store, not only the
+    /// ones in the chain of the head.
+    fn get_block(&mut self, hash: &Hash) -> Result<Option<Block<T>>, LoadError>;
+
+    /// Check if the store has a block, without reading it.
+    fn contains(&mut self, hash: &Hash) -> Result<bool, LoadError>;
 }
 
 impl<T: BlockData> std::fmt::Debug for dyn ChainStore<T> {
@@ -88,6 +221,7 @@ impl<T: BlockData> std::fmt::Debug for dyn ChainStore<T> {
 ///     └── heads
 ///     │   ├── garden-1
 ///     │   └── garden-2
+///     ├── index
 ///     └── HEAD
 #[derive(Debug, PartialEq)]
 pub struct FsChainStore<T: BlockData> {
@@ -110,29 +244,77 @@ pub struct FsChainStore<T: BlockData> {
 
     pub chain: BlockChain<T>,
 
+    /// Where every persisted block is, by its hash. See `block_index`.
+    ///   Example path: .garden/index
+    pub index_path: PathBuf,
+
+    /// The block index is read on the first lookup.
+    index: Option<BlockIndex>,
+
+    /// Why a rebuilt index couldn't be saved, see `take_index_error`.
+    index_error: Option<String>,
+
     /// The number of blocks that need to be persisted.
     unpersisted_block_count: usize,
 }
 
+/// Heads are files, so a ref can't be both a head and a namespace of other heads, e.g.
+/// "feature" and "feature/plots". Check that creating the head wouldn't need either.
+fn check_head_conflict(
+    heads_path: &Path,
+    head_ref: &HeadRef,
+) -> Result<(), ChainStoreError> {
+    let conflict = |existing: String| ChainStoreError::ConflictingHeadRef {
+        name: head_ref.str().into(),
+        existing,
+    };
+    let mut prefix = String::new();
+    for component in head_ref.str().split('/') {
+        if !prefix.is_empty() {
+            if heads_path.join(&prefix).is_file() {
+                return Err(conflict(prefix));
+            }
+            prefix.push('/');
+        }
+        prefix.push_str(component);
+    }
+    let head_path = heads_path.join(head_ref.str());
+    if head_path.is_dir() {
+        let nested = fs::read_dir(&head_path)
+            .ok()
+            .and_then(|mut entries| entries.next())
+            .and_then(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned());
+        if let Some(nested) = nested {
+            return Err(conflict(format!("{}/{}", head_ref.str(), nested)));
+        }
+    }
+    Ok(())
+}
+
 impl<T: BlockData> FsChainStore<T> {
-    pub fn try_new(root_path: PathBuf, head_ref: HeadRef) -> Result<Self> {
+    pub fn try_new(
+        root_path: PathBuf,
+        head_ref: HeadRef,
+    ) -> Result<Self, ChainStoreError> {
         if !root_path.as_path().exists() {
             let parent = root_path.as_path().parent();
             if parent.is_none() {
-                bail!("the root path is not valid: {}", root_path.display())
+                return Err(ChainStoreError::InvalidRootPath(root_path));
             }
 
             let parent = parent.unwrap();
             if !parent.is_dir() {
-                bail!("the parent path is a file: {}", parent.display());
+                return Err(ChainStoreError::InvalidRootPath(root_path));
             }
 
             // Make the directory.
-            if fs::create_dir(root_path.clone()).is_err() {
-                bail!("failed to make the root path for {}", root_path.display());
-            }
+            fs::create_dir(root_path.clone()).map_err(ChainStoreError::io(
+                "failed to make the root path",
+                &root_path,
+            ))?;
         } else if !root_path.as_path().is_dir() {
-            bail!("A file exists at that root path {}", root_path.display());
+            return Err(ChainStoreError::RootPathIsFile(root_path));
         }
 
         // Double check the logic above was correct.
@@ -140,76 +322,387 @@ impl<T: BlockData> FsChainStore<T> {
 
         let mut chains_path = root_path.clone();
         chains_path.push("chains");
+        let index_path = root_path.join("index");
         if !chains_path.exists() {
-            if fs::create_dir(chains_path.clone()).is_err() {
-                bail!(
-                    "failed to create chains directory {}",
-                    chains_path.display()
-                )
-            }
+            fs::create_dir(chains_path.clone()).map_err(ChainStoreError::io(
+                "failed to create chains directory",
+                &chains_path,
+            ))?;
+            // There are no blocks yet, so the empty index is complete. Gardens from
+            // before the index existed don't have one, see `rebuild_index`.
+            fs::write(&index_path, "").map_err(ChainStoreError::io(
+                "failed to create the block index",
+                &index_path,
+            ))?;
         }
 
         let mut heads_path = root_path.clone();
         heads_path.push("heads");
         if !heads_path.exists() {
-            if fs::create_dir(heads_path.clone()).is_err() {
-                bail!("failed to create heads directory {}", heads_path.display())
-            }
+            fs::create_dir(heads_path.clone()).map_err(ChainStoreError::io(
+                "failed to create heads directory",
+                &heads_path,
+            ))?;
         }
 
+        check_head_conflict(&heads_path, &head_ref)?;
+
         Ok(Self {
             root_path,
             chains_path,
             heads_path,
             head_ref,
             chain: BlockChain::new(),
+            index_path,
+            index: None,
+            index_error: None,
             unpersisted_block_count: 0,
         })
     }
 
+    /// Replace the loaded chain with blocks that are already persisted, e.g. to start
+    /// a new head from the genesis block of the garden, or with no blocks to forget
+    /// the chain. The head is written on the next persist.
+    pub fn start_from(&mut self, blocks: Vec<Block<T>>) {
+        self.chain = BlockChain::from(blocks);
+        self.unpersisted_block_count = 0;
+    }
+
     pub fn head_path(&self, head_ref: &HeadRef) -> PathBuf {
         let mut head_path = self.heads_path.clone();
         head_path.push(head_ref.str());
         head_path
     }
 
-    pub fn load_next_parent_chain<'a>(&'a mut self) -> Result<Option<&'a Block<T>>> {
-        let hash = {
-            if let Some(root_most_block) = self.chain.blocks.front() {
-                root_most_block.payload.parent.clone()
+    fn write_head(&self, hash: &Hash) -> Result<(), ChainStoreError> {
+        // Another store could have made a conflicting head since this one was created.
+        check_head_conflict(&self.heads_path, &self.head_ref)?;
+        let head_path = self.head_path(&self.head_ref);
+        if let Some(parent) = head_path.parent() {
+            // Namespaced refs like remotes/<peer>/<head> are nested directories.
+            fs::create_dir_all(parent).map_err(ChainStoreError::io(
+                "failed to create the head reference directory",
+                parent,
+            ))?;
+        }
+        // Write a temporary file and rename it over the head, so that the head is
+        // never partly written. The leading '.' keeps it from being a valid ref.
+        let file_name = head_path
+            .file_name()
+            .expect("A head ref has a file name.")
+            .to_string_lossy();
+        let tmp_path = head_path.with_file_name(format!(".{}.tmp", file_name));
+        fs::write(&tmp_path, String::from(hash)).map_err(ChainStoreError::io(
+            "failed to write head reference",
+            &tmp_path,
+        ))?;
+        fs::rename(&tmp_path, &head_path).map_err(ChainStoreError::io(
+            "failed to write head reference",
+            &head_path,
+        ))
+    }
+
+    /// The refs of every head that has been persisted, sorted by name. This includes
+    /// the namespaced ones, e.g. remotes/<peer>/<head>. Files that aren't valid ref
+    /// names are skipped, see `head_refs_and_strays`.
+    pub fn head_refs(&self) -> Result<Vec<HeadRef>, ChainStoreError> {
+        Ok(self.head_refs_and_strays()?.0)
+    }
+
+    /// The refs of every head, as with `head_refs`, and why each of the stray files in
+    /// the heads directory was skipped, so that the caller can warn about them. A stray
+    /// file shouldn't hide every other head.
+    pub fn head_refs_and_strays(
+        &self,
+    ) -> Result<(Vec<HeadRef>, Vec<ChainStoreError>), ChainStoreError> {
+        let mut head_refs = vec![];
+        let mut strays = vec![];
+        self.collect_head_refs(&self.heads_path, "", &mut head_refs, &mut strays)?;
+        head_refs.sort_by(|a, b| a.str().cmp(b.str()));
+        Ok((head_refs, strays))
+    }
+
+    fn collect_head_refs(
+        &self,
+        dir: &Path,
+        prefix: &str,
+        head_refs: &mut Vec<HeadRef>,
+        strays: &mut Vec<ChainStoreError>,
+    ) -> Result<(), ChainStoreError> {
+        let read_error = ChainStoreError::io("failed to read the heads directory", dir);
+        let entries = fs::read_dir(dir).map_err(read_error)?;
+        for entry in entries {
+            let entry = entry.map_err(ChainStoreError::io(
+                "failed to read the heads directory",
+                dir,
+            ))?;
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            if file_name.starts_with('.') {
+                // A head that is being written, see `write_head`.
+                continue;
+            }
+            let name = format!("{}{}", prefix, file_name);
+            let is_dir = entry
+                .file_type()
+                .map_err(ChainStoreError::io(
+                    "failed to read the heads directory",
+                    dir,
+                ))?
+                .is_dir();
+            if is_dir {
+                self.collect_head_refs(
+                    &entry.path(),
+                    &format!("{}/", name),
+                    head_refs,
+                    strays,
+                )?;
             } else {
-                let mut head_path = self.heads_path.clone();
-                head_path.push(self.head_ref.str());
-                if !head_path.exists() {
-                    return Ok(None);
+                match HeadRef::try_from(name) {
+                    Ok(head_ref) => head_refs.push(head_ref),
+                    Err(err) => strays.push(err),
                 }
-                resolve_fs_ref(&head_path)?
             }
-        };
+        }
+        Ok(())
+    }
 
-        if hash.is_root() {
+    /// Read the hash the head ref points to, without loading any blocks. This is None
+    /// when nothing has been persisted for the head yet.
+    pub fn head_hash(&self) -> Result<Option<Hash>, LoadError> {
+        self.resolve_head(&self.head_ref)
+    }
+
+    /// Read the hash any head ref points to, see `head_hash`.
+    pub fn resolve_head(&self, head_ref: &HeadRef) -> Result<Option<Hash>, LoadError> {
+        let head_path = self.head_path(head_ref);
+        if !head_path.exists() {
             return Ok(None);
         }
+        resolve_fs_ref(&head_path)
+            .map(Some)
+            .map_err(|source| LoadError::Ref {
+                path: head_path,
+                source,
+            })
+    }
 
-        let hash_str = StackStringHash::from(&hash);
+    /// The path of the chunk file that ends with the block with this hash, e.g.
+    /// .garden/chains/01/23456789abcdef0123456789abcdef0123456789abcdef0123456789000001
+    pub fn chunk_path(&self, hash: &Hash) -> PathBuf {
+        let hash_str = StackStringHash::from(hash);
         let mut path = self.chains_path.clone();
-
         path.push(&hash_str.str()[0..2]);
         path.push(&hash_str.str()[2..64]);
+        path
+    }
 
-        let file = fs::File::open(path.clone()).with_context(|| {
-            format!("attempted to load the next chain {}", path.display())
+    fn read_chunk(&self, hash: &Hash) -> Result<Vec<Block<T>>, LoadError> {
+        let path = self.chunk_path(hash);
+        let file = fs::File::open(&path).map_err(|source| {
+            if source.kind() == io::ErrorKind::NotFound {
+                LoadError::MissingChunk {
+                    hash: hash.clone(),
+                    path: path.clone(),
+                }
+            } else {
+                LoadError::Io {
+                    path: path.clone(),
+                    source,
+                }
+            }
         })?;
 
-        let reader = BufReader::new(file);
+        let blocks = serde_json::from_reader::<BufReader<fs::File>, Vec<Block<T>>>(
+            BufReader::new(file),
+        )
+        .map_err(|source| LoadError::MalformedChunk {
+            path: path.clone(),
+            source,
+        })?;
+        if blocks.is_empty() {
+            return Err(LoadError::EmptyChunk { path });
+        }
+        Ok(blocks)
+    }
+
+    /// The tip hashes of the chunks that the head's chain is stored in, from the tip
+    /// to the root. This reads the chunks, but doesn't load them into the chain.
+    pub fn reachable_chunks(&self) -> Result<Vec<Hash>, LoadError> {
+        let mut chunks = vec![];
+        let mut hash = match self.head_hash()? {
+            Some(hash) => hash,
+            None => return Ok(chunks),
+        };
+        while !hash.is_root() {
+            let blocks = self.read_chunk(&hash)?;
+            chunks.push(hash);
+            hash = blocks[0].payload.parent.clone();
+        }
+        Ok(chunks)
+    }
+
+    /// Index every chunk, for gardens that don't have an index file yet, or have
+    /// one that is out of date. Use `write_index` to save it.
+    pub fn rebuild_index(&mut self) -> Result<(), LoadError> {
+        let mut index = BlockIndex::default();
+        let chunks = self.get_known_hashes().map_err(|err| LoadError::Io {
+            path: self.chains_path.clone(),
+            source: io::Error::other(err),
+        })?;
+        for chunk in chunks {
+            for (offset, block) in self.read_chunk(&chunk)?.into_iter().enumerate() {
+                let location = BlockLocation {
+                    chunk: chunk.clone(),
+                    offset,
+                };
+                index.insert(block.hash, location);
+            }
+        }
+        self.index = Some(index);
+        Ok(())
+    }
+
+    /// Replace the index file with the loaded index.
+    pub fn write_index(&mut self) -> Result<(), ChainStoreError> {
+        match &mut self.index {
+            Some(index) => index.write(&self.index_path),
+            None => Ok(()),
+        }
+    }
+
+    /// When the index had to be rebuilt but couldn't be saved, this is why. Lookups
+    /// still work, but the next chain store rebuilds it again.
+    pub fn take_index_error(&mut self) -> Option<String> {
+        self.index_error.take()
+    }
+
+    fn load_index(&mut self) -> Result<&mut BlockIndex, LoadError> {
+        if self.index.is_none() {
+            if self.index_path.exists() {
+                self.index = Some(BlockIndex::read(&self.index_path)?);
+            } else {
+                self.rebuild_index()?;
+                // Save it so the chunks aren't all read again on the next run. The
+                // index in memory is still complete if this fails.
+                self.index_error = self.write_index().err().map(|err| err.to_string());
+            }
+        }
+        Ok(self.index.as_mut().expect("The index was loaded."))
+    }
+
+    fn locate_block(&mut self, hash: &Hash) -> Result<Option<BlockLocation>, LoadError> {
+        let index_path = self.index_path.clone();
+        let index = self.load_index()?;
+        if let Some(location) = index.get(hash) {
+            return Ok(Some(location.clone()));
+        }
+        // Another chain store could have persisted the block since the index was read.
+        index.refresh(&index_path)?;
+        Ok(index.get(hash).cloned())
+    }
+
+    /// Blocks that haven't been persisted aren't in the index yet.
+    fn find_unpersisted(&self, hash: &Hash) -> Option<&Block<T>> {
+        self.chain
+            .blocks
+            .iter()
+            .rev()
+            .take(self.unpersisted_block_count)
+            .find(|block| block.hash == *hash)
+    }
+
+    /// The hashes of every block that starts with the hex prefix, sorted.
+    pub fn find_by_prefix(&mut self, prefix: &str) -> Result<Vec<Hash>, LoadError> {
+        let prefix = prefix.to_ascii_lowercase();
+        let unpersisted: Vec<Hash> = self
+            .chain
+            .blocks
+            .iter()
+            .rev()
+            .take(self.unpersisted_block_count)
+            .map(|block| block.hash.clone())
+            .collect();
+        let index_path = self.index_path.clone();
+        let index = self.load_index()?;
+        index.refresh(&index_path)?;
+        let mut hashes: Vec<String> = index
+            .hashes()
+            .chain(unpersisted.iter())
+            .map(String::from)
+            .filter(|hash| hash.starts_with(&prefix))
+            .collect();
+        hashes.sort();
+        hashes.dedup();
+        Ok(hashes
+            .iter()
+            .map(|hash| Hash::try_from(hash.as_str()).expect("The hash was formatted."))
+            .collect())
+    }
+
+    /// The chain that ends with a block, from the root, whichever head it is in. This
+    /// reads whole chunks rather than looking up each block. It is None when there is
+    /// no block with the hash.
+    pub fn blocks_to(&mut self, hash: &Hash) -> Result<Option<Vec<Block<T>>>, LoadError> {
+        // The blocks are collected from the tip back to the root.
+        let mut blocks: Vec<Block<T>> = vec![];
+        let mut hash = hash.clone();
+
+        let unpersisted_start = self.chain.blocks.len() - self.unpersisted_block_count;
+        let unpersisted = self.chain.blocks.range(unpersisted_start..);
+        if let Some(end) = unpersisted.clone().position(|block| block.hash == hash) {
+            blocks.extend(unpersisted.take(end + 1).rev().cloned());
+            hash = blocks.last().unwrap().payload.parent.clone();
+        }
+
+        while !hash.is_root() {
+            let (mut chunk, offset) = match self.locate_block(&hash)? {
+                Some(location) => (self.read_chunk(&location.chunk)?, location.offset),
+                None if blocks.is_empty() => return Ok(None),
+                None => {
+                    // Parents are normally chunk tips, so try reading it directly.
+                    let chunk = self.read_chunk(&hash)?;
+                    let offset = chunk.len() - 1;
+                    (chunk, offset)
+                }
+            };
+            if chunk.get(offset).map(|block| &block.hash) != Some(&hash) {
+                return Err(LoadError::StaleIndex {
+                    path: self.index_path.clone(),
+                    hash,
+                });
+            }
+            chunk.truncate(offset + 1);
+            hash = chunk[0].payload.parent.clone();
+            blocks.extend(chunk.into_iter().rev());
+        }
+
+        blocks.reverse();
+        Ok(Some(blocks))
+    }
+
+    pub fn load_next_parent_chain<'a>(
+        &'a mut self,
+    ) -> Result<Option<&'a Block<T>>, LoadError> {
+        let hash = {
+            if let Some(root_most_block) = self.chain.blocks.front() {
+                root_most_block.payload.parent.clone()
+            } else {
+                match self.head_hash()? {
+                    Some(hash) => hash,
+                    None => return Ok(None),
+                }
+            }
+        };
+
+        if hash.is_root() {
+            return Ok(None);
+        }
 
         //                     [block(5), block(6), block(7)]
         // [block(3), block(4)]                             └── Existing block chain
         //                    └── .json file to load
 
-        let mut blocks =
-            serde_json::from_reader::<BufReader<fs::File>, Vec<Block<T>>>(reader)
-                .context("failed to deserialize block")?;
+        let mut blocks = self.read_chunk(&hash)?;
 
         for block in blocks.drain(..).rev() {
             self.chain.blocks.push_front(block);
@@ -218,7 +711,7 @@ impl<T: BlockData> FsChainStore<T> {
         Ok(self.chain.blocks.front())
     }
 
-    pub fn load_all_chains(&mut self) -> Result<()> {
+    pub fn load_all_chains(&mut self) -> Result<(), LoadError> {
         loop {
             let chain = self.load_next_parent_chain()?;
             if chain.is_none() {
@@ -230,27 +723,25 @@ impl<T: BlockData> FsChainStore<T> {
 }
 
 impl<T: BlockData> ChainStore<T> for FsChainStore<T> {
-    fn get_known_hashes(&mut self) -> Result<HashSet<Hash>> {
+    fn get_known_hashes(&mut self) -> Result<HashSet<Hash>, ChainStoreError> {
         let mut chain_hashes = HashSet::<Hash>::new();
-        let dir_entries = fs::read_dir(self.chains_path.clone());
-        if dir_entries.is_err() {
-            bail!("could not read directory {}", self.chains_path.display());
-        }
+        let dir_entries = fs::read_dir(self.chains_path.clone()).map_err(
+            ChainStoreError::io("could not read directory", &self.chains_path),
+        )?;
 
         let mut path_str = String::new();
-        for dir_entry in dir_entries.unwrap() {
+        for dir_entry in dir_entries {
             if dir_entry.is_err() {
                 continue;
             }
             let dir_entry = dir_entry.unwrap();
 
-            let postfix_dir_entries = fs::read_dir(dir_entry.path());
-            if postfix_dir_entries.is_err() {
-                bail!("could not read directory {}", dir_entry.path().display());
-            }
+            let postfix_dir_entries = fs::read_dir(dir_entry.path()).map_err(
+                ChainStoreError::io("could not read directory", &dir_entry.path()),
+            )?;
             let prefix_file_name = dir_entry.file_name();
             let prefix_path_str: &str = &prefix_file_name.to_string_lossy();
-            for postfix_dir_entry in postfix_dir_entries.unwrap() {
+            for postfix_dir_entry in postfix_dir_entries {
                 if postfix_dir_entry.is_err() {
                     continue;
                 }
@@ -270,16 +761,13 @@ impl<T: BlockData> ChainStore<T> for FsChainStore<T> {
         Ok(chain_hashes)
     }
 
-    fn persist(&mut self) -> Result<()> {
+    fn persist(&mut self) -> Result<(), ChainStoreError> {
         let tip = self.chain.tip();
         if tip.is_none() {
             // There is nothing to persist.
             return Ok(());
         }
         let tip = tip.unwrap();
-        let tip_string = StackStringHash::from(&tip.hash);
-
-        let mut target_path = self.chains_path.clone();
 
         // Use the same optimization as git and store the chains in multiple
         // sub-folders.
@@ -289,26 +777,22 @@ impl<T: BlockData> ChainStore<T> for FsChainStore<T> {
         //   └── chain file
         // ^^
         // └── prefix
+        let target_path = self.chunk_path(&tip.hash);
 
-        // Add the prefix, e.g "01" in th example above.
-        target_path.push(&tip_string.str()[0..2]);
-
-        // Ensure the prefix folder exists.
-        if !target_path.as_path().is_dir() {
+        // Ensure the prefix folder exists, e.g "01" in th example above.
+        let prefix_path = target_path.parent().expect("The chunk path has a prefix.");
+        if !prefix_path.is_dir() {
             // Make the directory.
-            fs::create_dir(target_path.clone()).with_context(|| {
-                format!(
-                    "failed to create the hash prefix directory {}",
-                    target_path.display()
-                )
-            })?;
+            fs::create_dir(prefix_path).map_err(ChainStoreError::io(
+                "failed to create the hash prefix directory",
+                prefix_path,
+            ))?;
         }
 
-        // Add the chain file name, e.g "23456789abcdefff...f" in th example above.
-        target_path.push(&tip_string.str()[2..64]);
-
         if target_path.as_path().exists() {
-            // This block has already been serialized, we are done.
+            // This block has already been serialized, e.g. by another head. Only the
+            // head reference needs updating.
+            self.write_head(&tip.hash)?;
             self.unpersisted_block_count = 0;
             return Ok(());
         }
@@ -338,9 +822,9 @@ impl<T: BlockData> ChainStore<T> for FsChainStore<T> {
         }
 
         // Create the target file.
-        let target_file = fs::File::create(target_path.clone()).with_context(|| {
-            format!("attempted to load the next chain {}", target_path.display())
-        })?;
+        let target_file = fs::File::create(target_path.clone()).map_err(
+            ChainStoreError::io("failed to create the chain file", &target_path),
+        )?;
 
         // Build a list of the blocks from the VecDeque.
         let mut blocks: Vec<&Block<T>> = Vec::new();
@@ -354,17 +838,35 @@ impl<T: BlockData> ChainStore<T> for FsChainStore<T> {
         }
 
         // Write out the chain as JSON.
-        serde_json::to_writer_pretty(target_file, &blocks).with_context(|| {
-            format!("failed to write blocks to file {}", target_path.display())
+        serde_json::to_writer_pretty(target_file, &blocks).map_err(|err| {
+            ChainStoreError::io("failed to write blocks to file", &target_path)(
+                err.into(),
+            )
         })?;
 
-        fs::write(self.head_path(&self.head_ref), String::from(&(tip.hash)))
-            .with_context(|| {
-                format!(
-                    "failed to write head reference {}",
-                    self.head_path(&self.head_ref).display()
-                )
-            })?;
+        let entries: Vec<(Hash, BlockLocation)> = blocks
+            .iter()
+            .enumerate()
+            .map(|(offset, block)| {
+                let location = BlockLocation {
+                    chunk: tip.hash.clone(),
+                    offset,
+                };
+                (block.hash.clone(), location)
+            })
+            .collect();
+        // The blocks are indexed before the head points to them, so that a failure
+        // in between can't leave a head with blocks that the index is missing.
+        // An older garden has no index file, which is built on the next lookup.
+        if self.index_path.exists() {
+            BlockIndex::append(&self.index_path, &entries)?;
+        }
+        if let Some(index) = &mut self.index {
+            for (hash, location) in entries {
+                index.insert(hash, location);
+            }
+        }
+        self.write_head(&tip.hash)?;
 
         self.unpersisted_block_count = 0;
         Ok(())
@@ -376,18 +878,18 @@ impl<T: BlockData> ChainStore<T> for FsChainStore<T> {
 
     fn iter_all(
         &mut self,
-    ) -> Result<Box<dyn DoubleEndedIterator<Item = &Block<T>> + '_>> {
+    ) -> Result<Box<dyn DoubleEndedIterator<Item = &Block<T>> + '_>, LoadError> {
         if self.chain.is_partial() {
             self.load_all_chains()?;
         }
-        if self.chain.is_partial() && !self.chain.blocks.is_empty() {
-            bail!(
-                "The chain is incomplete, the first block is {:?}",
-                self.chain
-                    .blocks
-                    .front()
-                    .expect("Logic error, unable to get front block.")
-            );
+        if self.chain.is_partial() {
+            if let Some(block) = self.chain.blocks.front() {
+                return Err(LoadError::BrokenLink {
+                    index: 0,
+                    expected: Hash::empty(),
+                    actual: block.payload.parent.clone(),
+                });
+            }
         }
         Ok(Box::new(self.chain.blocks.iter()))
     }
@@ -404,6 +906,52 @@ impl<T: BlockData> ChainStore<T> for FsChainStore<T> {
     fn head_ref(&self) -> &HeadRef {
         &self.head_ref
     }
+
+    fn unpersisted_block_count(&self) -> usize {
+        self.unpersisted_block_count
+    }
+
+    fn loaded_block_count(&self) -> usize {
+        self.chain.blocks.len()
+    }
+
+    fn reconcile(&mut self, blocks: &[Block<T>]) -> Result<usize, ReconcileError> {
+        let persisted_len = self.chain.blocks.len() - self.unpersisted_block_count;
+        let fork_index = self.chain.reconcile(blocks)?;
+        // Blocks from the fork onward need persisting, even if the blocks they
+        // replaced had already been persisted.
+        self.unpersisted_block_count =
+            self.chain.blocks.len() - fork_index.min(persisted_len);
+        Ok(fork_index)
+    }
+
+    fn get_block(&mut self, hash: &Hash) -> Result<Option<Block<T>>, LoadError> {
+        if let Some(block) = self.find_unpersisted(hash) {
+            return Ok(Some(block.clone()));
+        }
+        let location = match self.locate_block(hash)? {
+            Some(location) => location,
+            None => return Ok(None),
+        };
+        match self
+            .read_chunk(&location.chunk)?
+            .into_iter()
+            .nth(location.offset)
+        {
+            Some(block) if block.hash == *hash => Ok(Some(block)),
+            _ => Err(LoadError::StaleIndex {
+                path: self.index_path.clone(),
+                hash: hash.clone(),
+            }),
+        }
+    }
+
+    fn contains(&mut self, hash: &Hash) -> Result<bool, LoadError> {
+        if self.find_unpersisted(hash).is_some() {
+            return Ok(true);
+        }
+        Ok(self.locate_block(hash)?.is_some())
+    }
 }
 
 // #[derive(Debug)]
@@ -532,6 +1080,31 @@ pub enum ResolveRefError {
     FailedToReadRef,
 }
 
+/// Check that a loaded block follows its parent, and that its hash matches its
+/// contents. The index is the position of the block in the chain, for the error.
+pub fn check_block<T: BlockData>(
+    index: usize,
+    parent: &Hash,
+    block: &Block<T>,
+) -> Result<(), LoadError> {
+    if *parent != block.payload.parent {
+        return Err(LoadError::BrokenLink {
+            index,
+            expected: parent.clone(),
+            actual: block.payload.parent.clone(),
+        });
+    }
+    let hash = block.payload.hash();
+    if hash != block.hash {
+        return Err(LoadError::HashMismatch {
+            index,
+            expected: hash,
+            actual: block.hash.clone(),
+        });
+    }
+    Ok(())
+}
+
 fn resolve_fs_ref(path: &PathBuf) -> Result<Hash, ResolveRefError> {
     match fs::read_to_string(path) {
         Ok(contents) => match Hash::try_from(contents.as_str()) {
@@ -709,8 +1282,9 @@ mod test {
                 "├── chains",
                 "│   └── d7",
                 "│       └── 22da39a7e34043683136eb3048b7ac1f3c68778875b17ffc01d8809632bb9c",
-                "└── heads",
-                "    └── my-garden",
+                "├── heads",
+                "│   └── my-garden",
+                "└── index",
             ]
         );
     }
@@ -740,8 +1314,9 @@ mod test {
                 "│   │   └── 22da39a7e34043683136eb3048b7ac1f3c68778875b17ffc01d8809632bb9c",
                 "│   └── dc",
                 "│       └── 8243497f48f2fbb2677646456d4d3f123250a95c838082bfc97716b775b5ff",
-                "└── heads",
-                "    └── my-garden",
+                "├── heads",
+                "│   └── my-garden",
+                "└── index",
             ]
         );
     }
@@ -777,8 +1352,9 @@ mod test {
                 "│   │   └── 8243497f48f2fbb2677646456d4d3f123250a95c838082bfc97716b775b5ff",
                 "│   └── fb",
                 "│       └── a2f217aa0411b48bc370769b9018dbbd1996f7d6ef0221e9db829975931330",
-                "└── heads",
-                "    └── my-garden",
+                "├── heads",
+                "│   └── my-garden",
+                "└── index",
             ]
         );
     }
@@ -947,4 +1523,215 @@ mod test {
 
         assert_eq!(data, vec!["data 1", "data 2", "data 3", "data 4"]);
     }
+
+    #[test]
+    fn test_get_block() {
+        let mut test = ChainStoreTest::new();
+        let ChainStoreTest {
+            ref mut chain_store,
+            ref path,
+            ..
+        } = test;
+        let get_data = |chain_store: &mut FsChainStore<String>, hash: &Hash| {
+            chain_store
+                .get_block(hash)
+                .unwrap()
+                .map(|block| block.payload.data)
+        };
+
+        let hash_1 = chain_store.add("data 1".into()).hash.clone();
+        chain_store.persist().unwrap();
+        let hash_2 = chain_store.add("data 2".into()).hash.clone();
+        assert_eq!(get_data(chain_store, &hash_1).as_deref(), Some("data 1"));
+        assert_eq!(
+            get_data(chain_store, &hash_2).as_deref(),
+            Some("data 2"),
+            "Unpersisted blocks are found."
+        );
+        assert!(!chain_store.contains(&Hash::empty()).unwrap());
+        assert_eq!(get_data(chain_store, &Hash::empty()), None);
+        chain_store.persist().unwrap();
+
+        // Blocks from other heads are found, even ones persisted after the index was
+        // read.
+        let mut other = FsChainStore::<String>::try_new(
+            path.clone(),
+            HeadRef::try_from("other").unwrap(),
+        )
+        .unwrap();
+        assert!(other.contains(&hash_2).unwrap());
+        assert_eq!(other.iter_loaded().count(), 0, "The chain isn't loaded.");
+        let hash_3 = chain_store.add("data 3".into()).hash.clone();
+        chain_store.persist().unwrap();
+        assert_eq!(get_data(&mut other, &hash_3).as_deref(), Some("data 3"));
+
+        // A garden from before the index existed is indexed on the first lookup, and
+        // the index is saved for the next run.
+        fs::remove_file(&chain_store.index_path).unwrap();
+        let mut older = FsChainStore::<String>::try_new(
+            path.clone(),
+            HeadRef::try_from("older").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(get_data(&mut older, &hash_2).as_deref(), Some("data 2"));
+        assert!(older.index_path.exists());
+        assert!(older.take_index_error().is_none());
+        let mut reloaded = FsChainStore::<String>::try_new(
+            path.clone(),
+            HeadRef::try_from("older").unwrap(),
+        )
+        .unwrap();
+        for hash in [&hash_1, &hash_2, &hash_3] {
+            assert!(reloaded.contains(hash).unwrap());
+        }
+
+        // An index that points at the wrong block is an error, rather than a wrong
+        // answer.
+        fs::write(&reloaded.index_path, format!("{} {} 0\n", hash_1, hash_2)).unwrap();
+        let mut swapped = FsChainStore::<String>::try_new(
+            path.clone(),
+            HeadRef::try_from("older").unwrap(),
+        )
+        .unwrap();
+        assert!(matches!(
+            swapped.get_block(&hash_1),
+            Err(LoadError::StaleIndex { .. })
+        ));
+
+        // An index that can't be saved still answers lookups, and the caller is told.
+        let mut unsaved = FsChainStore::<String>::try_new(
+            path.clone(),
+            HeadRef::try_from("older").unwrap(),
+        )
+        .unwrap();
+        unsaved.index_path = path.join("missing").join("index");
+        assert_eq!(get_data(&mut unsaved, &hash_2).as_deref(), Some("data 2"));
+        assert!(unsaved
+            .take_index_error()
+            .unwrap()
+            .starts_with("failed to write the block index"));
+        assert!(unsaved.take_index_error().is_none());
+    }
+
+    #[test]
+    fn test_namespaced_head_refs() {
+        let mut test = ChainStoreTest::new();
+        let ChainStoreTest {
+            ref mut chain_store,
+            ref path,
+            ..
+        } = test;
+        chain_store.add("data 1".into());
+        chain_store.persist().unwrap();
+
+        let mut remote = FsChainStore::<String>::try_new(
+            path.clone(),
+            HeadRef::try_from("remotes/peer-1/my-garden").unwrap(),
+        )
+        .unwrap();
+        remote.add("data 2".into());
+        remote.persist().unwrap();
+        assert!(join_path(path, &["heads", "remotes", "peer-1", "my-garden"]).exists());
+
+        let names: Vec<String> = chain_store
+            .head_refs()
+            .unwrap()
+            .iter()
+            .map(|head_ref| head_ref.str().into())
+            .collect();
+        assert_eq!(names, ["my-garden", "remotes/peer-1/my-garden"]);
+        fs::write(join_path(path, &["heads", "not a ref"]), "").unwrap();
+        fs::write(join_path(path, &["heads", ".my-garden.tmp"]), "").unwrap();
+        assert_eq!(
+            chain_store.head_refs().unwrap().len(),
+            2,
+            "Stray files are skipped."
+        );
+        let (_, strays) = chain_store.head_refs_and_strays().unwrap();
+        assert!(matches!(
+            strays.as_slice(),
+            [ChainStoreError::InvalidHeadRef { name, .. }] if name == "not a ref"
+        ));
+        assert_eq!(
+            HeadRef::remote("peer-1", &chain_store.head_ref).unwrap(),
+            remote.head_ref
+        );
+        assert_eq!(
+            remote.head_ref.remote_parts(),
+            Some(("peer-1", "my-garden"))
+        );
+        assert_eq!(chain_store.head_ref.remote_parts(), None);
+
+        for invalid in ["", "..", "../heads", "remotes/peer-1", "HEAD", "main~1"] {
+            assert!(
+                matches!(
+                    HeadRef::try_from(invalid),
+                    Err(ChainStoreError::InvalidHeadRef { .. })
+                ),
+                "{:?} is invalid",
+                invalid
+            );
+        }
+
+        let nested = HeadRef::try_from("feature/plots").unwrap();
+        assert_eq!(
+            HeadRef::remote("peer-1", &nested).unwrap().str(),
+            "remotes/peer-1/feature/plots"
+        );
+        let mut remote = FsChainStore::<String>::try_new(
+            path.clone(),
+            HeadRef::remote("peer-1", &nested).unwrap(),
+        )
+        .unwrap();
+        remote.add("data 3".into());
+        remote.persist().unwrap();
+        assert_eq!(
+            remote.head_ref.remote_parts(),
+            Some(("peer-1", "feature/plots"))
+        );
+    }
+
+    #[test]
+    fn test_conflicting_head_refs() {
+        let mut test = ChainStoreTest::new();
+        let ChainStoreTest { ref path, .. } = test;
+        let path = path.clone();
+        let store = |name: &str| {
+            FsChainStore::<String>::try_new(
+                path.clone(),
+                HeadRef::try_from(name.to_string()).unwrap(),
+            )
+        };
+
+        let mut plots = store("feature/plots").unwrap();
+        plots.add("data 1".into());
+        plots.persist().unwrap();
+        match store("feature") {
+            Err(ChainStoreError::ConflictingHeadRef { name, existing }) => {
+                assert_eq!(name, "feature");
+                assert_eq!(existing, "feature/plots");
+            }
+            other => panic!("Expected a conflict, got {:?}", other.map(|_| ())),
+        }
+
+        test.chain_store.add("data 0".into());
+        test.chain_store.persist().unwrap();
+        match store("my-garden/nested") {
+            Err(ChainStoreError::ConflictingHeadRef { existing, .. }) => {
+                assert_eq!(existing, "my-garden");
+            }
+            other => panic!("Expected a conflict, got {:?}", other.map(|_| ())),
+        }
+
+        // The conflict is also caught when it appears after the store was created.
+        let mut late = store("late").unwrap();
+        let mut nested = store("late/nested").unwrap();
+        nested.add("data 2".into());
+        nested.persist().unwrap();
+        late.add("data 3".into());
+        assert!(matches!(
+            late.persist(),
+            Err(ChainStoreError::ConflictingHeadRef { .. })
+        ));
+    }
 }
@@ -0,0 +1,188 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+use crate::{actions, selectors, Store};
+
+/// Decides when the chain is persisted without the user asking for it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AutosavePolicy {
+    /// Save once this many blocks haven't been persisted. Each step the player took
+    /// since their position was last recorded counts as a block.
+    pub every_blocks: Option<usize>,
+    /// Save once the oldest unpersisted block, or step, is this old.
+    pub every: Option<Duration>,
+    /// Save when quitting from the menu, or when the window is closed. When this is
+    /// off, the user is asked to confirm quitting with unpersisted blocks.
+    pub on_quit: bool,
+}
+
+impl Default for AutosavePolicy {
+    fn default() -> Self {
+        Self {
+            every_blocks: Some(50),
+            every: Some(Duration::from_secs(60)),
+            on_quit: true,
+        }
+    }
+}
+
+impl AutosavePolicy {
+    pub fn should_save(&self, unpersisted: usize, unsaved_for: Duration) -> bool {
+        if unpersisted == 0 {
+            return false;
+        }
+        if let Some(every_blocks) = self.every_blocks {
+            if unpersisted >= every_blocks {
+                return true;
+            }
+        }
+        if let Some(every) = self.every {
+            if unsaved_for >= every {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// Applies the AutosavePolicy to a Store as the game runs.
+#[derive(Debug)]
+pub struct Autosave {
+    pub policy: AutosavePolicy,
+    /// When the store was first seen with unpersisted blocks.
+    unsaved_since: Option<Instant>,
+}
+
+impl Autosave {
+    pub fn new(policy: AutosavePolicy) -> Self {
+        Self {
+            policy,
+            unsaved_since: None,
+        }
+    }
+
+    /// Persist the store if the policy calls for it. Returns true if it was saved.
+    pub fn update(&mut self, store: &mut Store) -> Result<bool> {
+        let unpersisted = store.chains.unpersisted_block_count()
+            + selectors::get_unsaved_moves(store.state());
+        if unpersisted == 0 {
+            // Nothing to save, or it was saved some other way.
+            self.unsaved_since = None;
+            return Ok(false);
+        }
+        let unsaved_since = *self.unsaved_since.get_or_insert_with(Instant::now);
+
+        if !self
+            .policy
+            .should_save(unpersisted, unsaved_since.elapsed())
+        {
+            return Ok(false);
+        }
+        actions::save(store)?;
+        self.unsaved_since = None;
+        Ok(true)
+    }
+}
+
+/// Record where the player left off before quitting, whether or not the policy saves
+/// on quit, so that walking around is never lost without asking. Returns true if
+/// there are blocks that quitting would lose.
+pub fn checkpoint_before_quit(store: &mut Store) -> bool {
+    actions::checkpoint(store);
+    store.chains.unpersisted_block_count() > 0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        chain_store::{FsChainStore, HeadRef},
+        game::primitives::Position,
+        ChainAction, GameAction,
+    };
+    use tempdir::TempDir;
+
+    fn get_store(dir: &TempDir) -> Store {
+        let chain_store = FsChainStore::<ChainAction>::try_new(
+            dir.path().into(),
+            HeadRef::try_from("my-garden").unwrap(),
+        )
+        .expect("Failed to create ChainStore");
+        let mut store = Store::try_new(Box::new(chain_store)).unwrap();
+        store.dispatch(actions::create_garden_plot("The Secret Garden".into()));
+        store.persist().unwrap();
+        store
+    }
+
+    fn walk(store: &mut Store, steps: usize) {
+        let step = Position::new(1, 0);
+        for _ in 0..steps {
+            let position = selectors::get_player_position(store.state()).unwrap();
+            store.dispatch(GameAction::MovePlayer((position + step, step)).into());
+        }
+    }
+
+    #[test]
+    fn test_moves_count_toward_autosave() {
+        let dir = TempDir::new("garden-autosave").unwrap();
+        let mut store = get_store(&dir);
+        let mut autosave = Autosave::new(AutosavePolicy {
+            every_blocks: Some(3),
+            every: None,
+            on_quit: true,
+        });
+        walk(&mut store, 2);
+        assert!(!autosave.update(&mut store).unwrap());
+        walk(&mut store, 1);
+        assert!(
+            autosave.update(&mut store).unwrap(),
+            "Walking is saved, even without any new blocks."
+        );
+        assert_eq!(store.chains.unpersisted_block_count(), 0);
+        assert_eq!(selectors::get_unsaved_moves(store.state()), 0);
+        assert!(matches!(
+            store.chains.iter_loaded().next_back().unwrap().payload.data,
+            ChainAction::MovePlayer(_)
+        ));
+    }
+
+    #[test]
+    fn test_checkpoint_before_quit() {
+        let dir = TempDir::new("garden-autosave").unwrap();
+        let mut store = get_store(&dir);
+        assert!(!checkpoint_before_quit(&mut store), "Nothing to lose.");
+        walk(&mut store, 2);
+        assert!(
+            checkpoint_before_quit(&mut store),
+            "Walking is checkpointed, and then asked about."
+        );
+        assert_eq!(store.chains.unpersisted_block_count(), 1);
+        actions::save(&mut store).unwrap();
+        assert!(!checkpoint_before_quit(&mut store));
+    }
+
+    #[test]
+    fn test_should_save() {
+        let policy = AutosavePolicy {
+            every_blocks: Some(10),
+            every: Some(Duration::from_secs(30)),
+            on_quit: true,
+        };
+        let secs = Duration::from_secs;
+        assert!(!policy.should_save(0, secs(100)), "Nothing to save.");
+        assert!(!policy.should_save(9, secs(29)));
+        assert!(policy.should_save(10, secs(0)), "Enough blocks.");
+        assert!(policy.should_save(1, secs(30)), "Enough time.");
+    }
+
+    #[test]
+    fn test_should_save_disabled() {
+        let policy = AutosavePolicy {
+            every_blocks: None,
+            every: None,
+            on_quit: false,
+        };
+        assert!(!policy.should_save(1000, Duration::from_secs(1000)));
+    }
+}
@@ -0,0 +1,122 @@
+use rltk::{Rltk, RGB};
+
+use super::{
+    game_state::{GAME_H, GAME_W},
+    hud::LOG_LINES,
+    input_device::InputDevice,
+    keymap::Command,
+};
+use crate::{middleware::ActionInspector, selector_stats};
+
+/// How many recent actions the inspector keeps.
+const INSPECTOR_CAPACITY: usize = 100;
+
+const LEFT: i32 = GAME_W / 2;
+const TOP: i32 = 1;
+const WIDTH: i32 = GAME_W - LEFT;
+/// Stop above the separator line of the HUD's message log.
+const HEIGHT: i32 = GAME_H - LOG_LINES - 1 - TOP;
+
+/// A debug overlay showing the selector cache stats, and the recent actions that
+/// went through the store. It is toggled with the ToggleDevTools command.
+#[derive(Debug)]
+pub struct DevTools {
+    pub visible: bool,
+    pub inspector: ActionInspector,
+}
+
+impl Default for DevTools {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            inspector: ActionInspector::new(INSPECTOR_CAPACITY),
+        }
+    }
+}
+
+impl DevTools {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn update(&mut self, input_device: &InputDevice) {
+        if input_device.is_command(Command::ToggleDevTools) {
+            self.visible = !self.visible;
+        }
+    }
+
+    /// The text of the overlay, which is cut to fit inside of the box.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![String::from(" hit%  calls  avg µs selector")];
+        for (name, stats) in selector_stats::all() {
+            lines.push(format!(
+                "{:>4.0}% {:>6} {:>7} {}",
+                stats.hit_rate() * 100.0,
+                stats.hits + stats.misses,
+                stats.average_recompute_time().as_micros(),
+                name
+            ));
+        }
+
+        lines.push(String::new());
+        lines.push(String::from("Actions"));
+        // Fill the remaining space with the newest actions.
+        let room = (HEIGHT as usize - 2).saturating_sub(lines.len());
+        let records = self.inspector.records();
+        let skip = records.len().saturating_sub(room);
+        for record in records.iter().skip(skip) {
+            let marker = if record.changed_state { '*' } else { ' ' };
+            lines.push(format!("{}{}", marker, record.action));
+        }
+        lines
+    }
+
+    pub fn draw(&self, ctx: &mut Rltk) {
+        if !self.visible {
+            return;
+        }
+        let fg = RGB::named(rltk::GRAY60);
+        let bg = RGB::named(rltk::BLACK);
+        ctx.draw_box(LEFT, TOP, WIDTH - 1, HEIGHT - 1, fg, bg);
+        let max_width = (WIDTH - 2) as usize;
+        for (i, line) in self.lines().iter().take(HEIGHT as usize - 2).enumerate() {
+            let line: String = line.chars().take(max_width).collect();
+            ctx.print_color(
+                LEFT + 1,
+                TOP + 1 + i as i32,
+                RGB::named(rltk::WHITE),
+                bg,
+                line,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{actions, middleware::Middleware, Action, State};
+    use std::rc::Rc;
+
+    #[test]
+    fn test_lines_fit() {
+        let mut devtools = DevTools::new();
+        let state = Rc::new(State::new());
+        for i in 0..INSPECTOR_CAPACITY {
+            let action: Action = actions::create_garden_plot(format!("Garden {}", i));
+            let next = Rc::new(state.reduce(&action));
+            devtools.inspector.after_reduce(&action, &state, &next);
+        }
+
+        let lines = devtools.lines();
+        assert_eq!(
+            lines.len(),
+            HEIGHT as usize - 2,
+            "The actions fill the box."
+        );
+        assert!(
+            lines.last().unwrap().contains("Garden 99"),
+            "The newest action is shown."
+        );
+    }
+}
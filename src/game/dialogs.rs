@@ -0,0 +1,101 @@
+//! The dialogs of the game. Each one pushes a modal onto the stack, along with
+//! what to do with its result, so new dialogs don't require changes to GameState.
+
+use super::{
+    autosave,
+    game_state::{GAME_H, GAME_W},
+    ui::{Choices, ModalContext, ModalStack, TextInput},
+};
+use crate::{
+    actions,
+    garden::{self, GardenPlot},
+};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum MainMenuItem {
+    Save,
+    Exit,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum QuitItem {
+    SaveAndExit,
+    ExitWithoutSaving,
+    Cancel,
+}
+
+pub fn ask_new_garden(modals: &mut ModalStack) {
+    let mut text_input = TextInput::new(String::from(""), garden::MAX_NAME_LEN as i32)
+        .with_validator(GardenPlot::validate_name);
+    text_input.center(GAME_W, GAME_H);
+    modals.push(text_input, |name, cx| match name {
+        Some(name) => cx.store.dispatch(actions::create_garden_plot(name)),
+        // A garden is required to play, so keep asking.
+        None => ask_new_garden(cx.modals),
+    });
+}
+
+/// Show the main menu. `save_on_quit` comes from the AutosavePolicy, and decides if
+/// exiting saves, or asks first.
+pub fn show_main_menu(modals: &mut ModalStack, save_on_quit: bool) {
+    let mut choices = Choices::new(vec![
+        (String::from("Save"), MainMenuItem::Save),
+        (String::from("Exit"), MainMenuItem::Exit),
+    ]);
+    choices.center(GAME_W, GAME_H);
+    modals.push(choices, move |item, cx| match item {
+        Some(MainMenuItem::Save) => {
+            if let Err(err) = actions::save(cx.store) {
+                cx.log.push(format!("Failed to save: {}", err));
+                // Keep the menu open, so saving can be tried again.
+                show_main_menu(cx.modals, save_on_quit);
+            }
+        }
+        Some(MainMenuItem::Exit) => request_quit(cx, save_on_quit),
+        None => {}
+    });
+}
+
+/// Quit the game, without losing any blocks that haven't been persisted yet. They
+/// are either saved, or the user is asked what to do with them.
+pub fn request_quit(cx: &mut ModalContext, save_on_quit: bool) {
+    if !save_on_quit {
+        if autosave::checkpoint_before_quit(cx.store) {
+            confirm_quit(cx.modals);
+        } else {
+            cx.rltk.quit();
+        }
+        return;
+    }
+    // Saving may record where the player left off, even with no unsaved blocks.
+    match actions::save(cx.store) {
+        Ok(()) => cx.rltk.quit(),
+        Err(err) => {
+            cx.log.push(format!("Failed to save: {}", err));
+            confirm_quit(cx.modals);
+        }
+    }
+}
+
+fn confirm_quit(modals: &mut ModalStack) {
+    let mut choices = Choices::new(vec![
+        (String::from("Save and exit"), QuitItem::SaveAndExit),
+        (
+            String::from("Exit without saving"),
+            QuitItem::ExitWithoutSaving,
+        ),
+        (String::from("Cancel"), QuitItem::Cancel),
+    ]);
+    choices.center(GAME_W, GAME_H);
+    modals.push(choices, |item, cx| match item {
+        Some(QuitItem::SaveAndExit) => match actions::save(cx.store) {
+            Ok(()) => cx.rltk.quit(),
+            Err(err) => {
+                cx.log.push(format!("Failed to save: {}", err));
+                confirm_quit(cx.modals);
+            }
+        },
+        Some(QuitItem::ExitWithoutSaving) => cx.rltk.quit(),
+        Some(QuitItem::Cancel) | None => {}
+    });
+}
@@ -6,23 +6,37 @@ use crate::{
 use anyhow::Result;
 
 use super::{
+    autosave::{Autosave, AutosavePolicy},
+    devtools::DevTools,
+    dialogs,
     drawable::Draw,
+    hud::{Hud, LOG_LINES},
     input_device::InputDevice,
+    keymap::{Command, KeyMap},
     player::Player,
     primitives::{BBox, Position, Size},
     ui,
 };
-use rltk::Rltk;
+use rltk::{BEvent, Rltk, INPUT, RGB};
 
 pub enum Phase {
     Playing,
     Menu,
 }
 
+/// The configurable parts of the game.
+#[derive(Debug, Default)]
+pub struct GameOptions {
+    pub keymap: KeyMap,
+    pub autosave: AutosavePolicy,
+}
+
 pub struct GameState {
     input_device: InputDevice,
-    input_ui: Option<ui::InputUI>,
-    input_handler: ui::InputHandler,
+    modals: ui::ModalStack,
+    hud: Hud,
+    devtools: DevTools,
+    autosave: Autosave,
     store: Store,
     prev_state: Rc<State>,
 }
@@ -30,18 +44,39 @@ pub struct GameState {
 pub const GAME_W: i32 = 80;
 pub const GAME_H: i32 = 50;
 
+/// The part of the screen the world is drawn in, between the status line and the
+/// separator above the log.
+pub fn world_view() -> BBox<i32> {
+    BBox {
+        top_left: Position::new(0, 1),
+        size: Size::new(GAME_W - 1, GAME_H - LOG_LINES - 3),
+    }
+}
+
 impl GameState {
-    pub fn try_new(chain_store: Box<dyn ChainStore<ChainAction>>) -> Result<Self> {
+    pub fn try_new(
+        chain_store: Box<dyn ChainStore<ChainAction>>,
+        options: GameOptions,
+    ) -> Result<Self> {
+        // Closing the window goes through the event queue, so that the game gets a
+        // chance to save first.
+        INPUT.lock().activate_event_queue();
+
         let mut game_state = Self {
-            input_device: InputDevice::new(),
-            input_ui: None,
-            input_handler: Default::default(),
+            input_device: InputDevice::new(options.keymap),
+            modals: ui::ModalStack::new(),
+            hud: Hud::new(),
+            devtools: DevTools::new(),
+            autosave: Autosave::new(options.autosave),
             store: Store::try_new(chain_store)?,
             prev_state: Rc::new(State::new()),
         };
 
+        let inspector = game_state.devtools.inspector.clone();
+        game_state.store.add_middleware(Box::new(inspector));
+
         if selectors::get_my_garden(game_state.state()).is_none() {
-            game_state.ask_new_garden()
+            dialogs::ask_new_garden(&mut game_state.modals);
         }
 
         Ok(game_state)
@@ -51,56 +86,54 @@ impl GameState {
         self.store.state()
     }
 
-    pub fn ask_new_garden(&mut self) {
-        let mut text_input = ui::TextInput::new(String::from(""), 30);
-        text_input.center(GAME_W, GAME_H);
-        self.input_ui = Some(ui::InputUI::TextInput(text_input));
-        self.input_handler = ui::InputHandler::NewGarden;
-    }
-
-    pub fn show_main_menu(&mut self) {
-        let mut choices =
-            ui::Choices::new(vec![String::from("Save"), String::from("Exit")]);
-        choices.center(GAME_W, GAME_H);
-        self.input_ui = Some(ui::InputUI::Choices(choices));
-        self.input_handler = ui::InputHandler::MainMenu;
-    }
-
     pub fn update(&mut self, ctx: &mut Rltk) {
         self.store.dispatch(actions::tick_game());
+        self.input_device.mode = self.modals.input_mode();
         self.input_device.update(ctx);
-        if self.input_ui.is_none() && self.input_device.is_esc {
-            eprintln!("show_main_menu");
-            self.show_main_menu();
-        }
-        actions::maybe_move_player(&mut self.store, &self.input_device);
-
-        if let Some(ref mut input_ui) = self.input_ui {
-            if let Some(text) = match input_ui {
-                ui::InputUI::Choices(input) => input.update(&self.input_device),
-                ui::InputUI::TextInput(input) => input.update(&self.input_device, &ctx),
-            } {
-                self.input_ui = None;
-                self.handle_input(text, ctx);
+        if self.modals.is_empty() {
+            if self.input_device.is_command(Command::Menu) {
+                dialogs::show_main_menu(&mut self.modals, self.autosave.policy.on_quit);
+            } else {
+                actions::maybe_move_player(&mut self.store, &self.input_device);
+                actions::maybe_move_cursor(&mut self.store, &self.input_device);
             }
+        } else {
+            self.modals.update(
+                &self.input_device,
+                &mut self.store,
+                ctx,
+                &mut self.hud.log,
+            );
+        }
+
+        // Autosave persists, and the HUD reads the StoreEvents of persisting, which
+        // store subscribers can't do, so they are polled once the frame's actions are
+        // done.
+        if let Err(err) = self.autosave.update(&mut self.store) {
+            self.hud.log.push(format!("Autosave failed: {}", err));
         }
+        self.hud.update(&self.input_device, &mut self.store);
+        self.devtools.update(&self.input_device);
     }
 
-    pub fn handle_input(&mut self, text: String, ctx: &mut Rltk) {
-        match self.input_handler {
-            ui::InputHandler::NewGarden => {
-                self.store.dispatch(actions::create_garden_plot(text));
-            }
-            ui::InputHandler::MainMenu => {
-                if text == "Save" {
-                    self.store
-                        .chains
-                        .persist()
-                        .expect("Failed to store the block chain");
-                } else if text == "Exit" {
-                    ctx.quit();
-                }
+    /// Drain the event queue, which is only used for noticing the window closing.
+    fn handle_events(&mut self, ctx: &mut Rltk) {
+        let mut close_requested = false;
+        INPUT.lock().for_each_message(|event| {
+            if let BEvent::CloseRequested = event {
+                close_requested = true;
             }
+        });
+        if close_requested {
+            dialogs::request_quit(
+                &mut ui::ModalContext {
+                    store: &mut self.store,
+                    rltk: ctx,
+                    modals: &mut self.modals,
+                    log: &mut self.hud.log,
+                },
+                self.autosave.policy.on_quit,
+            );
         }
     }
 
@@ -112,13 +145,14 @@ impl GameState {
         if let Some(player) = selectors::get_drawable_player(self.state()) {
             player.draw(state.clone(), ctx, &*player);
         }
-
-        if let Some(ref input_ui) = self.input_ui {
-            match input_ui {
-                ui::InputUI::Choices(input) => input.draw(state, ctx, input),
-                ui::InputUI::TextInput(input) => input.draw(state, ctx, input),
-            }
+        if let Some(cursor) = selectors::get_cursor(state.clone()) {
+            let on_screen = cursor - selectors::get_camera(state.clone());
+            ctx.set_bg(on_screen.x, on_screen.y, RGB::named(rltk::GRAY30));
         }
+
+        self.hud.draw(ctx);
+        self.devtools.draw(ctx);
+        self.modals.draw(state, ctx);
     }
 }
 
@@ -128,6 +162,7 @@ impl rltk::GameState for GameState {
         if ctx.quitting {
             eprintln!("Quitting");
         }
+        self.handle_events(ctx);
         self.update(ctx);
         self.draw(self.state(), ctx);
         self.prev_state = self.state();
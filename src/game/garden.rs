@@ -7,7 +7,7 @@ use crate::{
     },
     garden::GardenPlot,
     hash::Hash,
-    State,
+    selectors, State,
 };
 
 use rltk::{Rltk, RGB};
@@ -52,21 +52,23 @@ impl DrawableGarden {
 impl drawable::Draw for DrawableGarden {
     fn draw<T: Entity>(&self, state: Rc<State>, ctx: &mut Rltk, _entity: &T) {
         self.drawable_box.draw(state.clone(), ctx, self);
-        let mut position = self.bbox.top_left;
+        let mut position = self.bbox(state.clone()).top_left;
         position.x += 2;
         self.drawable_text.draw(state, ctx, &position);
     }
 }
 
 impl Entity for DrawableGarden {
-    fn position<'a>(&'a self, _state: Rc<State>) -> Position {
-        Position::new(
-            self.bbox.top_left.x + (self.bbox.size.x / 2),
-            self.bbox.top_left.y + (self.bbox.size.y / 2),
-        )
+    fn position<'a>(&'a self, state: Rc<State>) -> Position {
+        self.bbox(state).center()
     }
 
-    fn bbox<'a>(&'a self, _state: Rc<State>) -> BBox<i32> {
-        self.bbox.clone()
+    /// Entities are drawn relative to the camera, so this is the box on the screen,
+    /// rather than in the world.
+    fn bbox<'a>(&'a self, state: Rc<State>) -> BBox<i32> {
+        BBox {
+            top_left: self.bbox.top_left - selectors::get_camera(state),
+            size: self.bbox.size,
+        }
     }
 }
@@ -0,0 +1,219 @@
+use std::collections::VecDeque;
+
+use rltk::{Rltk, RGB};
+
+use super::{
+    game_state::{GAME_H, GAME_W},
+    input_device::InputDevice,
+    keymap::Command,
+};
+use crate::{selectors, Store};
+
+/// How many lines of the message log are visible at once.
+pub const LOG_LINES: i32 = 6;
+
+/// How many messages are kept around for scrolling back.
+const LOG_CAPACITY: usize = 200;
+
+/// A list of messages, with the newest at the end. It can be scrolled back through.
+#[derive(Debug, Default)]
+pub struct MessageLog {
+    messages: VecDeque<String>,
+    /// How many messages back from the newest the view is scrolled.
+    scroll: usize,
+}
+
+impl MessageLog {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn push(&mut self, message: String) {
+        if self.messages.len() == LOG_CAPACITY {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(message);
+        // Keep the view steady if it was scrolled back.
+        if self.scroll > 0 {
+            self.scroll = (self.scroll + 1).min(self.max_scroll());
+        }
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.messages.len().saturating_sub(LOG_LINES as usize)
+    }
+
+    pub fn scroll_up(&mut self) {
+        self.scroll = (self.scroll + 1).min(self.max_scroll());
+    }
+
+    pub fn scroll_down(&mut self) {
+        self.scroll = self.scroll.saturating_sub(1);
+    }
+
+    /// The messages that are currently in view, oldest first.
+    pub fn visible(&self) -> impl Iterator<Item = &String> {
+        let end = self.messages.len() - self.scroll;
+        let start = end.saturating_sub(LOG_LINES as usize);
+        self.messages.range(start..end)
+    }
+}
+
+/// The heads-up display, which is a status line along the top of the screen, and a
+/// message log along the bottom.
+#[derive(Debug, Default)]
+pub struct Hud {
+    pub log: MessageLog,
+    /// The status line, which is only rebuilt when the store has events, as counting
+    /// the blocks every frame walks the whole chain.
+    status: Option<String>,
+}
+
+impl Hud {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn update(&mut self, input_device: &InputDevice, store: &mut Store) {
+        let events = store.take_events();
+        if !events.is_empty() || self.status.is_none() {
+            self.status = Some(Hud::status(store));
+        }
+        for event in events {
+            self.log.push(event.to_string());
+        }
+        if input_device.is_command(Command::ScrollLogUp) {
+            self.log.scroll_up();
+        }
+        if input_device.is_command(Command::ScrollLogDown) {
+            self.log.scroll_down();
+        }
+    }
+
+    /// Build the status line, e.g. "My Garden | head 1a2b3c4d | 12 blocks | 3 unsaved"
+    pub fn status(store: &Store) -> String {
+        let name = match selectors::get_my_garden(store.state()) {
+            Some(plot) => plot.name.clone(),
+            None => String::from("(no garden)"),
+        };
+        let head = match store.chains.iter_loaded().next_back() {
+            Some(block) => block.hash.short(),
+            None => String::from("none"),
+        };
+        format!(
+            "{} | head {} | {} blocks | {} unsaved",
+            name,
+            head,
+            store.chains.loaded_block_count(),
+            store.chains.unpersisted_block_count()
+        )
+    }
+
+    pub fn draw(&self, ctx: &mut Rltk) {
+        let fg = RGB::named(rltk::GRAY60);
+        let bg = RGB::named(rltk::BLACK);
+        if let Some(status) = &self.status {
+            ctx.print_color(0, 0, fg, bg, status);
+        }
+
+        let top = GAME_H - LOG_LINES;
+        for x in 0..GAME_W {
+            ctx.set(x, top - 1, fg, bg, rltk::to_cp437('─'));
+        }
+        for (i, message) in self.log.visible().enumerate() {
+            ctx.print_color(0, top + i as i32, RGB::named(rltk::WHITE), bg, message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        actions,
+        chain_store::{FsChainStore, HeadRef},
+        game::keymap::KeyMap,
+        ChainAction,
+    };
+    use tempdir::TempDir;
+
+    fn visible(log: &MessageLog) -> Vec<&str> {
+        log.visible().map(|s| s.as_str()).collect()
+    }
+
+    #[test]
+    fn test_message_log_scroll() {
+        let mut log = MessageLog::new();
+        for i in 0..8 {
+            log.push(format!("{}", i));
+        }
+        assert_eq!(visible(&log), vec!["2", "3", "4", "5", "6", "7"]);
+
+        log.scroll_up();
+        log.scroll_up();
+        log.scroll_up();
+        assert_eq!(
+            visible(&log),
+            vec!["0", "1", "2", "3", "4", "5"],
+            "Scrolling stops at the oldest message."
+        );
+
+        log.push("8".into());
+        assert_eq!(
+            visible(&log),
+            vec!["0", "1", "2", "3", "4", "5"],
+            "The view stays put when scrolled back."
+        );
+
+        log.scroll_down();
+        assert_eq!(visible(&log), vec!["1", "2", "3", "4", "5", "6"]);
+    }
+
+    #[test]
+    fn test_message_log_capacity() {
+        let mut log = MessageLog::new();
+        for i in 0..(LOG_CAPACITY + 10) {
+            log.push(format!("{}", i));
+        }
+        assert_eq!(log.messages.len(), LOG_CAPACITY);
+        assert_eq!(log.messages.front().map(|s| s.as_str()), Some("10"));
+    }
+
+    #[test]
+    fn test_status_is_rebuilt_on_store_events() {
+        let dir = TempDir::new("garden").unwrap();
+        let chain_store = FsChainStore::<ChainAction>::try_new(
+            dir.path().into(),
+            HeadRef::try_from("my-garden").unwrap(),
+        )
+        .expect("Failed to create ChainStore");
+        let mut store = Store::try_new(Box::new(chain_store)).unwrap();
+        let input_device = InputDevice::new(KeyMap::default());
+        let mut hud = Hud::new();
+
+        hud.update(&input_device, &mut store);
+        assert!(hud
+            .status
+            .as_ref()
+            .unwrap()
+            .contains("| 0 blocks | 0 unsaved"));
+
+        store.dispatch(actions::create_garden_plot("The Secret Garden".into()));
+        assert!(
+            hud.status.as_ref().unwrap().contains("| 0 blocks"),
+            "The status is kept between updates."
+        );
+        hud.update(&input_device, &mut store);
+        let status = hud.status.clone().unwrap();
+        assert!(status.starts_with("The Secret Garden"));
+        assert!(status.contains("| 1 blocks | 1 unsaved"));
+
+        store.persist().unwrap();
+        hud.update(&input_device, &mut store);
+        assert!(hud
+            .status
+            .as_ref()
+            .unwrap()
+            .contains("| 1 blocks | 0 unsaved"));
+    }
+}
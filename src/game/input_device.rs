@@ -1,169 +1,157 @@
-use super::primitives::{Position, Vec2};
+use super::{
+    keymap::{self, Command, KeyMap},
+    primitives::{Position, Vec2},
+};
 use rltk::{Rltk, VirtualKeyCode};
+use std::cell::RefCell;
+
+/// Decides how key presses are interpreted.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum InputMode {
+    /// Keys are looked up in the KeyMap and turned into commands.
+    Commands,
+    /// Keys that type a character only produce a letter, so that typing "w" into a
+    /// text input doesn't also move the player. Other keys still go through the
+    /// KeyMap.
+    Text,
+}
+
+/// Editing keys that are only reported in InputMode::Text.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TextEdit {
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Home,
+    End,
+}
+
+impl TextEdit {
+    fn from_key(key: VirtualKeyCode) -> Option<Self> {
+        match key {
+            VirtualKeyCode::Back => Some(TextEdit::Backspace),
+            VirtualKeyCode::Delete => Some(TextEdit::Delete),
+            VirtualKeyCode::Left => Some(TextEdit::Left),
+            VirtualKeyCode::Right => Some(TextEdit::Right),
+            VirtualKeyCode::Home => Some(TextEdit::Home),
+            VirtualKeyCode::End => Some(TextEdit::End),
+            _ => None,
+        }
+    }
+}
 
 pub struct InputDevice {
+    pub keymap: KeyMap,
+    pub mode: InputMode,
     pub move_intent: Position,
-    pub is_enter: bool,
-    pub is_backspace: bool,
-    pub is_quitting: bool,
-    pub is_esc: bool,
+    pub commands: Vec<Command>,
+    pub text_edit: Option<TextEdit>,
     pub letter: Option<char>,
+    /// Where the mouse is on the screen.
+    pub mouse: Position,
+    /// Text copied from a text input, for pasting into another. The window has no
+    /// access to the system clipboard, so this is only shared within the game.
+    pub clipboard: RefCell<String>,
 }
 
 impl InputDevice {
-    pub fn new() -> Self {
+    pub fn new(keymap: KeyMap) -> Self {
         Self {
+            keymap,
+            mode: InputMode::Commands,
             move_intent: Vec2::new(0, 0),
-            is_enter: false,
-            is_backspace: false,
-            is_quitting: false,
-            is_esc: false,
+            commands: Vec::new(),
+            text_edit: None,
             letter: None,
+            mouse: Vec2::new(0, 0),
+            clipboard: RefCell::new(String::new()),
         }
     }
 
+    /// Check if a command was triggered this frame.
+    pub fn is_command(&self, command: Command) -> bool {
+        self.commands.contains(&command)
+    }
+
     pub fn update(&mut self, ctx: &mut Rltk) {
+        self.update_key(ctx.key, ctx.shift);
+        let (x, y) = ctx.mouse_pos();
+        self.mouse = Position::new(x, y);
+    }
+
+    /// Process a single key press, split out from `update` so that it can be driven
+    /// without an Rltk context.
+    pub fn update_key(&mut self, key: Option<VirtualKeyCode>, shift: bool) {
         self.move_intent = Vec2::new(0, 0);
-        self.is_enter = false;
-        self.is_backspace = false;
-        self.is_esc = false;
+        self.commands.clear();
+        self.text_edit = None;
         self.letter = None;
 
-        if let Some(key) = ctx.key {
-            match key {
-                VirtualKeyCode::Left | VirtualKeyCode::Numpad4 | VirtualKeyCode::A => {
-                    self.move_intent.x = -1;
-                }
-                VirtualKeyCode::Right | VirtualKeyCode::Numpad6 | VirtualKeyCode::D => {
-                    self.move_intent.x = 1;
-                }
-                VirtualKeyCode::Up | VirtualKeyCode::Numpad8 | VirtualKeyCode::W => {
-                    self.move_intent.y = -1;
-                }
-                VirtualKeyCode::Down | VirtualKeyCode::Numpad2 | VirtualKeyCode::S => {
-                    self.move_intent.y = 1;
-                }
-                VirtualKeyCode::Return => {
-                    self.is_enter = true;
-                }
-                VirtualKeyCode::Back => {
-                    self.is_backspace = true;
-                }
-                VirtualKeyCode::Escape => self.is_esc = true,
-                _ => {}
+        let key = match key {
+            Some(key) => key,
+            None => return,
+        };
+
+        if self.mode == InputMode::Text {
+            self.text_edit = TextEdit::from_key(key);
+            if self.text_edit.is_some() {
+                return;
             }
+            self.letter = keymap::key_to_char(key, shift);
+            if self.letter.is_some() {
+                return;
+            }
+        }
 
-            self.letter = if ctx.shift {
-                match key {
-                    VirtualKeyCode::Key1 => Some('!'),
-                    VirtualKeyCode::Key2 => Some('@'),
-                    VirtualKeyCode::Key3 => Some('#'),
-                    VirtualKeyCode::Key4 => Some('$'),
-                    VirtualKeyCode::Key5 => Some('%'),
-                    VirtualKeyCode::Key6 => Some('^'),
-                    VirtualKeyCode::Key7 => Some('&'),
-                    VirtualKeyCode::Key8 => Some('*'),
-                    VirtualKeyCode::Key9 => Some('('),
-                    VirtualKeyCode::Key0 => Some(')'),
-                    VirtualKeyCode::A => Some('A'),
-                    VirtualKeyCode::B => Some('B'),
-                    VirtualKeyCode::C => Some('C'),
-                    VirtualKeyCode::D => Some('D'),
-                    VirtualKeyCode::E => Some('E'),
-                    VirtualKeyCode::F => Some('F'),
-                    VirtualKeyCode::G => Some('G'),
-                    VirtualKeyCode::H => Some('H'),
-                    VirtualKeyCode::I => Some('I'),
-                    VirtualKeyCode::J => Some('J'),
-                    VirtualKeyCode::K => Some('K'),
-                    VirtualKeyCode::L => Some('L'),
-                    VirtualKeyCode::M => Some('M'),
-                    VirtualKeyCode::N => Some('N'),
-                    VirtualKeyCode::O => Some('O'),
-                    VirtualKeyCode::P => Some('P'),
-                    VirtualKeyCode::Q => Some('Q'),
-                    VirtualKeyCode::R => Some('R'),
-                    VirtualKeyCode::S => Some('S'),
-                    VirtualKeyCode::T => Some('T'),
-                    VirtualKeyCode::U => Some('U'),
-                    VirtualKeyCode::V => Some('V'),
-                    VirtualKeyCode::W => Some('W'),
-                    VirtualKeyCode::X => Some('X'),
-                    VirtualKeyCode::Y => Some('Y'),
-                    VirtualKeyCode::Z => Some('Z'),
-
-                    VirtualKeyCode::Apostrophe => Some('\''),
-                    VirtualKeyCode::Asterisk => Some('*'),
-                    VirtualKeyCode::At => Some('@'),
-                    VirtualKeyCode::Backslash => Some('\\'),
-                    VirtualKeyCode::Colon => Some(':'),
-                    VirtualKeyCode::Comma => Some(','),
-                    VirtualKeyCode::Equals => Some('='),
-                    VirtualKeyCode::Grave => Some('`'),
-                    VirtualKeyCode::Minus => Some('-'),
-                    VirtualKeyCode::Period => Some('.'),
-                    VirtualKeyCode::Plus => Some('+'),
-                    VirtualKeyCode::Semicolon => Some(';'),
-                    VirtualKeyCode::Slash => Some('/'),
-                    VirtualKeyCode::Space => Some(' '),
-                    _ => None,
-                }
-            } else {
-                match key {
-                    VirtualKeyCode::Key1 => Some('1'),
-                    VirtualKeyCode::Key2 => Some('2'),
-                    VirtualKeyCode::Key3 => Some('3'),
-                    VirtualKeyCode::Key4 => Some('4'),
-                    VirtualKeyCode::Key5 => Some('5'),
-                    VirtualKeyCode::Key6 => Some('6'),
-                    VirtualKeyCode::Key7 => Some('7'),
-                    VirtualKeyCode::Key8 => Some('8'),
-                    VirtualKeyCode::Key9 => Some('9'),
-                    VirtualKeyCode::Key0 => Some('0'),
-                    VirtualKeyCode::A => Some('a'),
-                    VirtualKeyCode::B => Some('b'),
-                    VirtualKeyCode::C => Some('c'),
-                    VirtualKeyCode::D => Some('d'),
-                    VirtualKeyCode::E => Some('e'),
-                    VirtualKeyCode::F => Some('f'),
-                    VirtualKeyCode::G => Some('g'),
-                    VirtualKeyCode::H => Some('h'),
-                    VirtualKeyCode::I => Some('i'),
-                    VirtualKeyCode::J => Some('j'),
-                    VirtualKeyCode::K => Some('k'),
-                    VirtualKeyCode::L => Some('l'),
-                    VirtualKeyCode::M => Some('m'),
-                    VirtualKeyCode::N => Some('n'),
-                    VirtualKeyCode::O => Some('o'),
-                    VirtualKeyCode::P => Some('p'),
-                    VirtualKeyCode::Q => Some('q'),
-                    VirtualKeyCode::R => Some('r'),
-                    VirtualKeyCode::S => Some('s'),
-                    VirtualKeyCode::T => Some('t'),
-                    VirtualKeyCode::U => Some('u'),
-                    VirtualKeyCode::V => Some('v'),
-                    VirtualKeyCode::W => Some('w'),
-                    VirtualKeyCode::X => Some('x'),
-                    VirtualKeyCode::Y => Some('y'),
-                    VirtualKeyCode::Z => Some('z'),
-
-                    VirtualKeyCode::Apostrophe => Some('\''),
-                    VirtualKeyCode::Asterisk => Some('*'),
-                    VirtualKeyCode::At => Some('@'),
-                    VirtualKeyCode::Backslash => Some('\\'),
-                    VirtualKeyCode::Colon => Some(':'),
-                    VirtualKeyCode::Comma => Some(','),
-                    VirtualKeyCode::Equals => Some('='),
-                    VirtualKeyCode::Grave => Some('`'),
-                    VirtualKeyCode::Minus => Some('-'),
-                    VirtualKeyCode::Period => Some('.'),
-                    VirtualKeyCode::Plus => Some('+'),
-                    VirtualKeyCode::Semicolon => Some(';'),
-                    VirtualKeyCode::Slash => Some('/'),
-                    VirtualKeyCode::Space => Some(' '),
-                    _ => None,
-                }
+        self.commands = self.keymap.commands(key);
+        for command in &self.commands {
+            match command {
+                Command::MoveLeft => self.move_intent.x = -1,
+                Command::MoveRight => self.move_intent.x = 1,
+                Command::MoveUp => self.move_intent.y = -1,
+                Command::MoveDown => self.move_intent.y = 1,
+                _ => {}
             }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_command_mode() {
+        let mut input_device = InputDevice::new(KeyMap::default());
+        input_device.update_key(Some(VirtualKeyCode::W), false);
+        assert_eq!(input_device.move_intent, Position::new(0, -1));
+        assert_eq!(input_device.letter, None);
+
+        input_device.update_key(Some(VirtualKeyCode::Return), false);
+        assert!(input_device.is_command(Command::Confirm));
+        assert_eq!(input_device.move_intent, Position::new(0, 0));
+    }
+
+    #[test]
+    fn test_text_mode() {
+        let mut input_device = InputDevice::new(KeyMap::default());
+        input_device.mode = InputMode::Text;
+
+        // Typing a letter doesn't also move the player.
+        input_device.update_key(Some(VirtualKeyCode::W), true);
+        assert_eq!(input_device.letter, Some('W'));
+        assert_eq!(input_device.move_intent, Position::new(0, 0));
+        assert!(input_device.commands.is_empty());
+
+        // Keys that don't type anything still go through the key map.
+        input_device.update_key(Some(VirtualKeyCode::Return), false);
+        assert_eq!(input_device.letter, None);
+        assert!(input_device.is_command(Command::Confirm));
+
+        // The arrow keys edit the text rather than move.
+        input_device.update_key(Some(VirtualKeyCode::Left), false);
+        assert_eq!(input_device.text_edit, Some(TextEdit::Left));
+        assert_eq!(input_device.move_intent, Position::new(0, 0));
+    }
+}
@@ -0,0 +1,338 @@
+use anyhow::{bail, Context, Result};
+use rltk::VirtualKeyCode;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs, path::Path};
+
+/// The semantic commands of the game. Physical keys are bound to these through a
+/// KeyMap, so that the rest of the game never needs to know about the keyboard.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Command {
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    Menu,
+    Confirm,
+    Back,
+    /// Copy the text of a text input, see `InputDevice::clipboard`.
+    Copy,
+    /// Paste into a text input.
+    Paste,
+    Plant,
+    Undo,
+    ScrollLogUp,
+    ScrollLogDown,
+    ToggleDevTools,
+}
+
+/// Binds physical keys to commands. A key can trigger more than one command, e.g.
+/// Escape is both "menu" while playing, and "back" while in a dialog.
+///
+/// The config file is JSON, and maps a command to the list of key names:
+/// ```json
+/// {
+///   "move_up": ["Up", "Numpad8", "W"],
+///   "plant": ["P"]
+/// }
+/// ```
+/// Any command missing from the file keeps its default bindings. Key names are the
+/// names of rltk's VirtualKeyCode variants.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyMap {
+    bindings: HashMap<Command, Vec<VirtualKeyCode>>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        use VirtualKeyCode::*;
+        let mut keymap = Self {
+            bindings: HashMap::new(),
+        };
+        keymap.bind(Command::MoveLeft, &[Left, Numpad4, A]);
+        keymap.bind(Command::MoveRight, &[Right, Numpad6, D]);
+        keymap.bind(Command::MoveUp, &[Up, Numpad8, W]);
+        keymap.bind(Command::MoveDown, &[Down, Numpad2, S]);
+        keymap.bind(Command::Menu, &[Escape]);
+        keymap.bind(Command::Confirm, &[Return, NumpadEnter]);
+        keymap.bind(Command::Back, &[Escape]);
+        keymap.bind(Command::Copy, &[F3]);
+        keymap.bind(Command::Paste, &[Insert]);
+        keymap.bind(Command::Plant, &[P]);
+        keymap.bind(Command::Undo, &[U]);
+        keymap.bind(Command::ScrollLogUp, &[PageUp]);
+        keymap.bind(Command::ScrollLogDown, &[PageDown]);
+        keymap.bind(Command::ToggleDevTools, &[F12]);
+        keymap
+    }
+}
+
+impl KeyMap {
+    /// Replace the bindings for a command.
+    pub fn bind(&mut self, command: Command, keys: &[VirtualKeyCode]) {
+        self.bindings.insert(command, keys.to_vec());
+    }
+
+    pub fn keys(&self, command: Command) -> &[VirtualKeyCode] {
+        match self.bindings.get(&command) {
+            Some(keys) => keys,
+            None => &[],
+        }
+    }
+
+    /// List all of the commands that a key triggers.
+    pub fn commands(&self, key: VirtualKeyCode) -> Vec<Command> {
+        let mut commands: Vec<Command> = self
+            .bindings
+            .iter()
+            .filter(|(_, keys)| keys.contains(&key))
+            .map(|(command, _)| *command)
+            .collect();
+        // Keep the order stable, as the HashMap iteration order is arbitrary.
+        commands.sort_by_key(|command| *command as u8);
+        commands
+    }
+
+    /// Parse a JSON config, layering it on top of the default bindings.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let config: HashMap<Command, Vec<String>> =
+            serde_json::from_str(json).context("failed to parse the key map")?;
+
+        let mut keymap = KeyMap::default();
+        for (command, names) in config {
+            let mut keys = Vec::new();
+            for name in names {
+                match key_from_name(&name) {
+                    Some(key) => keys.push(key),
+                    None => bail!("unknown key {:?} bound to {:?}", name, command),
+                }
+            }
+            keymap.bind(command, &keys);
+        }
+        Ok(keymap)
+    }
+
+    /// Load the key map from a file, or use the defaults if there is no file.
+    pub fn load_or_default(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(KeyMap::default());
+        }
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("failed to read the key map {}", path.display()))?;
+        KeyMap::from_json(&json)
+            .with_context(|| format!("invalid key map {}", path.display()))
+    }
+}
+
+/// The keys that can be named in a key map config.
+const NAMED_KEYS: &[VirtualKeyCode] = {
+    use VirtualKeyCode::*;
+    &[
+        Key1,
+        Key2,
+        Key3,
+        Key4,
+        Key5,
+        Key6,
+        Key7,
+        Key8,
+        Key9,
+        Key0,
+        A,
+        B,
+        C,
+        D,
+        E,
+        F,
+        G,
+        H,
+        I,
+        J,
+        K,
+        L,
+        M,
+        N,
+        O,
+        P,
+        Q,
+        R,
+        S,
+        T,
+        U,
+        V,
+        W,
+        X,
+        Y,
+        Z,
+        Escape,
+        F1,
+        F2,
+        F3,
+        F4,
+        F5,
+        F6,
+        F7,
+        F8,
+        F9,
+        F10,
+        F11,
+        F12,
+        Insert,
+        Home,
+        Delete,
+        End,
+        PageDown,
+        PageUp,
+        Left,
+        Up,
+        Right,
+        Down,
+        Back,
+        Return,
+        Space,
+        Tab,
+        Numpad0,
+        Numpad1,
+        Numpad2,
+        Numpad3,
+        Numpad4,
+        Numpad5,
+        Numpad6,
+        Numpad7,
+        Numpad8,
+        Numpad9,
+        NumpadAdd,
+        NumpadDivide,
+        NumpadDecimal,
+        NumpadEnter,
+        NumpadMultiply,
+        NumpadSubtract,
+        Apostrophe,
+        Backslash,
+        Comma,
+        Equals,
+        Grave,
+        Minus,
+        Period,
+        Semicolon,
+        Slash,
+    ]
+};
+
+/// Look up a key by the name of its VirtualKeyCode variant, e.g. "PageUp".
+pub fn key_from_name(name: &str) -> Option<VirtualKeyCode> {
+    NAMED_KEYS
+        .iter()
+        .find(|key| format!("{:?}", key) == name)
+        .copied()
+}
+
+/// The characters a key types, as (unshifted, shifted).
+const CHARACTER_KEYS: &[(VirtualKeyCode, char, char)] = {
+    use VirtualKeyCode::*;
+    &[
+        (Key1, '1', '!'),
+        (Key2, '2', '@'),
+        (Key3, '3', '#'),
+        (Key4, '4', '$'),
+        (Key5, '5', '%'),
+        (Key6, '6', '^'),
+        (Key7, '7', '&'),
+        (Key8, '8', '*'),
+        (Key9, '9', '('),
+        (Key0, '0', ')'),
+        (A, 'a', 'A'),
+        (B, 'b', 'B'),
+        (C, 'c', 'C'),
+        (D, 'd', 'D'),
+        (E, 'e', 'E'),
+        (F, 'f', 'F'),
+        (G, 'g', 'G'),
+        (H, 'h', 'H'),
+        (I, 'i', 'I'),
+        (J, 'j', 'J'),
+        (K, 'k', 'K'),
+        (L, 'l', 'L'),
+        (M, 'm', 'M'),
+        (N, 'n', 'N'),
+        (O, 'o', 'O'),
+        (P, 'p', 'P'),
+        (Q, 'q', 'Q'),
+        (R, 'r', 'R'),
+        (S, 's', 'S'),
+        (T, 't', 'T'),
+        (U, 'u', 'U'),
+        (V, 'v', 'V'),
+        (W, 'w', 'W'),
+        (X, 'x', 'X'),
+        (Y, 'y', 'Y'),
+        (Z, 'z', 'Z'),
+        (Apostrophe, '\'', '\''),
+        (Asterisk, '*', '*'),
+        (At, '@', '@'),
+        (Backslash, '\\', '\\'),
+        (Colon, ':', ':'),
+        (Comma, ',', ','),
+        (Equals, '=', '='),
+        (Grave, '`', '`'),
+        (Minus, '-', '-'),
+        (Period, '.', '.'),
+        (Plus, '+', '+'),
+        (Semicolon, ';', ';'),
+        (Slash, '/', '/'),
+        (Space, ' ', ' '),
+    ]
+};
+
+/// Get the character that a key types, if any.
+pub fn key_to_char(key: VirtualKeyCode, shift: bool) -> Option<char> {
+    CHARACTER_KEYS
+        .iter()
+        .find(|(k, _, _)| *k == key)
+        .map(|(_, ch, shifted)| if shift { *shifted } else { *ch })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_default_bindings() {
+        let keymap = KeyMap::default();
+        assert_eq!(keymap.commands(VirtualKeyCode::W), vec![Command::MoveUp]);
+        assert_eq!(
+            keymap.commands(VirtualKeyCode::Escape),
+            vec![Command::Menu, Command::Back]
+        );
+        assert_eq!(keymap.commands(VirtualKeyCode::F5), vec![]);
+    }
+
+    #[test]
+    fn test_from_json() {
+        let keymap = KeyMap::from_json(r#"{ "move_up": ["K"], "plant": ["Space"] }"#)
+            .expect("Failed to parse the key map.");
+
+        assert_eq!(keymap.keys(Command::MoveUp), &[VirtualKeyCode::K]);
+        assert_eq!(keymap.keys(Command::Plant), &[VirtualKeyCode::Space]);
+        assert_eq!(keymap.commands(VirtualKeyCode::W), vec![]);
+        // Commands missing from the config keep their defaults.
+        assert_eq!(
+            keymap.keys(Command::MoveDown),
+            KeyMap::default().keys(Command::MoveDown)
+        );
+    }
+
+    #[test]
+    fn test_from_json_invalid() {
+        KeyMap::from_json(r#"{ "move_up": ["NotAKey"] }"#).expect_err("Unknown key.");
+        KeyMap::from_json(r#"{ "fly": ["W"] }"#).expect_err("Unknown command.");
+    }
+
+    #[test]
+    fn test_key_to_char() {
+        assert_eq!(key_to_char(VirtualKeyCode::W, false), Some('w'));
+        assert_eq!(key_to_char(VirtualKeyCode::W, true), Some('W'));
+        assert_eq!(key_to_char(VirtualKeyCode::Key1, true), Some('!'));
+        assert_eq!(key_to_char(VirtualKeyCode::Left, false), None);
+    }
+}
@@ -1,7 +1,12 @@
+pub mod autosave;
+pub mod devtools;
+pub mod dialogs;
 pub mod drawable;
 pub mod game_state;
 pub mod garden;
+pub mod hud;
 pub mod input_device;
+pub mod keymap;
 pub mod player;
 pub mod primitives;
 pub mod ui;
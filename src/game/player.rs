@@ -36,6 +36,9 @@ impl drawable::Draw for Player {
 
 impl Entity for Player {
     fn position<'a>(&'a self, state: Rc<State>) -> Position {
-        selectors::get_player_position(state).expect("Failed to get the player position.")
+        let position = selectors::get_player_position(state.clone())
+            .expect("Failed to get the player position.");
+        // Entities are drawn relative to the camera.
+        position - selectors::get_camera(state)
     }
 }
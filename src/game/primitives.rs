@@ -76,6 +76,14 @@ impl<T: Number> std::ops::AddAssign for Vec2<T> {
     }
 }
 
+impl<T: Number> std::ops::Sub<Vec2<T>> for Vec2<T> {
+    type Output = Vec2<T>;
+
+    fn sub(self, other: Vec2<T>) -> Vec2<T> {
+        Vec2::new(self.x - other.x, self.y - other.y)
+    }
+}
+
 impl<T: Number> std::ops::Div<Vec2<T>> for Vec2<T> {
     type Output = Vec2<T>;
 
@@ -1,32 +1,40 @@
 use std::rc::Rc;
 
+use super::Widget;
 use crate::{
     game::{
         drawable,
         input_device::InputDevice,
+        keymap::Command,
         primitives::{BBox, Entity, Position, Size},
     },
     State,
 };
 use rltk::Rltk;
 
+/// A list of labeled values to choose from. The result is None if it was cancelled.
 #[derive(PartialEq, Debug, Clone)]
-pub struct Choices {
+pub struct Choices<V> {
     pub values: Vec<drawable::Text>,
+    pub items: Vec<V>,
     pub box_: drawable::Box,
     pub cursor: drawable::Glyph,
     pub bbox: BBox<i32>,
     pub cursor_index: i32,
+    result: Option<Option<V>>,
 }
 
-impl Choices {
-    pub fn new(mut values: Vec<String>) -> Self {
+impl<V: Clone + PartialEq> Choices<V> {
+    pub fn new(choices: Vec<(String, V)>) -> Self {
+        let (values, items): (Vec<String>, Vec<V>) = choices.into_iter().unzip();
         let mut width = 0;
         for value in &values {
-            width = width.max(value.len());
+            width = width.max(value.chars().count());
         }
         Self {
             cursor_index: 0,
+            items,
+            result: None,
             box_: drawable::Box {
                 line_type: drawable::LineType::Single,
                 fg: rltk::GRAY60.into(),
@@ -38,7 +46,7 @@ impl Choices {
             },
             // Convert into drawable::Text
             values: values
-                .drain(0..values.len())
+                .into_iter()
                 .map(|string| drawable::Text {
                     string,
                     fg: rltk::WHITE.into(),
@@ -53,31 +61,42 @@ impl Choices {
         }
     }
 
-    pub fn update(&mut self, input_device: &InputDevice) -> Option<String> {
+    /// Centers the text input in a given window size.
+    pub fn center(&mut self, w: i32, h: i32) {
+        self.bbox.top_left.x = (w - self.bbox.size.x) / 2;
+        self.bbox.top_left.y = (h - self.bbox.size.y) / 2;
+    }
+}
+
+impl<V: Clone + PartialEq> Widget for Choices<V> {
+    type Output = Option<V>;
+
+    fn update(&mut self, input_device: &InputDevice, _ctx: &Rltk) {
         let len = self.values.len() as i32;
         self.cursor_index =
-            ((self.cursor_index as i32 + input_device.move_intent.y) + len) % len;
-        if input_device.is_enter {
-            Some(
-                self.values
+            ((self.cursor_index + input_device.move_intent.y) + len) % len;
+        if input_device.is_command(Command::Confirm) {
+            self.result = Some(Some(
+                self.items
                     .get(self.cursor_index as usize)
                     .expect("Unable to get value.")
-                    .string
                     .clone(),
-            )
-        } else {
-            None
+            ));
+        } else if input_device.is_command(Command::Back) {
+            self.result = Some(None);
         }
     }
 
-    /// Centers the text input in a given window size.
-    pub fn center(&mut self, w: i32, h: i32) {
-        self.bbox.top_left.x = (w - self.bbox.size.x) / 2;
-        self.bbox.top_left.y = (h - self.bbox.size.y) / 2;
+    fn draw(&self, state: Rc<State>, ctx: &mut Rltk) {
+        drawable::Draw::draw(self, state, ctx, self);
+    }
+
+    fn result(&mut self) -> Option<Self::Output> {
+        self.result.take()
     }
 }
 
-impl drawable::Draw for Choices {
+impl<V: Clone + PartialEq> drawable::Draw for Choices<V> {
     fn draw<T: Entity>(&self, state: Rc<State>, ctx: &mut Rltk, _entity: &T) {
         self.box_.draw(state.clone(), ctx, self);
         for (i, value) in self.values.iter().enumerate() {
@@ -101,7 +120,7 @@ impl drawable::Draw for Choices {
     }
 }
 
-impl Entity for Choices {
+impl<V: Clone + PartialEq> Entity for Choices<V> {
     fn position<'a>(&'a self, _state: Rc<State>) -> Position {
         Position::new(
             self.bbox.top_left.x + (self.bbox.size.x / 2),
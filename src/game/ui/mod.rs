@@ -1,22 +1,31 @@
 mod choices;
+mod modal;
 mod text_input;
 
+use std::rc::Rc;
+
+use rltk::Rltk;
+
+use super::input_device::{InputDevice, InputMode};
+use crate::State;
+
 pub use choices::Choices;
-pub use text_input::TextInput;
+pub use modal::{Modal, ModalContext, ModalStack};
+pub use text_input::{TextInput, Validator};
 
-pub enum InputUI {
-    Choices(Choices),
-    TextInput(TextInput),
-}
+/// A piece of interactive UI. It is updated every frame until it has a result.
+pub trait Widget {
+    type Output;
 
-pub enum InputHandler {
-    NewGarden,
-    MainMenu,
-}
+    fn update(&mut self, input_device: &InputDevice, ctx: &Rltk);
+
+    fn draw(&self, state: Rc<State>, ctx: &mut Rltk);
+
+    /// Take the result once the widget is finished. This is only ever Some once.
+    fn result(&mut self) -> Option<Self::Output>;
 
-impl Default for InputHandler {
-    fn default() -> Self {
-        // Arbitrarily pick one.
-        InputHandler::NewGarden
+    /// How the keyboard should be interpreted while this widget has focus.
+    fn input_mode(&self) -> InputMode {
+        InputMode::Commands
     }
 }
@@ -0,0 +1,126 @@
+use std::rc::Rc;
+
+use rltk::Rltk;
+
+use super::Widget;
+use crate::{
+    game::{
+        hud::MessageLog,
+        input_device::{InputDevice, InputMode},
+    },
+    State, Store,
+};
+
+/// What a modal's result handler has access to. Handlers can push new modals, which
+/// is how dialogs nest, and report problems in the message log of the HUD.
+pub struct ModalContext<'a> {
+    pub store: &'a mut Store,
+    pub rltk: &'a mut Rltk,
+    pub modals: &'a mut ModalStack,
+    pub log: &'a mut MessageLog,
+}
+
+/// A type-erased widget, along with what to do with its result.
+pub trait Modal {
+    fn input_mode(&self) -> InputMode;
+
+    /// Returns true once the modal is finished and can be removed.
+    fn update(&mut self, input_device: &InputDevice, cx: &mut ModalContext) -> bool;
+
+    fn draw(&self, state: Rc<State>, ctx: &mut Rltk);
+}
+
+type ResultHandler<T> = Box<dyn FnOnce(T, &mut ModalContext)>;
+
+struct WidgetModal<W: Widget> {
+    widget: W,
+    on_result: Option<ResultHandler<W::Output>>,
+}
+
+impl<W: Widget> Modal for WidgetModal<W> {
+    fn input_mode(&self) -> InputMode {
+        self.widget.input_mode()
+    }
+
+    fn update(&mut self, input_device: &InputDevice, cx: &mut ModalContext) -> bool {
+        self.widget.update(input_device, cx.rltk);
+        match self.widget.result() {
+            Some(output) => {
+                let on_result = self
+                    .on_result
+                    .take()
+                    .expect("Logic error, a modal produced more than one result.");
+                on_result(output, cx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn draw(&self, state: Rc<State>, ctx: &mut Rltk) {
+        self.widget.draw(state, ctx);
+    }
+}
+
+/// The open modals. Only the top-most one receives input, but they are all drawn.
+#[derive(Default)]
+pub struct ModalStack {
+    modals: Vec<Box<dyn Modal>>,
+}
+
+impl ModalStack {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn push<W: Widget + 'static>(
+        &mut self,
+        widget: W,
+        on_result: impl FnOnce(W::Output, &mut ModalContext) + 'static,
+    ) {
+        self.modals.push(Box::new(WidgetModal {
+            widget,
+            on_result: Some(Box::new(on_result)),
+        }));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.modals.is_empty()
+    }
+
+    pub fn input_mode(&self) -> InputMode {
+        match self.modals.last() {
+            Some(modal) => modal.input_mode(),
+            None => InputMode::Commands,
+        }
+    }
+
+    /// Update the top-most modal.
+    pub fn update(
+        &mut self,
+        input_device: &InputDevice,
+        store: &mut Store,
+        rltk: &mut Rltk,
+        log: &mut MessageLog,
+    ) {
+        // Take the modal off of the stack while it runs, so that its result handler
+        // is free to push new modals.
+        if let Some(mut modal) = self.modals.pop() {
+            let mut cx = ModalContext {
+                store,
+                rltk,
+                modals: self,
+                log,
+            };
+            if !modal.update(input_device, &mut cx) {
+                self.modals.push(modal);
+            }
+        }
+    }
+
+    pub fn draw(&self, state: Rc<State>, ctx: &mut Rltk) {
+        for modal in &self.modals {
+            modal.draw(state.clone(), ctx);
+        }
+    }
+}
@@ -1,30 +1,66 @@
-use std::rc::Rc;
+use std::{fmt, rc::Rc};
 
-use rltk::Rltk;
+use rltk::{Rltk, RGB};
 
+use super::Widget;
 use crate::{
     game::{
         drawable,
-        input_device::InputDevice,
+        input_device::{InputDevice, InputMode, TextEdit},
+        keymap::Command,
         primitives::{BBox, Entity, Position, Size},
     },
     State,
 };
 
+/// Checks the text before it is submitted. An error message is shown inline and the
+/// input stays open.
+#[derive(Clone)]
+pub struct Validator(Rc<ValidateFn>);
+
+type ValidateFn = dyn Fn(&str) -> Result<(), String>;
+
+impl Validator {
+    pub fn new(validate: impl Fn(&str) -> Result<(), String> + 'static) -> Self {
+        Self(Rc::new(validate))
+    }
+}
+
+impl PartialEq for Validator {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl fmt::Debug for Validator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Validator")
+    }
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct TextInput {
     pub text: drawable::Text,
     pub box_: drawable::Box,
     pub cursor: drawable::Glyph,
     pub bbox: BBox<i32>,
+    /// The maximum number of characters, which is also the visible width.
     pub max_width: i32,
     pub blink_time: f32,
+    /// The cursor position, counted in characters rather than bytes.
+    pub cursor_index: usize,
+    pub validator: Option<Validator>,
+    pub error: Option<drawable::Text>,
+    /// The inner value is None when the input was cancelled, which is distinct from
+    /// submitting an empty string.
+    result: Option<Option<String>>,
 }
 
 const BLINK: f32 = 1000.0;
 
 impl TextInput {
     pub fn new(string: String, max_width: i32) -> Self {
+        let cursor_index = string.chars().count();
         Self {
             max_width,
             box_: drawable::Box {
@@ -48,33 +84,140 @@ impl TextInput {
                 bg: rltk::BLACK.into(),
             },
             blink_time: 0.0,
+            cursor_index,
+            validator: None,
+            error: None,
+            result: None,
         }
     }
 
+    pub fn with_validator(
+        mut self,
+        validate: impl Fn(&str) -> Result<(), String> + 'static,
+    ) -> Self {
+        self.validator = Some(Validator::new(validate));
+        self
+    }
+
     /// Centers the text input in a given window size.
     pub fn center(&mut self, w: i32, h: i32) {
         self.bbox.top_left.x = (w - self.bbox.size.x) / 2;
         self.bbox.top_left.y = (h - self.bbox.size.y) / 2;
     }
 
-    pub fn update(&mut self, input_device: &InputDevice, ctx: &Rltk) -> Option<String> {
-        if let Some(letter) = input_device.letter {
-            if self.text.string.len() < self.max_width as usize {
-                self.text.string.push(letter);
+    fn len(&self) -> usize {
+        self.text.string.chars().count()
+    }
+
+    /// Convert the character-based cursor into a byte offset into the string.
+    fn byte_index(&self, char_index: usize) -> usize {
+        self.text
+            .string
+            .char_indices()
+            .nth(char_index)
+            .map(|(index, _)| index)
+            .unwrap_or(self.text.string.len())
+    }
+
+    fn insert(&mut self, ch: char) {
+        if ch.is_control() || self.len() >= self.max_width as usize {
+            return;
+        }
+        let index = self.byte_index(self.cursor_index);
+        self.text.string.insert(index, ch);
+        self.cursor_index += 1;
+    }
+
+    /// Insert text at the cursor, dropping anything that doesn't fit.
+    pub fn paste(&mut self, text: &str) {
+        for ch in text.chars() {
+            self.insert(ch);
+        }
+    }
+
+    fn edit(&mut self, text_edit: TextEdit) {
+        match text_edit {
+            TextEdit::Backspace => {
+                if self.cursor_index > 0 {
+                    self.cursor_index -= 1;
+                    let index = self.byte_index(self.cursor_index);
+                    self.text.string.remove(index);
+                }
             }
+            TextEdit::Delete => {
+                if self.cursor_index < self.len() {
+                    let index = self.byte_index(self.cursor_index);
+                    self.text.string.remove(index);
+                }
+            }
+            TextEdit::Left => self.cursor_index = self.cursor_index.saturating_sub(1),
+            TextEdit::Right => {
+                self.cursor_index = (self.cursor_index + 1).min(self.len())
+            }
+            TextEdit::Home => self.cursor_index = 0,
+            TextEdit::End => self.cursor_index = self.len(),
         }
+    }
 
-        self.blink_time = (self.blink_time + ctx.frame_time_ms) % BLINK;
+    /// The Rltk-independent part of `Widget::update`.
+    pub fn apply_input(&mut self, input_device: &InputDevice) {
+        if let Some(letter) = input_device.letter {
+            self.insert(letter);
+            self.error = None;
+        }
+        if let Some(text_edit) = input_device.text_edit {
+            self.edit(text_edit);
+            self.error = None;
+        }
 
-        if input_device.is_backspace {
-            self.text.string.pop();
+        if input_device.is_command(Command::Copy) {
+            *input_device.clipboard.borrow_mut() = self.text.string.clone();
+        }
+        if input_device.is_command(Command::Paste) {
+            self.paste(&input_device.clipboard.borrow());
+            self.error = None;
         }
 
-        if input_device.is_enter {
-            Some(self.text.string.clone())
-        } else {
-            None
+        if input_device.is_command(Command::Back) {
+            self.result = Some(None);
+            return;
         }
+
+        if input_device.is_command(Command::Confirm) {
+            if let Some(Validator(ref validate)) = self.validator {
+                if let Err(message) = validate(&self.text.string) {
+                    self.error = Some(drawable::Text {
+                        // Keep the message inside of the box.
+                        string: message.chars().take(self.max_width as usize).collect(),
+                        fg: RGB::named(rltk::RED),
+                        bg: RGB::named(rltk::BLACK),
+                    });
+                    return;
+                }
+            }
+            self.result = Some(Some(self.text.string.clone()));
+        }
+    }
+}
+
+impl Widget for TextInput {
+    type Output = Option<String>;
+
+    fn update(&mut self, input_device: &InputDevice, ctx: &Rltk) {
+        self.blink_time = (self.blink_time + ctx.frame_time_ms) % BLINK;
+        self.apply_input(input_device);
+    }
+
+    fn draw(&self, state: Rc<State>, ctx: &mut Rltk) {
+        drawable::Draw::draw(self, state, ctx, self);
+    }
+
+    fn result(&mut self) -> Option<Self::Output> {
+        self.result.take()
+    }
+
+    fn input_mode(&self) -> InputMode {
+        InputMode::Text
     }
 }
 
@@ -86,12 +229,19 @@ impl drawable::Draw for TextInput {
             ctx,
             &Position::new(self.bbox.top_left.x + 2, self.bbox.top_left.y + 2),
         );
+        if let Some(ref error) = self.error {
+            error.draw(
+                state.clone(),
+                ctx,
+                &Position::new(self.bbox.top_left.x + 2, self.bbox.top_left.y + 3),
+            );
+        }
         if self.blink_time < BLINK / 2.0 {
             self.cursor.draw(
                 state,
                 ctx,
                 &Position::new(
-                    self.bbox.top_left.x + 2 + self.text.string.len() as i32,
+                    self.bbox.top_left.x + 2 + self.cursor_index as i32,
                     self.bbox.top_left.y + 2,
                 ),
             );
@@ -111,3 +261,112 @@ impl Entity for TextInput {
         self.bbox.clone()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game::keymap::KeyMap;
+    use rltk::VirtualKeyCode;
+
+    fn press(text_input: &mut TextInput, key: VirtualKeyCode) -> Option<Option<String>> {
+        let mut input_device = InputDevice::new(KeyMap::default());
+        input_device.mode = InputMode::Text;
+        input_device.update_key(Some(key), false);
+        text_input.apply_input(&input_device);
+        text_input.result()
+    }
+
+    #[test]
+    fn test_cursor_editing() {
+        let mut text_input = TextInput::new("ac".into(), 10);
+        press(&mut text_input, VirtualKeyCode::Left);
+        press(&mut text_input, VirtualKeyCode::B);
+        assert_eq!(text_input.text.string, "abc");
+
+        press(&mut text_input, VirtualKeyCode::Home);
+        press(&mut text_input, VirtualKeyCode::Delete);
+        assert_eq!(text_input.text.string, "bc");
+
+        press(&mut text_input, VirtualKeyCode::End);
+        press(&mut text_input, VirtualKeyCode::Back);
+        assert_eq!(text_input.text.string, "b");
+        assert_eq!(text_input.cursor_index, 1);
+    }
+
+    #[test]
+    fn test_unicode() {
+        let mut text_input = TextInput::new("".into(), 4);
+        text_input.paste("ñandú!");
+        assert_eq!(
+            text_input.text.string, "ñand",
+            "The width is counted in chars."
+        );
+
+        press(&mut text_input, VirtualKeyCode::Left);
+        press(&mut text_input, VirtualKeyCode::Left);
+        press(&mut text_input, VirtualKeyCode::Back);
+        assert_eq!(text_input.text.string, "ñnd");
+
+        // The console draws each char in its own cell, even wide and combining ones.
+        let mut wide = TextInput::new("".into(), 3);
+        wide.paste("庭e\u{301}x");
+        assert_eq!(wide.text.string, "庭e\u{301}");
+    }
+
+    #[test]
+    fn test_cancel_and_submit() {
+        let mut text_input = TextInput::new("".into(), 10);
+        assert_eq!(
+            press(&mut text_input, VirtualKeyCode::Return),
+            Some(Some("".into())),
+            "An empty submission."
+        );
+        assert_eq!(
+            press(&mut text_input, VirtualKeyCode::Escape),
+            Some(None),
+            "Cancelled."
+        );
+    }
+
+    #[test]
+    fn test_validation() {
+        let mut text_input = TextInput::new("".into(), 10).with_validator(|text| {
+            if text.is_empty() {
+                Err("Empty".into())
+            } else {
+                Ok(())
+            }
+        });
+        assert_eq!(press(&mut text_input, VirtualKeyCode::Return), None);
+        assert_eq!(
+            text_input.error.as_ref().map(|e| e.string.as_str()),
+            Some("Empty")
+        );
+
+        press(&mut text_input, VirtualKeyCode::A);
+        assert_eq!(
+            press(&mut text_input, VirtualKeyCode::Return),
+            Some(Some("a".into()))
+        );
+    }
+
+    #[test]
+    fn test_copy_and_paste() {
+        let mut input_device = InputDevice::new(KeyMap::default());
+        input_device.mode = InputMode::Text;
+        let mut copy_from = TextInput::new("ñandú".into(), 10);
+        input_device.update_key(Some(VirtualKeyCode::F3), false);
+        copy_from.apply_input(&input_device);
+        assert_eq!(*input_device.clipboard.borrow(), "ñandú");
+
+        let mut paste_into = TextInput::new("ac".into(), 5);
+        paste_into.cursor_index = 1;
+        input_device.update_key(Some(VirtualKeyCode::Insert), false);
+        paste_into.apply_input(&input_device);
+        assert_eq!(
+            paste_into.text.string, "añanc",
+            "The paste is inserted at the cursor, and cut off at the max width."
+        );
+        assert_eq!(paste_into.cursor_index, 4);
+    }
+}
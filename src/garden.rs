@@ -23,7 +23,25 @@ impl SerializedBytes for GardenPlot {
     }
 }
 
+/// The longest name a garden plot can have, in characters.
+pub const MAX_NAME_LEN: usize = 30;
+
 impl GardenPlot {
+    /// Check that a name is usable for a garden plot, returning a message for the
+    /// user if it is not.
+    pub fn validate_name(name: &str) -> Result<(), String> {
+        if name.trim().is_empty() {
+            return Err("Enter a name.".into());
+        }
+        if name.chars().count() > MAX_NAME_LEN {
+            return Err(format!(
+                "The name must be {} letters or less.",
+                MAX_NAME_LEN
+            ));
+        }
+        Ok(())
+    }
+
     pub fn new(name: String) -> Self {
         Self {
             uuid: Uuid::new_v4(),
@@ -0,0 +1,1047 @@
+//! The .garden directory that the `garden` command works in. Like git, it is found by
+//! walking up from the working directory, so the command can be run from anywhere
+//! inside a project.
+
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use libp2p::identity::Keypair;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    block_chain::{verify_blocks, Block, BlockData, ReconcileError},
+    bundle::{Bundle, BundleError},
+    chain_store::{check_block, ChainStoreError, FsChainStore, HeadRef, LoadError},
+    peers::{self, AddressBook, PeersError, IDENTITY_FILE_NAME, PEERS_FILE_NAME},
+    store::RemoteChanges,
+    world::WorldMetadata,
+    ChainAction, ChainStore, Hash,
+};
+
+/// The name of the directory that holds a garden.
+pub const GARDEN_DIR_NAME: &str = ".garden";
+
+/// The name of the config file in the .garden directory.
+pub const CONFIG_FILE_NAME: &str = "config.json";
+
+/// The head that is used when no other one is given.
+pub const DEFAULT_HEAD: &str = "my-garden";
+
+#[derive(Error, Debug)]
+pub enum GardenDirError {
+    #[error("no {GARDEN_DIR_NAME} directory was found in {} or any of its parents", .0.display())]
+    NotFound(PathBuf),
+    #[error("the garden directory {} does not exist", .0.display())]
+    Missing(PathBuf),
+    #[error("a garden already exists at {}", .0.display())]
+    AlreadyExists(PathBuf),
+    #[error("the reference {0:?} does not exist")]
+    UnknownHead(String),
+    #[error("the head {head:?} could not be loaded, so nothing was collected")]
+    UnreadableHead {
+        head: String,
+        #[source]
+        source: LoadError,
+    },
+    #[error("failed to remove {}", .path.display())]
+    Remove {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("{0:?} is not a remote-tracking ref, e.g. remotes/<peer>/<head>")]
+    NotARemote(String),
+    #[error("the fetched blocks do not form a chain")]
+    MalformedBlocks,
+    #[error("the fetched blocks start from {0}, which is not in the garden")]
+    MissingBase(Hash),
+    #[error("the fetched blocks could not be recorded in {head:?}")]
+    Fetch {
+        head: String,
+        #[source]
+        source: ReconcileError,
+    },
+    #[error("{from:?} could not be merged into {into:?}")]
+    Merge {
+        from: String,
+        into: String,
+        #[source]
+        source: ReconcileError,
+    },
+    #[error("the genesis block {0} from the config is not in the garden")]
+    MissingGenesis(Hash),
+    #[error("failed to write the config at {}", .path.display())]
+    WriteConfig {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to read the config at {}", .path.display())]
+    ReadConfig {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("the config at {} is malformed", .path.display())]
+    MalformedConfig {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+    #[error(transparent)]
+    Peers(#[from] PeersError),
+    #[error(transparent)]
+    ChainStore(#[from] ChainStoreError),
+    #[error(transparent)]
+    Load(#[from] LoadError),
+    #[error(transparent)]
+    Bundle(#[from] BundleError),
+}
+
+/// The settings of a garden, which are stored in .garden/config.json.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GardenConfig {
+    /// The schema version the garden was created with, see `world::SCHEMA_VERSION`.
+    pub schema_version: u32,
+    /// The hash of the genesis block, which every chain of the garden starts from.
+    pub genesis: Hash,
+}
+
+/// The result of `GardenDir::fetch`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchResult {
+    /// What the remote-tracking ref pointed to before the fetch.
+    pub previous: Option<Hash>,
+    /// What it points to now.
+    pub tip: Option<Hash>,
+}
+
+impl FetchResult {
+    pub fn is_up_to_date(&self) -> bool {
+        self.previous == self.tip
+    }
+}
+
+/// The result of checking a single head with `GardenDir::fsck`.
+#[derive(Debug)]
+pub struct HeadCheck {
+    pub head_ref: HeadRef,
+    /// The number of blocks in the chain, or why it is broken.
+    pub result: Result<usize, LoadError>,
+    /// The hash of the first block of the chain, if it loaded and has any.
+    pub root: Option<Hash>,
+}
+
+#[derive(Debug)]
+pub struct FsckReport {
+    pub heads: Vec<HeadCheck>,
+    /// Chunks that no head leads to, which `GardenDir::gc` would remove. This is None
+    /// when a head is broken, as its chunks can't be told apart.
+    pub unreachable: Option<Vec<Hash>>,
+}
+
+impl FsckReport {
+    pub fn is_ok(&self) -> bool {
+        self.heads.iter().all(|head| head.result.is_ok())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GardenDir {
+    /// The path to the .garden directory itself.
+    pub path: PathBuf,
+}
+
+impl GardenDir {
+    /// Find the .garden directory in the start directory, or the closest parent that
+    /// has one.
+    pub fn discover(start: &Path) -> Result<Self, GardenDirError> {
+        for dir in start.ancestors() {
+            let path = dir.join(GARDEN_DIR_NAME);
+            if path.is_dir() {
+                return Ok(Self { path });
+            }
+        }
+        Err(GardenDirError::NotFound(start.to_path_buf()))
+    }
+
+    /// Use a .garden directory that is known up front, e.g. from --garden-dir.
+    pub fn open(path: PathBuf) -> Result<Self, GardenDirError> {
+        if !path.is_dir() {
+            return Err(GardenDirError::Missing(path));
+        }
+        Ok(Self { path })
+    }
+
+    /// Create a new .garden directory, with a genesis block for the world on the
+    /// default head, and a config that points to it.
+    pub fn init(path: PathBuf, world: WorldMetadata) -> Result<Self, GardenDirError> {
+        if path.exists() {
+            return Err(GardenDirError::AlreadyExists(path));
+        }
+        let garden_dir = Self { path };
+        let config = GardenConfig {
+            schema_version: world.schema_version,
+            genesis: {
+                // The chain store creates the directory layout.
+                let mut chain_store =
+                    garden_dir.chain_store(HeadRef::try_from(DEFAULT_HEAD)?)?;
+                let hash = chain_store.add(ChainAction::Genesis(world)).hash.clone();
+                chain_store.persist()?;
+                hash
+            },
+        };
+        garden_dir.write_config(&config)?;
+        Ok(garden_dir)
+    }
+
+    pub fn config_path(&self) -> PathBuf {
+        self.path.join(CONFIG_FILE_NAME)
+    }
+
+    /// Read the config. Gardens from before `init` existed don't have one.
+    pub fn config(&self) -> Result<Option<GardenConfig>, GardenDirError> {
+        let path = self.config_path();
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(source) => return Err(GardenDirError::ReadConfig { path, source }),
+        };
+        serde_json::from_str(&text)
+            .map(Some)
+            .map_err(|source| GardenDirError::MalformedConfig { path, source })
+    }
+
+    pub fn write_config(&self, config: &GardenConfig) -> Result<(), GardenDirError> {
+        let path = self.config_path();
+        let text = serde_json::to_string_pretty(config)
+            .expect("Unable to serialize the config.");
+        fs::write(&path, text)
+            .map_err(|source| GardenDirError::WriteConfig { path, source })
+    }
+
+    pub fn identity_path(&self) -> PathBuf {
+        self.path.join(IDENTITY_FILE_NAME)
+    }
+
+    /// The keypair the garden is known by on the network. Gardens from before it was
+    /// kept get one the first time it is needed.
+    pub fn identity(&self) -> Result<Keypair, GardenDirError> {
+        Ok(peers::load_or_create_identity(&self.identity_path())?)
+    }
+
+    pub fn peers_path(&self) -> PathBuf {
+        self.path.join(PEERS_FILE_NAME)
+    }
+
+    pub fn address_book(&self) -> Result<AddressBook, GardenDirError> {
+        Ok(AddressBook::read(&self.peers_path())?)
+    }
+
+    pub fn write_address_book(
+        &self,
+        address_book: &AddressBook,
+    ) -> Result<(), GardenDirError> {
+        Ok(address_book.write(&self.peers_path())?)
+    }
+
+    /// A chain store for the head. The head doesn't need to exist yet, in which case
+    /// its chain starts from the genesis block of the garden, so that every local
+    /// head is in the same world. The head is written once the chain is persisted.
+    pub fn chain_store<T: BlockData>(
+        &self,
+        head_ref: HeadRef,
+    ) -> Result<FsChainStore<T>, GardenDirError> {
+        let mut chain_store = FsChainStore::try_new(self.path.clone(), head_ref)?;
+        let is_new = !chain_store.head_path(&chain_store.head_ref).exists();
+        // Remote-tracking refs mirror a peer, see `fetch`.
+        if is_new && !chain_store.head_ref.is_remote() {
+            if let Some(config) = self.config()? {
+                let genesis = chain_store
+                    .get_block(&config.genesis)?
+                    .ok_or(GardenDirError::MissingGenesis(config.genesis))?;
+                // The genesis block was persisted by `init`, so it isn't unsaved.
+                chain_store.start_from(vec![genesis]);
+            }
+        }
+        Ok(chain_store)
+    }
+
+    /// A chain store for a head that has already been persisted.
+    pub fn existing_chain_store<T: BlockData>(
+        &self,
+        head_ref: HeadRef,
+    ) -> Result<FsChainStore<T>, GardenDirError> {
+        let chain_store = self.chain_store(head_ref)?;
+        if !chain_store.head_path(&chain_store.head_ref).exists() {
+            return Err(GardenDirError::UnknownHead(
+                chain_store.head_ref.str().into(),
+            ));
+        }
+        Ok(chain_store)
+    }
+
+    /// A garden that nothing has happened in yet only has the genesis block of its own
+    /// world, which the chain of another garden can never be reconciled with. When a
+    /// whole chain from another world arrives for such a garden, e.g. in a bundle or
+    /// from a peer, the garden takes on that world instead: the chain replaces the
+    /// one of the chain store and is persisted, the config points to its root, and
+    /// the other local heads, which only had the old genesis block, are removed.
+    /// Returns how many blocks were added, or None if the garden keeps its world.
+    pub fn adopt_world<T: BlockData>(
+        &self,
+        chain_store: &mut FsChainStore<T>,
+        blocks: &[Block<T>],
+    ) -> Result<Option<usize>, GardenDirError> {
+        let mut config = match self.config()? {
+            Some(config) => config,
+            None => return Ok(None),
+        };
+        match blocks.first() {
+            Some(root)
+                if root.payload.parent.is_root() && root.hash != config.genesis => {}
+            _ => return Ok(None),
+        }
+        if !verify_blocks(blocks, Hash::empty()) {
+            return Ok(None);
+        }
+        let only_genesis =
+            |chain_store: &mut FsChainStore<T>| -> Result<bool, LoadError> {
+                let mut chain = chain_store.iter_all()?;
+                Ok(
+                    chain.next().map(|block| &block.hash) == Some(&config.genesis)
+                        && chain.next().is_none(),
+                )
+            };
+        if !only_genesis(chain_store)? {
+            return Ok(None);
+        }
+        let mut other_heads = vec![];
+        for head_ref in self.head_refs()? {
+            if head_ref.is_remote() || head_ref == chain_store.head_ref {
+                continue;
+            }
+            let mut other = FsChainStore::<T>::try_new(self.path.clone(), head_ref)?;
+            if !only_genesis(&mut other)? {
+                return Ok(None);
+            }
+            other_heads.push(other.head_path(&other.head_ref));
+        }
+
+        chain_store.start_from(vec![]);
+        chain_store
+            .reconcile(blocks)
+            .expect("The blocks were verified to start an empty chain.");
+        chain_store.persist()?;
+        config.genesis = blocks[0].hash.clone();
+        self.write_config(&config)?;
+        for path in other_heads {
+            fs::remove_file(&path)
+                .map_err(|source| GardenDirError::Remove { path, source })?;
+        }
+        Ok(Some(blocks.len()))
+    }
+
+    /// Import a bundle into the head and persist it. A bundle of a whole chain can
+    /// take a garden that nothing has happened in yet into its world, see
+    /// `adopt_world`. Returns how many blocks were added.
+    pub fn import_bundle<T: BlockData>(
+        &self,
+        head_ref: HeadRef,
+        bundle: &Bundle<T>,
+    ) -> Result<usize, GardenDirError> {
+        let mut chain_store = self.chain_store(head_ref)?;
+        if bundle.base.is_root() {
+            if let Some(added) = self.adopt_world(&mut chain_store, &bundle.blocks)? {
+                return Ok(added);
+            }
+        }
+        Ok(bundle.import(&mut chain_store)?)
+    }
+
+    /// The refs of every persisted head, sorted by name.
+    pub fn head_refs(&self) -> Result<Vec<HeadRef>, ChainStoreError> {
+        Ok(self.head_refs_and_strays()?.0)
+    }
+
+    /// The refs of every persisted head, and the stray files in the heads directory,
+    /// see `FsChainStore::head_refs_and_strays`.
+    pub fn head_refs_and_strays(
+        &self,
+    ) -> Result<(Vec<HeadRef>, Vec<ChainStoreError>), ChainStoreError> {
+        FsChainStore::<String>::try_new(
+            self.path.clone(),
+            HeadRef::try_from(DEFAULT_HEAD)?,
+        )?
+        .head_refs_and_strays()
+    }
+
+    /// The remote-tracking refs, i.e. remotes/<peer>/<head>, sorted by name. When a
+    /// peer is given, only its refs are listed.
+    pub fn remote_head_refs(
+        &self,
+        peer: Option<&str>,
+    ) -> Result<Vec<HeadRef>, ChainStoreError> {
+        Ok(self
+            .head_refs()?
+            .into_iter()
+            .filter(|head_ref| match (head_ref.remote_parts(), peer) {
+                (Some((ref_peer, _)), Some(peer)) => ref_peer == peer,
+                (Some(_), None) => true,
+                (None, _) => false,
+            })
+            .collect())
+    }
+
+    /// Record what a peer advertised for one of its heads in a remote-tracking ref,
+    /// without touching any local head. The blocks can be the whole chain, or only the
+    /// newest blocks, as long as the block they start from is already in the garden.
+    /// The ref is replaced, rather than reconciled, as it mirrors the peer, even when
+    /// the peer's chain got shorter.
+    pub fn fetch<T: BlockData>(
+        &self,
+        remote_ref: &HeadRef,
+        blocks: &[Block<T>],
+    ) -> Result<FetchResult, GardenDirError> {
+        if !remote_ref.is_remote() {
+            return Err(GardenDirError::NotARemote(remote_ref.str().into()));
+        }
+        let mut chain_store = self.chain_store::<T>(remote_ref.clone())?;
+        let previous = chain_store.head_hash()?;
+        let tip = match blocks.last() {
+            Some(block) => block.hash.clone(),
+            None => {
+                return Ok(FetchResult {
+                    tip: previous.clone(),
+                    previous,
+                })
+            }
+        };
+        if previous.as_ref() == Some(&tip) {
+            return Ok(FetchResult {
+                previous,
+                tip: Some(tip),
+            });
+        }
+
+        let base = blocks[0].payload.parent.clone();
+        if !verify_blocks(blocks, base.clone()) {
+            return Err(GardenDirError::MalformedBlocks);
+        }
+        let mut chain = if base.is_root() {
+            vec![]
+        } else {
+            chain_store
+                .blocks_to(&base)?
+                .ok_or(GardenDirError::MissingBase(base))?
+        };
+        chain.extend(blocks.iter().cloned());
+
+        // A store that hasn't loaded the old chain writes the new one as is. The head
+        // is only replaced once the blocks are persisted, see `write_head`.
+        let mut chain_store = self.chain_store::<T>(remote_ref.clone())?;
+        chain_store
+            .reconcile(&chain)
+            .map_err(|source| GardenDirError::Fetch {
+                head: remote_ref.str().into(),
+                source,
+            })?;
+        chain_store.persist()?;
+        Ok(FetchResult {
+            previous,
+            tip: Some(tip),
+        })
+    }
+
+    /// Reconcile the chain of one head into another, e.g. a fetched remote head into a
+    /// local one. The longer chain wins, as with blocks from the network, so this
+    /// fails when the chain being merged is behind. The head being merged into is
+    /// created when it doesn't exist yet, and a garden that nothing has happened in
+    /// yet takes on the world of the chain, see `adopt_world`.
+    pub fn merge<T: BlockData>(
+        &self,
+        from: &HeadRef,
+        into: &HeadRef,
+    ) -> Result<RemoteChanges, GardenDirError> {
+        let mut from_store = self.existing_chain_store::<T>(from.clone())?;
+        let blocks: Vec<Block<T>> = from_store.iter_all()?.cloned().collect();
+
+        let mut into_store = self.chain_store::<T>(into.clone())?;
+        let len = into_store.iter_all()?.count();
+        if let Some(added) = self.adopt_world(&mut into_store, &blocks)? {
+            return Ok(RemoteChanges {
+                fork_index: 0,
+                removed: len,
+                added,
+            });
+        }
+        let fork_index =
+            into_store
+                .reconcile(&blocks)
+                .map_err(|source| GardenDirError::Merge {
+                    from: from.str().into(),
+                    into: into.str().into(),
+                    source,
+                })?;
+        let changes = RemoteChanges {
+            fork_index,
+            removed: len - fork_index,
+            added: into_store.loaded_block_count() - fork_index,
+        };
+        into_store.persist()?;
+        Ok(changes)
+    }
+
+    /// Check that every head loads, and that its blocks are intact and linked from the
+    /// root.
+    pub fn fsck<T: BlockData>(&self) -> Result<FsckReport, GardenDirError> {
+        let mut heads = vec![];
+        for head_ref in self.head_refs()? {
+            let mut chain_store = self.chain_store::<T>(head_ref.clone())?;
+            let result = check_chain(&mut chain_store);
+            heads.push(HeadCheck {
+                head_ref,
+                root: match result {
+                    Ok(_) => chain_store
+                        .chain
+                        .blocks
+                        .front()
+                        .map(|block| block.hash.clone()),
+                    Err(_) => None,
+                },
+                result,
+            });
+        }
+        let unreachable = match self.unreachable_chunks::<T>() {
+            Err(GardenDirError::UnreadableHead { .. }) => None,
+            unreachable => Some(unreachable?),
+        };
+        Ok(FsckReport { heads, unreachable })
+    }
+
+    /// Remove the chunks that no head leads to, e.g. the old blocks of a chain that
+    /// was replaced by a longer one, and rebuild the block index. When dry_run is set,
+    /// the chunks are only listed.
+    pub fn gc<T: BlockData>(&self, dry_run: bool) -> Result<Vec<Hash>, GardenDirError> {
+        let unreachable = self.unreachable_chunks::<T>()?;
+        if dry_run {
+            return Ok(unreachable);
+        }
+        let mut chain_store = self.chain_store::<T>(HeadRef::try_from(DEFAULT_HEAD)?)?;
+        for hash in &unreachable {
+            let path = chain_store.chunk_path(hash);
+            fs::remove_file(&path).map_err(|source| GardenDirError::Remove {
+                path: path.clone(),
+                source,
+            })?;
+            // Tidy up the prefix directory once it is empty, this fails otherwise.
+            if let Some(prefix_path) = path.parent() {
+                fs::remove_dir(prefix_path).ok();
+            }
+        }
+        // The index still has the blocks of the removed chunks.
+        chain_store.rebuild_index()?;
+        chain_store.write_index()?;
+        Ok(unreachable)
+    }
+
+    /// The chunks that can't be reached from any head, sorted by hash. A head that
+    /// fails to load is an error, since its chunks can't be told apart.
+    fn unreachable_chunks<T: BlockData>(&self) -> Result<Vec<Hash>, GardenDirError> {
+        let mut reachable = HashSet::new();
+        for head_ref in self.head_refs()? {
+            let chain_store = self.chain_store::<T>(head_ref.clone())?;
+            let chunks = chain_store.reachable_chunks().map_err(|source| {
+                GardenDirError::UnreadableHead {
+                    head: head_ref.str().into(),
+                    source,
+                }
+            })?;
+            reachable.extend(chunks);
+        }
+        let mut chain_store = self.chain_store::<T>(HeadRef::try_from(DEFAULT_HEAD)?)?;
+        let mut unreachable: Vec<Hash> = chain_store
+            .get_known_hashes()?
+            .into_iter()
+            .filter(|hash| !reachable.contains(hash))
+            .collect();
+        unreachable.sort_by_cached_key(|hash| String::from(hash));
+        Ok(unreachable)
+    }
+}
+
+fn check_chain<T: BlockData>(
+    chain_store: &mut FsChainStore<T>,
+) -> Result<usize, LoadError> {
+    let mut prev_hash = Hash::empty();
+    let mut count = 0;
+    for (index, block) in chain_store.iter_all()?.enumerate() {
+        check_block(index, &prev_hash, block)?;
+        prev_hash = block.hash.clone();
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        block_chain::BlockChain, game::primitives::Position, utils::TimeStampScope,
+    };
+    use tempdir::TempDir;
+
+    fn world() -> WorldMetadata {
+        WorldMetadata::new("The Land".into(), "creator".into())
+    }
+
+    fn get_store(garden_dir: &GardenDir, head: &'static str) -> FsChainStore<String> {
+        garden_dir
+            .chain_store(HeadRef::try_from(head).unwrap())
+            .expect("Failed to create the chain store.")
+    }
+
+    #[test]
+    fn test_discover() {
+        let dir = TempDir::new("garden-dir").unwrap();
+        let nested = dir.path().join("a").join("b");
+        fs::create_dir_all(&nested).unwrap();
+        assert!(matches!(
+            GardenDir::discover(&nested),
+            Err(GardenDirError::NotFound(_))
+        ));
+
+        let garden_dir =
+            GardenDir::init(dir.path().join(GARDEN_DIR_NAME), world()).unwrap();
+        assert_eq!(GardenDir::discover(&nested).unwrap(), garden_dir);
+        assert_eq!(GardenDir::discover(dir.path()).unwrap(), garden_dir);
+        assert!(matches!(
+            GardenDir::init(garden_dir.path.clone(), world()),
+            Err(GardenDirError::AlreadyExists(_))
+        ));
+    }
+
+    #[test]
+    fn test_init_writes_genesis() {
+        let _scope = TimeStampScope::new();
+        let dir = TempDir::new("garden-dir").unwrap();
+        let world = world();
+        let garden_dir =
+            GardenDir::init(dir.path().join(GARDEN_DIR_NAME), world.clone()).unwrap();
+        let config = garden_dir.config().unwrap().unwrap();
+        assert_eq!(config.schema_version, crate::world::SCHEMA_VERSION);
+
+        let mut chain_store = garden_dir
+            .existing_chain_store::<ChainAction>(HeadRef::try_from(DEFAULT_HEAD).unwrap())
+            .unwrap();
+        let root = chain_store.iter_all().unwrap().next().unwrap();
+        assert_eq!(root.hash, config.genesis);
+        assert_eq!(
+            WorldMetadata::of_chain(chain_store.iter_loaded()),
+            Some(&world)
+        );
+
+        // A new head starts from the genesis block, rather than another world.
+        let mut other = garden_dir
+            .chain_store::<ChainAction>(HeadRef::try_from("other").unwrap())
+            .unwrap();
+        assert_eq!(other.iter_all().unwrap().count(), 1);
+        assert!(
+            other.head_hash().unwrap().is_none(),
+            "The head is only written on persist."
+        );
+        other.add(ChainAction::MovePlayer((
+            Position::new(1, 0),
+            Position::new(0, 0),
+        )));
+        other.persist().unwrap();
+
+        let report = garden_dir.fsck::<ChainAction>().unwrap();
+        assert!(report.is_ok());
+        assert_eq!(report.heads.len(), 2);
+        for head in &report.heads {
+            assert_eq!(head.root, Some(config.genesis.clone()));
+        }
+
+        let mut unrelated = BlockChain::<String>::new();
+        unrelated.add_data("elsewhere".into());
+        let mut missing = config.clone();
+        missing.genesis = unrelated.tip().unwrap().hash.clone();
+        garden_dir.write_config(&missing).unwrap();
+        assert!(matches!(
+            garden_dir.chain_store::<ChainAction>(HeadRef::try_from("new").unwrap()),
+            Err(GardenDirError::MissingGenesis(_))
+        ));
+        assert!(
+            GardenDir::open(dir.path().into())
+                .unwrap()
+                .config()
+                .unwrap()
+                .is_none(),
+            "Older gardens have no config."
+        );
+    }
+
+    #[test]
+    fn test_adopt_world() {
+        let _scope = TimeStampScope::new();
+        let default_head = || HeadRef::try_from(DEFAULT_HEAD).unwrap();
+        let init = |dir: &TempDir, name: &str| {
+            let world = WorldMetadata::new(name.into(), "creator".into());
+            GardenDir::init(dir.path().join(GARDEN_DIR_NAME), world).unwrap()
+        };
+        let move_player =
+            |x| ChainAction::MovePlayer((Position::new(x, 0), Position::new(0, 0)));
+
+        let dir_a = TempDir::new("garden-dir-a").unwrap();
+        let garden_a = init(&dir_a, "A");
+        let mut store_a = garden_a.chain_store::<ChainAction>(default_head()).unwrap();
+        store_a.iter_all().unwrap();
+        store_a.add(move_player(1));
+        store_a.persist().unwrap();
+        let blocks: Vec<_> = store_a.iter_all().unwrap().cloned().collect();
+
+        // Another garden that nothing has happened in yet, with a second head.
+        let dir_b = TempDir::new("garden-dir-b").unwrap();
+        let garden_b = init(&dir_b, "B");
+        let mut other = garden_b
+            .chain_store::<ChainAction>(HeadRef::try_from("other").unwrap())
+            .unwrap();
+        assert_eq!(
+            other.unpersisted_block_count(),
+            0,
+            "The genesis block of a new head is already saved."
+        );
+        other.persist().unwrap();
+        assert_eq!(garden_b.head_refs().unwrap().len(), 2);
+
+        let mut store_b = garden_b.chain_store::<ChainAction>(default_head()).unwrap();
+        assert_eq!(
+            garden_b.adopt_world(&mut store_b, &blocks).unwrap(),
+            Some(2)
+        );
+        let genesis = garden_a.config().unwrap().unwrap().genesis;
+        assert_eq!(garden_b.config().unwrap().unwrap().genesis, genesis);
+        assert_eq!(
+            garden_b.head_refs().unwrap(),
+            [default_head()],
+            "The heads of the old world were removed."
+        );
+        let report = garden_b.fsck::<ChainAction>().unwrap();
+        assert!(report.is_ok());
+        assert_eq!(report.heads[0].result.as_ref().unwrap(), &2);
+        assert_eq!(report.heads[0].root, Some(genesis.clone()));
+        let mut new_head = garden_b
+            .chain_store::<ChainAction>(HeadRef::try_from("new").unwrap())
+            .unwrap();
+        assert_eq!(
+            new_head.iter_all().unwrap().next().unwrap().hash,
+            genesis,
+            "New heads start in the adopted world."
+        );
+        assert_eq!(
+            garden_b.adopt_world(&mut store_b, &blocks).unwrap(),
+            None,
+            "The world is the same."
+        );
+
+        // A garden that has blocks of its own keeps its world.
+        let dir_c = TempDir::new("garden-dir-c").unwrap();
+        let garden_c = init(&dir_c, "C");
+        let config = garden_c.config().unwrap();
+        let mut store_c = garden_c.chain_store::<ChainAction>(default_head()).unwrap();
+        store_c.add(move_player(2));
+        store_c.persist().unwrap();
+        assert_eq!(garden_c.adopt_world(&mut store_c, &blocks).unwrap(), None);
+        assert_eq!(garden_c.config().unwrap(), config);
+    }
+
+    #[test]
+    fn test_import_bundle_into_new_garden() {
+        let _scope = TimeStampScope::new();
+        let default_head = || HeadRef::try_from(DEFAULT_HEAD).unwrap();
+        let move_player =
+            |x| ChainAction::MovePlayer((Position::new(x, 0), Position::new(0, 0)));
+        let dir_a = TempDir::new("garden-dir-a").unwrap();
+        let garden_a =
+            GardenDir::init(dir_a.path().join(GARDEN_DIR_NAME), world()).unwrap();
+        let mut store_a = garden_a.chain_store::<ChainAction>(default_head()).unwrap();
+        store_a.iter_all().unwrap();
+        store_a.add(move_player(1));
+        store_a.add(move_player(2));
+        store_a.persist().unwrap();
+        let mut file = vec![];
+        Bundle::create(&mut store_a, None)
+            .unwrap()
+            .write(&mut file)
+            .unwrap();
+
+        let dir_b = TempDir::new("garden-dir-b").unwrap();
+        let garden_b =
+            GardenDir::init(dir_b.path().join(GARDEN_DIR_NAME), world()).unwrap();
+        let bundle = Bundle::<ChainAction>::read(file.as_slice()).unwrap();
+        let head_ref = HeadRef::try_from(bundle.head_ref.clone()).unwrap();
+        assert_eq!(
+            garden_b.import_bundle(head_ref.clone(), &bundle).unwrap(),
+            3
+        );
+        let mut store_b = garden_b.chain_store::<ChainAction>(default_head()).unwrap();
+        assert_eq!(
+            store_b.iter_all().unwrap().cloned().collect::<Vec<_>>(),
+            store_a.iter_all().unwrap().cloned().collect::<Vec<_>>()
+        );
+
+        // Later bundles only have the new blocks.
+        let base = store_a.chain.tip().unwrap().hash.clone();
+        store_a.add(move_player(3));
+        store_a.persist().unwrap();
+        let bundle = Bundle::create(&mut store_a, Some(&base)).unwrap();
+        assert_eq!(
+            garden_b.import_bundle(head_ref.clone(), &bundle).unwrap(),
+            1
+        );
+        assert_eq!(
+            garden_b.import_bundle(head_ref, &bundle).unwrap(),
+            0,
+            "The blocks were already imported."
+        );
+        assert!(garden_b.fsck::<ChainAction>().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_fsck_and_gc() {
+        let dir = TempDir::new("garden-dir").unwrap();
+        let garden_dir = GardenDir::open(dir.path().into()).unwrap();
+
+        let mut main = get_store(&garden_dir, "main");
+        main.add("a".into());
+        main.persist().unwrap();
+        main.add("b".into());
+        main.persist().unwrap();
+
+        // A fork is stored in a chunk of its own, after the shared one.
+        let mut fork = get_store(&garden_dir, "fork");
+        fs::copy(
+            main.head_path(&main.head_ref),
+            fork.head_path(&fork.head_ref),
+        )
+        .unwrap();
+        fork.iter_all().unwrap();
+        fork.add("c".into());
+        fork.persist().unwrap();
+
+        let report = garden_dir.fsck::<String>().unwrap();
+        assert!(report.is_ok());
+        let counts: Vec<(&str, usize)> = report
+            .heads
+            .iter()
+            .map(|head| (head.head_ref.str(), *head.result.as_ref().unwrap()))
+            .collect();
+        assert_eq!(counts, [("fork", 3), ("main", 2)]);
+        assert_eq!(report.unreachable, Some(vec![]));
+
+        // Dropping the fork leaves its chunk behind.
+        let fork_chunk = fork.chain.tip().unwrap().hash.clone();
+        let fork_chunks = vec![fork_chunk.clone()];
+        fs::remove_file(fork.head_path(&fork.head_ref)).unwrap();
+        assert_eq!(garden_dir.gc::<String>(true).unwrap(), fork_chunks);
+        assert!(
+            fork.chunk_path(&fork_chunk).exists(),
+            "A dry run keeps chunks."
+        );
+        assert_eq!(garden_dir.gc::<String>(false).unwrap(), fork_chunks);
+        assert!(!fork.chunk_path(&fork_chunk).exists());
+        assert!(
+            !get_store(&garden_dir, "main")
+                .contains(&fork_chunk)
+                .unwrap(),
+            "The index was rebuilt."
+        );
+        assert!(garden_dir.gc::<String>(false).unwrap().is_empty());
+
+        let mut main = get_store(&garden_dir, "main");
+        assert_eq!(main.iter_all().unwrap().count(), 2, "Main is untouched.");
+
+        // A broken head is reported by fsck, and stops gc.
+        let main_chunk = main.chain.tip().unwrap().hash.clone();
+        fs::write(main.chunk_path(&main_chunk), "[]").unwrap();
+        let report = garden_dir.fsck::<String>().unwrap();
+        assert!(!report.is_ok());
+        assert_eq!(report.heads.len(), 1, "The fork was removed.");
+        assert!(matches!(
+            report.heads[0].result,
+            Err(LoadError::EmptyChunk { .. })
+        ));
+        assert_eq!(report.unreachable, None);
+        fs::remove_file(main.chunk_path(&main_chunk)).unwrap();
+        assert!(matches!(
+            garden_dir.gc::<String>(false),
+            Err(GardenDirError::UnreadableHead { .. })
+        ));
+    }
+
+    #[test]
+    fn test_fetch_and_merge_into_new_garden() {
+        let _scope = TimeStampScope::new();
+        let default_head = || HeadRef::try_from(DEFAULT_HEAD).unwrap();
+        let remote_ref = HeadRef::try_from("remotes/peer-a/my-garden").unwrap();
+        let move_player =
+            |x| ChainAction::MovePlayer((Position::new(x, 0), Position::new(0, 0)));
+        let dir_a = TempDir::new("garden-dir-a").unwrap();
+        let garden_a =
+            GardenDir::init(dir_a.path().join(GARDEN_DIR_NAME), world()).unwrap();
+        let mut store_a = garden_a.chain_store::<ChainAction>(default_head()).unwrap();
+        store_a.iter_all().unwrap();
+        store_a.add(move_player(1));
+        store_a.persist().unwrap();
+        let blocks: Vec<_> = store_a.iter_all().unwrap().cloned().collect();
+
+        let dir_b = TempDir::new("garden-dir-b").unwrap();
+        let garden_b =
+            GardenDir::init(dir_b.path().join(GARDEN_DIR_NAME), world()).unwrap();
+        let fetched = garden_b.fetch(&remote_ref, &blocks).unwrap();
+        assert_eq!(fetched.tip, Some(blocks[1].hash.clone()));
+        let changes = garden_b
+            .merge::<ChainAction>(&remote_ref, &default_head())
+            .unwrap();
+        assert_eq!(
+            changes,
+            RemoteChanges {
+                fork_index: 0,
+                removed: 1,
+                added: 2
+            }
+        );
+
+        // From then on, only the new blocks are fetched and merged.
+        store_a.add(move_player(2));
+        store_a.persist().unwrap();
+        let new_blocks: Vec<_> = store_a.iter_all().unwrap().skip(2).cloned().collect();
+        garden_b.fetch(&remote_ref, &new_blocks).unwrap();
+        let changes = garden_b
+            .merge::<ChainAction>(&remote_ref, &default_head())
+            .unwrap();
+        assert_eq!(
+            changes,
+            RemoteChanges {
+                fork_index: 2,
+                removed: 0,
+                added: 1
+            }
+        );
+        let mut store_b = garden_b.chain_store::<ChainAction>(default_head()).unwrap();
+        assert_eq!(
+            store_b.iter_all().unwrap().cloned().collect::<Vec<_>>(),
+            store_a.iter_all().unwrap().cloned().collect::<Vec<_>>()
+        );
+        assert!(garden_b.fsck::<ChainAction>().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_fetch_and_merge() {
+        let _scope = TimeStampScope::new();
+        let dir = TempDir::new("garden-dir").unwrap();
+        let garden_dir = GardenDir::open(dir.path().into()).unwrap();
+        let main_ref = HeadRef::try_from("main").unwrap();
+        let mut main = get_store(&garden_dir, "main");
+        main.add("a".into());
+        main.add("b".into());
+        main.persist().unwrap();
+
+        // The peer has the same chain, plus a block.
+        let mut peer =
+            BlockChain::from(main.iter_all().unwrap().cloned().collect::<Vec<_>>());
+        let tip = peer.add_data("c".into()).hash.clone();
+        let remote = HeadRef::remote("peer-1", &main_ref).unwrap();
+        let fetched = garden_dir
+            .fetch(&remote, &peer.as_block_slice()[2..])
+            .unwrap();
+        assert_eq!(
+            fetched,
+            FetchResult {
+                previous: None,
+                tip: Some(tip.clone())
+            }
+        );
+        assert!(garden_dir
+            .fetch(&remote, peer.as_block_slice())
+            .unwrap()
+            .is_up_to_date());
+        assert_eq!(
+            get_store(&garden_dir, "main").head_hash().unwrap(),
+            main.head_hash().unwrap(),
+            "Fetching doesn't touch the local head."
+        );
+        assert_eq!(
+            garden_dir.remote_head_refs(None).unwrap(),
+            std::slice::from_ref(&remote)
+        );
+        assert!(garden_dir
+            .remote_head_refs(Some("peer-2"))
+            .unwrap()
+            .is_empty());
+
+        let changes = garden_dir.merge::<String>(&remote, &main_ref).unwrap();
+        assert_eq!(
+            changes,
+            RemoteChanges {
+                fork_index: 2,
+                removed: 0,
+                added: 1
+            }
+        );
+        assert_eq!(
+            get_store(&garden_dir, "main").head_hash().unwrap(),
+            Some(tip)
+        );
+
+        // The peer went back to a shorter chain. The remote ref follows it, but it
+        // can't be merged.
+        let mut peer = BlockChain::from(vec![peer.blocks[0].clone()]);
+        let tip = peer.add_data("x".into()).hash.clone();
+        let fetched = garden_dir
+            .fetch(&remote, &peer.as_block_slice()[1..])
+            .unwrap();
+        assert_eq!(fetched.tip, Some(tip));
+        assert_eq!(
+            garden_dir
+                .chain_store::<String>(remote.clone())
+                .unwrap()
+                .iter_all()
+                .unwrap()
+                .count(),
+            2
+        );
+        assert!(matches!(
+            garden_dir.merge::<String>(&remote, &main_ref),
+            Err(GardenDirError::Merge {
+                source: ReconcileError::ShorterForeignBlocks,
+                ..
+            })
+        ));
+        assert!(garden_dir.fsck::<String>().unwrap().is_ok());
+
+        assert!(matches!(
+            garden_dir.fetch(&main_ref, peer.as_block_slice()),
+            Err(GardenDirError::NotARemote(_))
+        ));
+        let mut unrelated = BlockChain::<String>::new();
+        unrelated.add_data("y".into());
+        unrelated.add_data("z".into());
+        assert!(matches!(
+            garden_dir.fetch(&remote, &unrelated.as_block_slice()[1..]),
+            Err(GardenDirError::MissingBase(_))
+        ));
+        assert_eq!(
+            get_store(&garden_dir, "remotes/peer-1/main")
+                .head_hash()
+                .unwrap(),
+            fetched.tip,
+            "A failed fetch leaves the remote ref as it was."
+        );
+    }
+}
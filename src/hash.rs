@@ -14,6 +14,13 @@ impl Hash {
         Hash([0; 32])
     }
 
+    /// The first few characters of the hash, for display to a user.
+    pub fn short(&self) -> String {
+        let mut string = String::from(self);
+        string.truncate(8);
+        string
+    }
+
     /// A root hash would be all 0 values. This is a somewhat hacky way to create a root
     /// block without adding another property to the Block struct.
     pub fn is_root(&self) -> bool {
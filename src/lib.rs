@@ -3,20 +3,29 @@
 
 pub mod actions;
 pub mod block_chain;
+pub mod block_index;
+pub mod bundle;
+pub mod chain_diff;
+pub mod chain_log;
+pub mod chain_query;
 pub mod chain_store;
 pub mod game;
 pub mod garden;
+pub mod garden_dir;
 pub mod hash;
+pub mod middleware;
+pub mod peers;
 pub mod reducers;
+pub mod ref_name;
+pub mod revision;
 mod state;
 pub mod store;
+pub mod store_actor;
 pub mod utils;
+pub mod world;
 
 pub use actions::{Action, ChainAction, GameAction};
 pub use chain_store::ChainStore;
 pub use hash::Hash;
-pub use state::{selectors, State};
+pub use state::{selector_stats, selectors, State};
 pub use store::Store;
-
-#[macro_use]
-extern crate static_assertions;
@@ -0,0 +1,96 @@
+//! Middleware sees every action that goes through the Store, both before and after
+//! it is reduced. This is the place for cross-cutting concerns, like the action
+//! inspector, that shouldn't be hard-wired into `Store::dispatch`.
+
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+use crate::{Action, GameAction, State};
+
+pub trait Middleware {
+    /// Called before the action is reduced.
+    fn before_reduce(&mut self, action: &Action, state: &Rc<State>) {}
+
+    /// Called after the action is reduced. Chain actions have already been added to
+    /// the chain at this point.
+    fn after_reduce(&mut self, action: &Action, prev: &Rc<State>, next: &Rc<State>) {}
+}
+
+/// An action that went through the store, as recorded by the ActionInspector.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActionRecord {
+    /// The Debug output of the action.
+    pub action: String,
+    /// Whether the action changed the state.
+    pub changed_state: bool,
+}
+
+/// Records the most recent actions for the devtools overlay. Clones share the same
+/// records, so one clone can be added to the store while another is read from.
+#[derive(Debug, Clone)]
+pub struct ActionInspector {
+    capacity: usize,
+    records: Rc<RefCell<VecDeque<ActionRecord>>>,
+}
+
+impl ActionInspector {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            records: Rc::new(RefCell::new(VecDeque::with_capacity(capacity))),
+        }
+    }
+
+    /// The recorded actions, oldest first.
+    pub fn records(&self) -> Vec<ActionRecord> {
+        self.records.borrow().iter().cloned().collect()
+    }
+}
+
+impl Middleware for ActionInspector {
+    fn after_reduce(&mut self, action: &Action, prev: &Rc<State>, next: &Rc<State>) {
+        if let Action::Game(GameAction::TickGame(_)) = action {
+            // This happens every frame, and would drown out everything else.
+            return;
+        }
+        let mut records = self.records.borrow_mut();
+        if records.len() == self.capacity {
+            records.pop_front();
+        }
+        records.push_back(ActionRecord {
+            action: format!("{:?}", action),
+            changed_state: !Rc::ptr_eq(prev, next),
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actions;
+
+    #[test]
+    fn test_action_inspector() {
+        let inspector = ActionInspector::new(2);
+        let mut middleware = inspector.clone();
+        let state = Rc::new(State::new());
+
+        let mut dispatch = |action: Action| {
+            let next = Rc::new(state.reduce(&action));
+            middleware.after_reduce(&action, &state, &next);
+        };
+        dispatch(actions::tick_game());
+        dispatch(actions::create_garden_plot("First".into()));
+        dispatch(actions::create_garden_plot("Second".into()));
+        dispatch(actions::create_garden_plot("Third".into()));
+
+        let records = inspector.records();
+        assert_eq!(
+            records.len(),
+            2,
+            "Ticks are skipped, and old records dropped."
+        );
+        assert!(records[0].action.contains("Second"));
+        assert!(records[1].action.contains("Third"));
+        assert!(records[1].changed_state);
+    }
+}
@@ -0,0 +1,330 @@
+//! Who this garden is on the network, and the peers it knows about. The keypair is
+//! kept in .garden/identity so the peer id stays the same between runs, and the
+//! address book is kept in .garden/peers.json.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use libp2p::identity::{error::DecodingError, Keypair};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// The name of the file with the keypair of the garden, in the .garden directory.
+pub const IDENTITY_FILE_NAME: &str = "identity";
+
+/// The name of the address book file, in the .garden directory.
+pub const PEERS_FILE_NAME: &str = "peers.json";
+
+/// How many of a peer's addresses are remembered, newest first.
+pub const MAX_ADDRESSES: usize = 8;
+
+#[derive(Error, Debug)]
+pub enum PeersError {
+    #[error("failed to read {}", .path.display())]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to write {}", .path.display())]
+    Write {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("the keypair at {} is malformed", .path.display())]
+    MalformedIdentity {
+        path: PathBuf,
+        #[source]
+        source: DecodingError,
+    },
+    #[error(
+        "the keypair at {} can be read by other users, restrict it with `chmod 600`",
+        .0.display()
+    )]
+    InsecureIdentity(PathBuf),
+    #[error("the address book at {} is malformed", .path.display())]
+    MalformedAddressBook {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+}
+
+/// Read the keypair, or generate one the first time, so the peer id of the garden
+/// doesn't change between runs.
+pub fn load_or_create_identity(path: &Path) -> Result<Keypair, PeersError> {
+    match read_identity(path) {
+        Err(PeersError::Read { source, .. })
+            if source.kind() == io::ErrorKind::NotFound =>
+        {
+            let keypair = Keypair::generate_ed25519();
+            write_identity(path, &keypair)?;
+            Ok(keypair)
+        }
+        result => result,
+    }
+}
+
+/// Read a keypair that was written with `write_identity`. A key that other users can
+/// read is refused rather than used, as with ssh keys.
+pub fn read_identity(path: &Path) -> Result<Keypair, PeersError> {
+    let read_error = |source| PeersError::Read {
+        path: path.to_path_buf(),
+        source,
+    };
+    let metadata = fs::metadata(path).map_err(read_error)?;
+    if !is_private(&metadata) {
+        return Err(PeersError::InsecureIdentity(path.to_path_buf()));
+    }
+    let bytes = fs::read(path).map_err(read_error)?;
+    Keypair::from_protobuf_encoding(&bytes).map_err(|source| {
+        PeersError::MalformedIdentity {
+            path: path.to_path_buf(),
+            source,
+        }
+    })
+}
+
+/// Write the keypair so that only the current user can read it. An existing file is
+/// not replaced, as that would change the peer id.
+pub fn write_identity(path: &Path, keypair: &Keypair) -> Result<(), PeersError> {
+    let write_error = |source| PeersError::Write {
+        path: path.to_path_buf(),
+        source,
+    };
+    let bytes = keypair.to_protobuf_encoding().map_err(|source| {
+        PeersError::MalformedIdentity {
+            path: path.to_path_buf(),
+            source,
+        }
+    })?;
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path).map_err(write_error)?;
+    file.write_all(&bytes).map_err(write_error)
+}
+
+#[cfg(unix)]
+fn is_private(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o077 == 0
+}
+
+#[cfg(not(unix))]
+fn is_private(_metadata: &fs::Metadata) -> bool {
+    true
+}
+
+/// How much a peer is trusted.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Trust {
+    /// Its heads are fetched, but only merged by hand.
+    #[default]
+    Unknown,
+    /// Its heads can be merged automatically, see `garden serve --merge`.
+    Trusted,
+    /// Its messages are ignored.
+    Blocked,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct KnownPeer {
+    pub nickname: Option<String>,
+    /// The multiaddrs the peer was last seen at, newest first.
+    #[serde(default)]
+    pub addresses: Vec<String>,
+    /// The unix timestamp of when the peer was last seen.
+    pub last_seen: Option<i64>,
+    #[serde(default)]
+    pub trust: Trust,
+}
+
+/// The peers the garden knows about, by peer id.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct AddressBook {
+    pub peers: BTreeMap<String, KnownPeer>,
+}
+
+impl AddressBook {
+    /// Read the address book. A missing file is an empty book.
+    pub fn read(path: &Path) -> Result<Self, PeersError> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Ok(Self::default())
+            }
+            Err(source) => {
+                return Err(PeersError::Read {
+                    path: path.to_path_buf(),
+                    source,
+                })
+            }
+        };
+        serde_json::from_str(&text).map_err(|source| PeersError::MalformedAddressBook {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), PeersError> {
+        let text = serde_json::to_string_pretty(self)
+            .expect("Unable to serialize the address book.");
+        fs::write(path, text).map_err(|source| PeersError::Write {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    pub fn get(&self, peer_id: &str) -> Option<&KnownPeer> {
+        self.peers.get(peer_id)
+    }
+
+    /// The entry for a peer, which is added if it isn't known yet.
+    pub fn entry(&mut self, peer_id: &str) -> &mut KnownPeer {
+        self.peers.entry(peer_id.into()).or_default()
+    }
+
+    pub fn remove(&mut self, peer_id: &str) -> Option<KnownPeer> {
+        self.peers.remove(peer_id)
+    }
+
+    /// Find a peer by its id or nickname.
+    pub fn find(&self, name: &str) -> Option<(&str, &KnownPeer)> {
+        if let Some((peer_id, peer)) = self.peers.get_key_value(name) {
+            return Some((peer_id, peer));
+        }
+        self.peers
+            .iter()
+            .find(|(_, peer)| peer.nickname.as_deref() == Some(name))
+            .map(|(peer_id, peer)| (peer_id.as_str(), peer))
+    }
+
+    pub fn trust(&self, peer_id: &str) -> Trust {
+        self.get(peer_id).map(|peer| peer.trust).unwrap_or_default()
+    }
+
+    /// Record that a peer was seen at an address. Returns true if anything changed.
+    pub fn saw(&mut self, peer_id: &str, address: &str, timestamp: i64) -> bool {
+        let peer = self.entry(peer_id);
+        let is_newest = peer.addresses.first().map(String::as_str) == Some(address);
+        if is_newest && peer.last_seen == Some(timestamp) {
+            return false;
+        }
+        peer.addresses.retain(|known| known != address);
+        peer.addresses.insert(0, address.into());
+        peer.addresses.truncate(MAX_ADDRESSES);
+        peer.last_seen = Some(timestamp);
+        true
+    }
+}
+
+/// A short name for a peer to show to the player, i.e. its nickname if it has one.
+pub fn display_name(address_book: &AddressBook, peer_id: &str) -> String {
+    match address_book
+        .get(peer_id)
+        .and_then(|peer| peer.nickname.as_ref())
+    {
+        Some(nickname) => format!("{} ({})", nickname, peer_id),
+        None => peer_id.into(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use libp2p::PeerId;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_identity() {
+        let dir = TempDir::new("garden-peers").unwrap();
+        let path = dir.path().join(IDENTITY_FILE_NAME);
+        let keypair = load_or_create_identity(&path).unwrap();
+        let reloaded = load_or_create_identity(&path).unwrap();
+        assert_eq!(
+            PeerId::from(keypair.public()),
+            PeerId::from(reloaded.public()),
+            "The peer id is the same between runs."
+        );
+        assert!(
+            write_identity(&path, &Keypair::generate_ed25519()).is_err(),
+            "The keypair isn't replaced."
+        );
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+            assert!(matches!(
+                read_identity(&path),
+                Err(PeersError::InsecureIdentity(_))
+            ));
+        }
+
+        fs::write(dir.path().join("garbage"), "not a key").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(
+                dir.path().join("garbage"),
+                fs::Permissions::from_mode(0o600),
+            )
+            .unwrap();
+        }
+        assert!(matches!(
+            read_identity(&dir.path().join("garbage")),
+            Err(PeersError::MalformedIdentity { .. })
+        ));
+    }
+
+    #[test]
+    fn test_address_book() {
+        let dir = TempDir::new("garden-peers").unwrap();
+        let path = dir.path().join(PEERS_FILE_NAME);
+        let mut book = AddressBook::read(&path).unwrap();
+        assert_eq!(book, AddressBook::default());
+
+        assert!(book.saw("peer-1", "/ip4/10.0.0.1/tcp/1", 10));
+        assert!(!book.saw("peer-1", "/ip4/10.0.0.1/tcp/1", 10));
+        assert!(book.saw("peer-1", "/ip4/10.0.0.2/tcp/1", 20));
+        assert!(book.saw("peer-1", "/ip4/10.0.0.1/tcp/1", 30));
+        for i in 0..MAX_ADDRESSES {
+            book.saw("peer-2", &format!("/ip4/10.0.1.{}/tcp/1", i), 40);
+        }
+        book.saw("peer-2", "/ip4/10.0.2.1/tcp/1", 50);
+        book.entry("peer-2").nickname = Some("Alice".into());
+        book.entry("peer-2").trust = Trust::Trusted;
+        book.write(&path).unwrap();
+
+        let book = AddressBook::read(&path).unwrap();
+        let peer_1 = book.get("peer-1").unwrap();
+        assert_eq!(
+            peer_1.addresses,
+            ["/ip4/10.0.0.1/tcp/1", "/ip4/10.0.0.2/tcp/1"],
+            "Addresses are moved to the front when seen again."
+        );
+        assert_eq!(peer_1.last_seen, Some(30));
+        assert_eq!(book.trust("peer-1"), Trust::Unknown);
+
+        let (peer_id, peer_2) = book.find("Alice").unwrap();
+        assert_eq!(peer_id, "peer-2");
+        assert_eq!(peer_2.addresses.len(), MAX_ADDRESSES);
+        assert_eq!(peer_2.addresses[0], "/ip4/10.0.2.1/tcp/1");
+        assert_eq!(book.trust("peer-2"), Trust::Trusted);
+        assert_eq!(display_name(&book, "peer-2"), "Alice (peer-2)");
+        assert!(book.find("Bob").is_none());
+    }
+}
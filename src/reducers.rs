@@ -1,16 +1,31 @@
 use crate::{
-    game::primitives::Position, garden::GardenPlot, Action, ChainAction, GameAction,
+    game::primitives::Position, garden::GardenPlot, state::Plots, Action, ChainAction,
+    GameAction,
 };
 use std::rc::Rc;
+use uuid::Uuid;
 
-pub fn garden(state: Option<Rc<GardenPlot>>, action: &Action) -> Option<Rc<GardenPlot>> {
+pub fn plots(state: Plots, action: &Action) -> Plots {
+    match action {
+        Action::Chain(ChainAction::CreatePlot(plot)) => {
+            if state.contains_key(&plot.uuid) {
+                return state;
+            }
+            state.update(plot.uuid, Rc::new(plot.clone()))
+        }
+        _ => state,
+    }
+}
+
+/// The first plot that was created is the player's garden.
+pub fn my_garden(state: Option<Uuid>, action: &Action) -> Option<Uuid> {
     match action {
         Action::Chain(ChainAction::CreatePlot(plot)) => {
             if state.is_some() {
                 // Do not allow overriding the garden.
                 return state;
             }
-            Some(Rc::new(plot.clone()))
+            Some(plot.uuid)
         }
         _ => state,
     }
@@ -39,7 +54,49 @@ pub fn player_position(state: Option<Position>, event: &Action) -> Option<Positi
             if state.is_none() {
                 Some(GardenPlot::get_default_bbox().center())
             } else {
-                None
+                state
+            }
+        }
+        Action::Chain(ChainAction::MovePlayer((position, move_intent)))
+        | Action::Game(GameAction::MovePlayer((position, move_intent))) => {
+            Some(*position)
+        }
+        _ => state,
+    }
+}
+
+/// How many steps the player has taken since their position was last recorded in
+/// the chain. These count toward autosaving, as blocks do.
+pub fn unsaved_moves(state: usize, event: &Action) -> usize {
+    match event {
+        Action::Game(GameAction::MovePlayer(_)) => state + 1,
+        Action::Chain(ChainAction::MovePlayer(_)) => 0,
+        _ => state,
+    }
+}
+
+pub fn camera(state: Position, event: &Action) -> Position {
+    match event {
+        Action::Game(GameAction::MoveCamera(camera)) => *camera,
+        _ => state,
+    }
+}
+
+pub fn cursor(state: Option<Position>, event: &Action) -> Option<Position> {
+    match event {
+        Action::Game(GameAction::MoveCursor(cursor)) => *cursor,
+        _ => state,
+    }
+}
+
+/// The player position as last recorded in the chain.
+pub fn checkpoint_position(state: Option<Position>, event: &Action) -> Option<Position> {
+    match event {
+        Action::Chain(ChainAction::CreatePlot(plot)) => {
+            if state.is_none() {
+                Some(GardenPlot::get_default_bbox().center())
+            } else {
+                state
             }
         }
         Action::Chain(ChainAction::MovePlayer((position, move_intent))) => {
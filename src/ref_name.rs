@@ -0,0 +1,286 @@
+//! The grammar of head ref names. Refs are stored as files under .garden/heads, so a
+//! name must never be able to reach outside of that directory, or collide with the
+//! syntax of revisions, see `revision`.
+//!
+//!   ref       = component ("/" component)*
+//!   component = (ALPHA / DIGIT / "_") *(ALPHA / DIGIT / "_" / "-" / ".")
+//!
+//! On top of that, names are limited in length and depth, and `HEAD` is reserved.
+//! Refs in the remotes namespace are `remotes/<peer>/<head>`, where the head is any
+//! valid local ref, so that every head of a peer can be tracked.
+
+use thiserror::Error;
+
+/// The longest a ref name can be, in bytes.
+pub const MAX_LEN: usize = 200;
+
+/// The longest a single component of a ref name can be, in bytes. This fits a peer id.
+pub const MAX_COMPONENT_LEN: usize = 64;
+
+/// The most components a local ref name can have. Remote refs have two more, for the
+/// remotes/<peer>/ prefix.
+pub const MAX_DEPTH: usize = 3;
+
+/// Names that can't be used for refs, in any case.
+pub const RESERVED: &[&str] = &["HEAD"];
+
+/// The namespace for the heads of other peers.
+pub const REMOTES_NAMESPACE: &str = "remotes";
+
+#[derive(Error, Debug, PartialEq, Clone)]
+pub enum RefNameError {
+    #[error("it is empty")]
+    Empty,
+    #[error("it is longer than {MAX_LEN} bytes")]
+    TooLong,
+    #[error("the head has more than {MAX_DEPTH} parts")]
+    TooDeep,
+    #[error("it has an empty part")]
+    EmptyComponent,
+    #[error("the part {0:?} is longer than {MAX_COMPONENT_LEN} bytes")]
+    ComponentTooLong(String),
+    #[error("the part {0:?} starts with a '.' or '-'")]
+    InvalidStart(String),
+    #[error("{0:?} is not allowed, only letters, digits, '_', '-', '.' and '/' are")]
+    InvalidChar(char),
+    #[error("{0:?} is reserved")]
+    Reserved(String),
+    #[error(
+        "remote refs are named {REMOTES_NAMESPACE}/<peer>/<head>, with a local head"
+    )]
+    InvalidRemote,
+}
+
+/// Check a ref name against the grammar.
+pub fn validate(name: &str) -> Result<(), RefNameError> {
+    if name.is_empty() {
+        return Err(RefNameError::Empty);
+    }
+    if name.len() > MAX_LEN {
+        return Err(RefNameError::TooLong);
+    }
+    if let Some(ch) = name.chars().find(|ch| !is_ref_char(*ch) && *ch != '/') {
+        return Err(RefNameError::InvalidChar(ch));
+    }
+
+    let components: Vec<&str> = name.split('/').collect();
+    if components[0] != REMOTES_NAMESPACE {
+        return validate_local(&components);
+    }
+    if components.len() < 3 {
+        return Err(RefNameError::InvalidRemote);
+    }
+    validate_component(components[1])?;
+    // A peer's own remote refs aren't tracked, see `validate_local`.
+    validate_local(&components[2..])
+}
+
+/// Check the components of a ref outside of the remotes namespace.
+fn validate_local(components: &[&str]) -> Result<(), RefNameError> {
+    if components.len() > MAX_DEPTH {
+        return Err(RefNameError::TooDeep);
+    }
+    for component in components {
+        validate_component(component)?;
+    }
+    if components[0] == REMOTES_NAMESPACE {
+        return Err(RefNameError::InvalidRemote);
+    }
+    Ok(())
+}
+
+fn validate_component(component: &str) -> Result<(), RefNameError> {
+    if component.is_empty() {
+        return Err(RefNameError::EmptyComponent);
+    }
+    if component.len() > MAX_COMPONENT_LEN {
+        return Err(RefNameError::ComponentTooLong(component.into()));
+    }
+    // This rules out "." and "..", hidden files, and names that look like flags.
+    if component.starts_with(['.', '-']) {
+        return Err(RefNameError::InvalidStart(component.into()));
+    }
+    if RESERVED
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(component))
+    {
+        return Err(RefNameError::Reserved(component.into()));
+    }
+    Ok(())
+}
+
+fn is_ref_char(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || ch == '_' || ch == '-' || ch == '.'
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+    use std::path::{Component, Path};
+
+    const VALID_CHARS: &[u8] =
+        b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789_-.";
+
+    /// Characters that show up in path and shell tricks.
+    const NASTY_CHARS: &[char] = &[
+        '/', '.', '\\', '~', '@', '{', '}', ':', ' ', '\0', '\n', '*', '?', '$', 'é',
+    ];
+
+    fn random_component(rng: &mut StdRng) -> String {
+        let len = rng.gen_range(1..=12);
+        let mut component: String = (0..len)
+            .map(|_| *VALID_CHARS.choose(rng).unwrap() as char)
+            .collect();
+        if component.starts_with(['.', '-']) {
+            component.replace_range(0..1, "g");
+        }
+        component
+    }
+
+    fn random_valid_name(rng: &mut StdRng) -> String {
+        let depth = rng.gen_range(1..=MAX_DEPTH);
+        let mut components: Vec<String> =
+            (0..depth).map(|_| random_component(rng)).collect();
+        for component in components.iter_mut() {
+            if RESERVED
+                .iter()
+                .any(|reserved| reserved.eq_ignore_ascii_case(component))
+            {
+                *component = "garden".into();
+            }
+        }
+        if components[0] == REMOTES_NAMESPACE {
+            components[0] = "garden".into();
+        }
+        if rng.gen_bool(0.2) {
+            components.insert(0, random_component(rng));
+            components.insert(0, REMOTES_NAMESPACE.into());
+            if RESERVED
+                .iter()
+                .any(|reserved| reserved.eq_ignore_ascii_case(&components[1]))
+            {
+                components[1] = "peer".into();
+            }
+        }
+        components.join("/")
+    }
+
+    fn random_name(rng: &mut StdRng) -> String {
+        let len = rng.gen_range(0..=20);
+        (0..len)
+            .map(|_| {
+                if rng.gen_bool(0.3) {
+                    *NASTY_CHARS.choose(rng).unwrap()
+                } else {
+                    *VALID_CHARS.choose(rng).unwrap() as char
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_examples() {
+        for valid in [
+            "my-garden",
+            "garden_1",
+            "v1.2",
+            "a",
+            "feature/plots",
+            "remotes/12D3KooWEp1T4DHH/my-garden",
+            "remotes/12D3KooWEp1T4DHH/feature/plots",
+            "remotes/peer/a/b/c",
+            "HEADS",
+        ] {
+            assert_eq!(validate(valid), Ok(()), "{:?} is valid", valid);
+        }
+
+        let too_long = "a/".repeat(MAX_LEN / 2) + "a";
+        for (invalid, error) in [
+            ("", RefNameError::Empty),
+            ("a/../etc", RefNameError::InvalidStart("..".into())),
+            ("../../etc", RefNameError::InvalidStart("..".into())),
+            ("a/../../etc", RefNameError::TooDeep),
+            ("..", RefNameError::InvalidStart("..".into())),
+            (".hidden", RefNameError::InvalidStart(".hidden".into())),
+            ("-rf", RefNameError::InvalidStart("-rf".into())),
+            ("/etc/passwd", RefNameError::EmptyComponent),
+            ("trailing/", RefNameError::EmptyComponent),
+            ("a//b", RefNameError::EmptyComponent),
+            ("a/b/c/d", RefNameError::TooDeep),
+            (&too_long, RefNameError::TooLong),
+            ("HEAD", RefNameError::Reserved("HEAD".into())),
+            ("head", RefNameError::Reserved("head".into())),
+            ("remotes", RefNameError::InvalidRemote),
+            ("remotes/peer", RefNameError::InvalidRemote),
+            ("remotes/peer/remotes/other/x", RefNameError::InvalidRemote),
+            ("remotes/peer/a/b/c/d", RefNameError::TooDeep),
+            ("remotes/../head", RefNameError::InvalidStart("..".into())),
+            ("main~1", RefNameError::InvalidChar('~')),
+            ("main@{1}", RefNameError::InvalidChar('@')),
+            ("a\\b", RefNameError::InvalidChar('\\')),
+            ("with space", RefNameError::InvalidChar(' ')),
+        ] {
+            assert_eq!(validate(invalid), Err(error), "{:?} is invalid", invalid);
+        }
+        assert_eq!(
+            validate(&"a".repeat(MAX_COMPONENT_LEN + 1)),
+            Err(RefNameError::ComponentTooLong(
+                "a".repeat(MAX_COMPONENT_LEN + 1)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_generated_names_are_valid() {
+        let mut rng = StdRng::seed_from_u64(48);
+        for _ in 0..1000 {
+            let name = random_valid_name(&mut rng);
+            assert_eq!(validate(&name), Ok(()), "{:?} is valid", name);
+        }
+    }
+
+    #[test]
+    fn test_valid_names_stay_in_the_heads_directory() {
+        let mut rng = StdRng::seed_from_u64(48);
+        let mut valid_count = 0;
+        for _ in 0..10_000 {
+            let name = random_name(&mut rng);
+            if validate(&name).is_err() {
+                continue;
+            }
+            valid_count += 1;
+            assert!(name.len() <= MAX_LEN);
+            assert!(
+                Path::new(&name)
+                    .components()
+                    .all(|component| matches!(component, Component::Normal(_))),
+                "{:?} only has normal path components",
+                name
+            );
+            assert!(
+                name.split('/').all(|component| !component.starts_with('.')),
+                "{:?} has no hidden files",
+                name
+            );
+            assert!(
+                !name.contains(['~', '@']),
+                "{:?} can't be confused with a revision",
+                name
+            );
+        }
+        assert!(valid_count > 100, "Enough names were valid to be useful.");
+    }
+
+    #[test]
+    fn test_nasty_chars_are_rejected() {
+        let mut rng = StdRng::seed_from_u64(48);
+        for _ in 0..1000 {
+            let mut name = random_valid_name(&mut rng);
+            let ch = *NASTY_CHARS[2..].choose(&mut rng).unwrap();
+            let index = rng.gen_range(0..=name.len());
+            name.insert(index, ch);
+            assert!(validate(&name).is_err(), "{:?} is invalid", name);
+        }
+    }
+}
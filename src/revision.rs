@@ -0,0 +1,286 @@
+//! Revisions name a block from the command line, similar to git revisions.
+//!
+//!   my-garden                 The block a head points to.
+//!   3fa9c1                    The block whose hash starts with these hex digits. The
+//!                             prefix must be unique, and at least MIN_PREFIX_LEN long.
+//!   my-garden~3               The third ancestor of a block. `~` alone is the parent.
+//!   my-garden@{2021-12-31}    The newest block of the chain that was added at or
+//!                             before the time, see `chain_query::parse_time`.
+//!
+//! The suffixes can be chained, e.g. `my-garden@{2021-12-31}~2`. When a name is both a
+//! head and a hash prefix, the head wins.
+//!
+//! The garden can't be played as of an older block yet, so only `garden cat`, `log`,
+//! `diff` and `bundle` take revisions.
+
+use std::{fmt, str::FromStr};
+
+use chrono::{SecondsFormat, TimeZone, Utc};
+use thiserror::Error;
+
+use crate::{
+    block_chain::BlockData,
+    chain_query::parse_time,
+    chain_store::{FsChainStore, HeadRef, LoadError},
+    ChainStore, Hash,
+};
+
+/// The shortest hash prefix that is looked up, so that short head names that happen
+/// to be hex aren't mistaken for blocks.
+pub const MIN_PREFIX_LEN: usize = 4;
+
+#[derive(Error, Debug)]
+pub enum RevisionError {
+    #[error("the revision {0:?} is not valid, {1}")]
+    Syntax(String, &'static str),
+    #[error("no head or block matches {0:?}")]
+    Unknown(String),
+    #[error(
+        "the prefix {prefix:?} matches more than one block: {}",
+        .matches.iter().map(Hash::short).collect::<Vec<_>>().join(", ")
+    )]
+    Ambiguous { prefix: String, matches: Vec<Hash> },
+    #[error("{0} goes back past the root of the chain")]
+    NoAncestor(String),
+    #[error("{0} is before the root of the chain")]
+    TooOld(String),
+    #[error(transparent)]
+    Load(#[from] LoadError),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Revision {
+    /// A head ref, or a hash prefix, which are told apart when resolving.
+    Name(String),
+    /// The Nth ancestor, e.g. my-garden~3.
+    Ancestor(Box<Revision>, usize),
+    /// The newest block at or before a timestamp, e.g. my-garden@{2021-12-31}.
+    AsOf(Box<Revision>, i64),
+}
+
+impl Revision {
+    /// Find the hash of the block the revision names.
+    pub fn resolve<T: BlockData>(
+        &self,
+        chain_store: &mut FsChainStore<T>,
+    ) -> Result<Hash, RevisionError> {
+        match self {
+            Revision::Name(name) => resolve_name(name, chain_store),
+            Revision::Ancestor(revision, generations) => {
+                let mut hash = revision.resolve(chain_store)?;
+                for _ in 0..*generations {
+                    let block = chain_store
+                        .get_block(&hash)?
+                        .ok_or_else(|| RevisionError::Unknown(hash.to_string()))?;
+                    if block.payload.parent.is_root() {
+                        return Err(RevisionError::NoAncestor(self.to_string()));
+                    }
+                    hash = block.payload.parent;
+                }
+                Ok(hash)
+            }
+            Revision::AsOf(revision, timestamp) => {
+                let hash = revision.resolve(chain_store)?;
+                let blocks = chain_store
+                    .blocks_to(&hash)?
+                    .ok_or_else(|| RevisionError::Unknown(hash.to_string()))?;
+                blocks
+                    .iter()
+                    .rev()
+                    .find(|block| block.payload.timestamp <= *timestamp)
+                    .map(|block| block.hash.clone())
+                    .ok_or_else(|| RevisionError::TooOld(self.to_string()))
+            }
+        }
+    }
+}
+
+fn resolve_name<T: BlockData>(
+    name: &str,
+    chain_store: &mut FsChainStore<T>,
+) -> Result<Hash, RevisionError> {
+    if let Ok(head_ref) = HeadRef::try_from(name.to_string()) {
+        if let Some(hash) = chain_store.resolve_head(&head_ref)? {
+            return Ok(hash);
+        }
+    }
+    if name.len() < MIN_PREFIX_LEN || !name.chars().all(|ch| ch.is_ascii_hexdigit()) {
+        return Err(RevisionError::Unknown(name.into()));
+    }
+    let mut matches = chain_store.find_by_prefix(name)?;
+    match matches.len() {
+        0 => Err(RevisionError::Unknown(name.into())),
+        1 => Ok(matches.remove(0)),
+        _ => Err(RevisionError::Ambiguous {
+            prefix: name.into(),
+            matches,
+        }),
+    }
+}
+
+impl FromStr for Revision {
+    type Err = RevisionError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let syntax = |reason| RevisionError::Syntax(text.into(), reason);
+        let name_end = text.find(['~', '@']).unwrap_or(text.len());
+        if name_end == 0 {
+            return Err(syntax("it needs to start with a head or a hash"));
+        }
+        let mut revision = Revision::Name(text[..name_end].into());
+
+        let mut rest = &text[name_end..];
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('~') {
+                let digits_end = after
+                    .find(|ch: char| !ch.is_ascii_digit())
+                    .unwrap_or(after.len());
+                let generations = match &after[..digits_end] {
+                    "" => 1,
+                    digits => digits
+                        .parse()
+                        .map_err(|_| syntax("the number after ~ is too large"))?,
+                };
+                revision = Revision::Ancestor(Box::new(revision), generations);
+                rest = &after[digits_end..];
+            } else if let Some(after) = rest.strip_prefix("@{") {
+                let end = after
+                    .find('}')
+                    .ok_or_else(|| syntax("the @{ is missing its closing }"))?;
+                let timestamp = parse_time(&after[..end])
+                    .map_err(|_| syntax("the time in @{...} could not be parsed"))?;
+                revision = Revision::AsOf(Box::new(revision), timestamp);
+                rest = &after[end + 1..];
+            } else {
+                return Err(syntax("expected ~N or @{time} after the name"));
+            }
+        }
+        Ok(revision)
+    }
+}
+
+impl fmt::Display for Revision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Revision::Name(name) => write!(f, "{}", name),
+            Revision::Ancestor(revision, generations) => {
+                write!(f, "{}~{}", revision, generations)
+            }
+            Revision::AsOf(revision, timestamp) => {
+                match Utc.timestamp_opt(*timestamp, 0).single() {
+                    Some(time) => write!(
+                        f,
+                        "{}@{{{}}}",
+                        revision,
+                        time.to_rfc3339_opts(SecondsFormat::Secs, true)
+                    ),
+                    None => write!(f, "{}@{{{}}}", revision, timestamp),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::TimeStampScope;
+    use std::collections::HashSet;
+    use tempdir::TempDir;
+
+    fn parse(text: &str) -> Revision {
+        text.parse().unwrap()
+    }
+
+    fn name(name: &str) -> Box<Revision> {
+        Box::new(Revision::Name(name.into()))
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("main"), *name("main"));
+        assert_eq!(parse("main~"), Revision::Ancestor(name("main"), 1));
+        assert_eq!(parse("3fa9~12"), Revision::Ancestor(name("3fa9"), 12));
+        assert_eq!(
+            parse("main@{2021-12-31}~2"),
+            Revision::Ancestor(Box::new(Revision::AsOf(name("main"), 1640908800)), 2)
+        );
+        assert_eq!(parse("main~2~").to_string(), "main~2~1");
+        assert_eq!(
+            parse("main@{2021-12-31}").to_string(),
+            "main@{2021-12-31T00:00:00Z}"
+        );
+        for invalid in ["", "~1", "main~x", "main@{never}", "main@{5", "main@5"] {
+            assert!(
+                matches!(invalid.parse::<Revision>(), Err(RevisionError::Syntax(..))),
+                "{:?} is invalid",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_resolve() {
+        let _scope = TimeStampScope::new();
+        let dir = TempDir::new("garden-revision").unwrap();
+        let mut chain_store = FsChainStore::<String>::try_new(
+            dir.path().into(),
+            HeadRef::try_from("main").unwrap(),
+        )
+        .unwrap();
+        // The timestamps count up from 0 in tests.
+        let mut hashes = vec![];
+        for i in 0..300 {
+            hashes.push(chain_store.add(i.to_string()).hash.clone());
+            if i % 100 == 99 {
+                chain_store.persist().unwrap();
+            }
+        }
+        let mut resolve = |text: &str| parse(text).resolve(&mut chain_store);
+
+        assert_eq!(resolve("main").unwrap(), hashes[299]);
+        assert_eq!(resolve("main~").unwrap(), hashes[298]);
+        assert_eq!(resolve("main~150").unwrap(), hashes[149]);
+        assert_eq!(resolve("main~299").unwrap(), hashes[0]);
+        assert!(matches!(
+            resolve("main~300"),
+            Err(RevisionError::NoAncestor(_))
+        ));
+        assert_eq!(resolve("main@{120}").unwrap(), hashes[120]);
+        assert_eq!(resolve("main~200@{120}").unwrap(), hashes[99]);
+        assert_eq!(resolve("main@{120}~20").unwrap(), hashes[100]);
+
+        let full = hashes[42].to_string();
+        assert_eq!(resolve(&full).unwrap(), hashes[42]);
+        assert_eq!(resolve(&full[..12]).unwrap(), hashes[42]);
+        assert_eq!(resolve(&full[..12].to_uppercase()).unwrap(), hashes[42]);
+        assert_eq!(resolve(&format!("{}~2", &full[..12])).unwrap(), hashes[40]);
+        assert!(matches!(
+            resolve(&full[..3]),
+            Err(RevisionError::Unknown(_))
+        ));
+        assert!(matches!(resolve("nope"), Err(RevisionError::Unknown(_))));
+
+        // Add blocks to another head until two hashes share their first 4 hex digits.
+        let mut other = FsChainStore::<String>::try_new(
+            dir.path().into(),
+            HeadRef::try_from("other").unwrap(),
+        )
+        .unwrap();
+        let mut prefixes: HashSet<String> = hashes
+            .iter()
+            .map(|hash| hash.to_string()[..4].into())
+            .collect();
+        let prefix = loop {
+            let prefix: String = other.add("other".into()).hash.to_string()[..4].into();
+            if !prefixes.insert(prefix.clone()) {
+                break prefix;
+            }
+        };
+        other.persist().unwrap();
+        assert!(matches!(
+            resolve(&prefix),
+            Err(RevisionError::Ambiguous { matches, .. }) if matches.len() > 1
+        ));
+    }
+}
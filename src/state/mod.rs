@@ -1,33 +1,62 @@
-use crate::{
-    combine_reducers, game::primitives::Position, garden::GardenPlot, reducers, Action,
-};
+use crate::{game::primitives::Position, garden::GardenPlot, reducers, Action};
+use garden_macros::Reducer;
+use im_rc::OrdMap;
 use std::rc::Rc;
+use uuid::Uuid;
 
-#[derive(PartialEq, Debug, Clone)]
+/// The garden plots by their uuid. This is a persistent map, so cloning it for the
+/// next state is cheap, and a plot that didn't change keeps its pointer.
+pub type Plots = OrdMap<Uuid, Rc<GardenPlot>>;
+
+#[derive(PartialEq, Debug, Clone, Reducer)]
+#[reducer(action = Action)]
 pub struct State {
-    my_garden: Option<Rc<GardenPlot>>,
+    #[reducer(reducers::plots)]
+    plots: Plots,
+    #[reducer(reducers::my_garden)]
+    my_garden: Option<Uuid>,
+    #[reducer(reducers::game_tick)]
     game_tick: Option<i64>,
+    #[reducer(reducers::player_position)]
     player_position: Option<Position>,
+    #[reducer(reducers::checkpoint_position)]
+    checkpoint_position: Option<Position>,
+    #[reducer(reducers::unsaved_moves)]
+    unsaved_moves: usize,
+    #[reducer(reducers::camera)]
+    camera: Position,
+    #[reducer(reducers::cursor)]
+    cursor: Option<Position>,
 }
 
 impl State {
     pub fn new() -> Self {
         Self {
+            plots: OrdMap::new(),
             my_garden: None,
             game_tick: Some(0),
             player_position: None,
+            checkpoint_position: None,
+            unsaved_moves: 0,
+            camera: Position::new(0, 0),
+            cursor: None,
         }
     }
 
-    pub fn reduce(&self, action: &Action) -> State {
-        use reducers::*;
-        combine_reducers!(self, State, action, {
-            my_garden: garden,
-            game_tick: game_tick,
-            player_position: player_position
-        })
+    /// Copy the session state, which comes from GameActions rather than the chain,
+    /// from another state. See `Store::apply_remote_blocks`.
+    pub fn with_session_of(&self, other: &State) -> Self {
+        Self {
+            game_tick: other.game_tick,
+            player_position: other.player_position,
+            unsaved_moves: other.unsaved_moves,
+            camera: other.camera,
+            cursor: other.cursor,
+            ..self.clone()
+        }
     }
 }
 
+pub mod selector_stats;
 pub mod selectors;
 mod utils;
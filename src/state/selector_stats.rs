@@ -0,0 +1,77 @@
+//! Counters for how well each selector's memoization is working. The `selector!`
+//! macro records into a thread local registry, which can be queried from code, and
+//! is shown in the devtools overlay.
+
+use std::{cell::RefCell, collections::BTreeMap, time::Duration};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SelectorStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// The total time spent recomputing values on a cache miss.
+    pub recompute_time: Duration,
+}
+
+impl SelectorStats {
+    /// The fraction of calls that were served from the cache.
+    pub fn hit_rate(&self) -> f64 {
+        let calls = self.hits + self.misses;
+        if calls == 0 {
+            return 0.0;
+        }
+        self.hits as f64 / calls as f64
+    }
+
+    pub fn average_recompute_time(&self) -> Duration {
+        if self.misses == 0 {
+            return Duration::ZERO;
+        }
+        self.recompute_time / self.misses as u32
+    }
+}
+
+thread_local! {
+    static REGISTRY: RefCell<BTreeMap<&'static str, SelectorStats>> =
+        const { RefCell::new(BTreeMap::new()) };
+}
+
+pub fn record_hit(name: &'static str) {
+    #[cfg(feature = "selector-cache-log")]
+    println!("selector {} - cache hit", name);
+
+    REGISTRY.with(|registry| {
+        registry.borrow_mut().entry(name).or_default().hits += 1;
+    });
+}
+
+pub fn record_miss(name: &'static str, recompute_time: Duration) {
+    #[cfg(feature = "selector-cache-log")]
+    println!("selector {} - cache miss", name);
+
+    REGISTRY.with(|registry| {
+        let mut registry = registry.borrow_mut();
+        let stats = registry.entry(name).or_default();
+        stats.misses += 1;
+        stats.recompute_time += recompute_time;
+    });
+}
+
+/// The stats for a single selector, if it has been called.
+pub fn get(name: &str) -> Option<SelectorStats> {
+    REGISTRY.with(|registry| registry.borrow().get(name).copied())
+}
+
+/// The stats for every selector that has been called, sorted by name.
+pub fn all() -> Vec<(&'static str, SelectorStats)> {
+    REGISTRY.with(|registry| {
+        registry
+            .borrow()
+            .iter()
+            .map(|(name, stats)| (*name, *stats))
+            .collect()
+    })
+}
+
+pub fn reset() {
+    REGISTRY.with(|registry| registry.borrow_mut().clear());
+}
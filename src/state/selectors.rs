@@ -7,13 +7,24 @@ use crate::{
         primitives::{BBox, Entity, Position, Size},
     },
     garden::GardenPlot,
-    selector, Hash, State,
+    selector,
+    state::Plots,
+    Hash, State,
 };
 use rltk::{Rltk, RGB};
 use std::{cell::RefCell, rc::Rc};
+use uuid::Uuid;
+
+pub fn get_plots(state: Rc<State>) -> Plots {
+    state.plots.clone()
+}
+
+pub fn get_plot(state: Rc<State>, uuid: Uuid) -> Option<Rc<GardenPlot>> {
+    state.plots.get(&uuid).cloned()
+}
 
 pub fn get_my_garden(state: Rc<State>) -> Option<Rc<GardenPlot>> {
-    state.my_garden.clone()
+    get_plot(state.clone(), state.my_garden?)
 }
 
 pub fn get_game_tick(state: Rc<State>) -> Option<i64> {
@@ -21,18 +32,19 @@ pub fn get_game_tick(state: Rc<State>) -> Option<i64> {
 }
 
 selector!(
-    pub fn get_drawable_garden(state: Rc<State>) -> Option<Rc<DrawableGarden>> {
-        memoize |plot: get_my_garden -> Option<Rc<GardenPlot>>| {
-            if let Some(ref plot) = plot {
-            eprintln!("Generating empty hash.");
+    pub fn get_drawable_garden_by_uuid(state: Rc<State>, uuid: Uuid) -> Option<Rc<DrawableGarden>> {
+        memoize(lru = 256) |plot: get_plot(uuid) -> Option<Rc<GardenPlot>>| {
+            let plot = plot?;
             let todo = Hash::empty();
-            return Some(Rc::from(DrawableGarden::new(GardenPlot::get_default_bbox(), todo, plot.clone())));
-            }
-            None
+            Some(Rc::new(DrawableGarden::new(GardenPlot::get_default_bbox(), todo, plot)))
         }
     }
 );
 
+pub fn get_drawable_garden(state: Rc<State>) -> Option<Rc<DrawableGarden>> {
+    get_drawable_garden_by_uuid(state.clone(), state.my_garden?)
+}
+
 fn get_player_is_some(state: Rc<State>) -> bool {
     state.player_position.is_some()
 }
@@ -51,18 +63,79 @@ selector!(
     }
 );
 
-selector!(
-    pub fn get_drawable_gardens(state: Rc<State>) -> Rc<Vec<Rc<DrawableGarden>>> {
-        memoize |my_garden: get_drawable_garden -> Option<Rc<DrawableGarden>>| {
-            let mut gardens = vec![];
-            if let Some(ref my_garden) = my_garden {
-                gardens.push(my_garden.clone());
-            }
-            Rc::from(gardens)
-        }
-    }
-);
+/// The drawable gardens for every plot. Each one is memoized separately, so only the
+/// plots that changed are rebuilt.
+pub fn get_drawable_gardens(state: Rc<State>) -> Vec<Rc<DrawableGarden>> {
+    state
+        .plots
+        .keys()
+        .filter_map(|uuid| get_drawable_garden_by_uuid(state.clone(), *uuid))
+        .collect()
+}
 
 pub fn get_player_position(state: Rc<State>) -> Option<Position> {
     state.player_position
 }
+
+pub fn get_checkpoint_position(state: Rc<State>) -> Option<Position> {
+    state.checkpoint_position
+}
+
+pub fn get_unsaved_moves(state: Rc<State>) -> usize {
+    state.unsaved_moves
+}
+
+pub fn get_camera(state: Rc<State>) -> Position {
+    state.camera
+}
+
+pub fn get_cursor(state: Rc<State>) -> Option<Position> {
+    state.cursor
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{actions, Action, ChainAction};
+
+    #[test]
+    fn test_unchanged_plots_are_shared() {
+        let plot_a = GardenPlot::new("A".into());
+        let plot_b = GardenPlot::new("B".into());
+        let create = |plot: &GardenPlot| -> Action {
+            ChainAction::CreatePlot(plot.clone()).into()
+        };
+
+        let state = Rc::new(State::new().reduce(&create(&plot_a)));
+        let garden_a = get_drawable_garden_by_uuid(state.clone(), plot_a.uuid).unwrap();
+        assert!(Rc::ptr_eq(
+            &garden_a,
+            &get_drawable_garden(state.clone()).unwrap()
+        ));
+
+        let next = Rc::new(state.reduce(&actions::tick_game()));
+        assert!(
+            get_plots(next.clone()).ptr_eq(&get_plots(state.clone())),
+            "The plots are untouched by other actions."
+        );
+
+        let next = Rc::new(next.reduce(&create(&plot_b)));
+        assert!(!get_plots(next.clone()).ptr_eq(&get_plots(state)));
+        assert!(
+            Rc::ptr_eq(
+                &garden_a,
+                &get_drawable_garden_by_uuid(next.clone(), plot_a.uuid).unwrap()
+            ),
+            "The unchanged plot is still memoized."
+        );
+
+        let gardens = get_drawable_gardens(next.clone());
+        assert_eq!(gardens.len(), 2);
+        assert!(gardens.iter().any(|garden| Rc::ptr_eq(garden, &garden_a)));
+        assert_eq!(
+            get_my_garden(next).unwrap().uuid,
+            plot_a.uuid,
+            "The first plot stays the player's garden."
+        );
+    }
+}
@@ -5,8 +5,9 @@ use std::rc::Rc;
 pub trait MaybeRc<T> {
     fn cache_is_some(&self) -> bool;
     fn are_contents_copyable(&self) -> bool;
-    fn cache_unwrap(&self) -> &Rc<T>;
     fn unwrap_copyable_value(&self) -> T;
+    /// Compare the pointers of two values that are not copyable.
+    fn cache_ptr_eq(&self, other: &Self) -> bool;
 }
 
 impl<T> MaybeRc<T> for Option<Rc<T>> {
@@ -22,10 +23,11 @@ impl<T> MaybeRc<T> for Option<Rc<T>> {
         panic!("Logic error, Rc<T> is not copyable.");
     }
 
-    fn cache_unwrap(&self) -> &Rc<T> {
-        &self
-            .as_ref()
-            .expect("Logic error, cache unwrapping failed.")
+    fn cache_ptr_eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+            _ => panic!("Logic error, cache unwrapping failed."),
+        }
     }
 }
 
@@ -42,8 +44,8 @@ impl<T: Copy> MaybeRc<T> for T {
         *self
     }
 
-    fn cache_unwrap(&self) -> &Rc<T> {
-        panic!("Logic error, should not unwrap a copyable type.");
+    fn cache_ptr_eq(&self, other: &Self) -> bool {
+        panic!("Logic error, should not compare pointers of a copyable type.");
     }
 }
 
@@ -60,8 +62,8 @@ impl<T: Copy> MaybeRc<T> for Option<T> {
         panic!("Logic error, Rc<T> is not copyable.");
     }
 
-    fn cache_unwrap(&self) -> &Rc<T> {
-        panic!("Logic error, should not unwrap a copyable type.");
+    fn cache_ptr_eq(&self, other: &Self) -> bool {
+        panic!("Logic error, should not compare pointers of a copyable type.");
     }
 }
 
@@ -78,11 +80,36 @@ impl<T> MaybeRc<T> for Rc<T> {
         panic!("Logic error, Rc<T> is not copyable.");
     }
 
-    fn cache_unwrap(&self) -> &Rc<T> {
-        self
+    fn cache_ptr_eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(self, other)
     }
 }
 
+/// Compare a selector argument with its cached value, using the MaybeRc semantics.
+/// Copyable values are compared directly, and everything else by pointer.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! selector_arg_matches {
+    ($value:expr, $cached:expr) => {
+        // The emptiness matches.
+        $value.cache_is_some() == $cached.cache_is_some()
+            && (
+                // There is no value to consider.
+                !$value.cache_is_some() ||
+
+                // Determine how to compare the values.
+                if $value.are_contents_copyable() {
+                    // This is something like i64 or Option<i64>, directly compare the
+                    // values.
+                    $value.unwrap_copyable_value() == $cached.unwrap_copyable_value()
+                } else {
+                    // Check the pointers for equality.
+                    $value.cache_ptr_eq(&$cached)
+                }
+            )
+    };
+}
+
 // Create a selector
 #[macro_export]
 macro_rules! selector {
@@ -147,31 +174,11 @@ macro_rules! selector {
                         $( ref [<cached_ $selector_var_name>] ),*
                     )) = *f.borrow() {
                         $(
-                            if cache_matches {
-                                if (
-                                    // The emptiness doesn't match.
-                                    $selector_var_name.cache_is_some() != [<cached_ $selector_var_name>].cache_is_some()
-                                ) || (
-                                    // There is a value to consider here.
-                                    $selector_var_name.cache_is_some() &&
-
-                                    // Determine how to compare the values.
-                                    if $selector_var_name.are_contents_copyable() {
-                                        // This is something like i64 or Option<i64>,
-                                        // directly compare the values.
-                                        $selector_var_name.unwrap_copyable_value()
-                                        != [<cached_ $selector_var_name>].unwrap_copyable_value()
-                                    } else {
-                                        // Check the pointers for equality.
-                                        !Rc::ptr_eq(
-                                            $selector_var_name.cache_unwrap(),
-                                            [<cached_ $selector_var_name>].cache_unwrap()
-                                        )
-                                    }
-
-                                ){
-                                    cache_matches = false;
-                                }
+                            if cache_matches && !$crate::selector_arg_matches!(
+                                $selector_var_name,
+                                [<cached_ $selector_var_name>]
+                            ) {
+                                cache_matches = false;
                             }
                         )*
                     } else {
@@ -194,13 +201,13 @@ macro_rules! selector {
                         result = (*f.borrow()).clone();
                     });
 
-                    #[cfg(feature = "selector-cache-log")]
-                    println!("selector {} - cache hit", stringify!($fn_name));
+                    crate::state::selector_stats::record_hit(stringify!($fn_name));
 
                     return result.expect("Logic error, failed to get returns from cache.");
                 }
 
 
+                let recompute_start = std::time::Instant::now();
                 let return_value: $Returns = [<selector_ $fn_name _impl>]($( $selector_var_name ),*);
 
                 let ref returns_cache = [<SELECTOR_ $fn_name:upper _RETURNS_CACHE>];
@@ -208,8 +215,10 @@ macro_rules! selector {
                     *f.borrow_mut() = Some(return_value.clone());
                 });
 
-                #[cfg(feature = "selector-cache-log")]
-                println!("selector {} - cache miss", stringify!($fn_name));
+                crate::state::selector_stats::record_miss(
+                    stringify!($fn_name),
+                    recompute_start.elapsed(),
+                );
 
                 return_value
             }
@@ -221,30 +230,120 @@ macro_rules! selector {
             }
         }
     }};
-}
 
-/// Combine the reducers
-///
-/// TODO - Return the old state if nothing has changed.
-#[macro_export]
-macro_rules! combine_reducers {
+    // A selector with a key argument, e.g. a uuid. The most recently used results are
+    // cached per key, up to the capacity. The key must be Clone + PartialEq. The key
+    // can be passed on to other keyed selectors, e.g. `plot: get_plot(uuid) -> T`.
     (
-        $self:ident,
-        $state:ident,
-        $action:ident,
-        {
-            $( $member:ident: $reducer:ident ),*
+        pub fn $fn_name:ident(state: $State:ty, $key:ident: $Key:ty) -> $Returns:ty {
+            memoize(lru = $capacity:expr) |
+                $(
+                    $selector_var_name:ident: $selector_fn_name:ident
+                    $( ($selector_key:ident) )? -> $SelectorReturns:ty
+                ),*
+            |
+            $contents:tt
         }
-    ) => {
-        {
-            $(
-                assert_fields!($state: $member);
-            )*
-            $state {
-                $( $member: $reducer($self.$member.clone(), $action) ),*
+    ) => { paste::paste! {
+        pub fn $fn_name(state: $State, $key: $Key) -> $Returns {
+            // Call out to the $fn_name_selector_impl module for proper macro hygiene.
+            [<$fn_name _selector_impl>]::$fn_name(state, $key)
+        }
+
+        mod [<$fn_name _selector_impl>] {
+            use super::*;
+            use std::rc::Rc;
+            use std::cell::RefCell;
+            use std::option::Option;
+            use crate::state::utils::MaybeRc;
+
+            // e.g. the tuple: (ArgTypeA, ArgTypeB)
+            type ArgsCacheTuple = ( $( $SelectorReturns ),* );
+
+            pub const CAPACITY: usize = $capacity;
+
+            thread_local! {
+                // The cache entries, with the most recently used first:
+                // ```
+                // pub static SELECTOR_GET_STATE_LRU_CACHE:
+                //     RefCell<Vec<(Key, (Arg1, Arg2), ReturnType)>> = RefCell::new(vec![]);
+                // ```
+                pub static [<SELECTOR_ $fn_name:upper _LRU_CACHE>]: RefCell<Vec<($Key, ArgsCacheTuple, $Returns)>> = RefCell::new(Vec::new());
+            }
+
+            #[inline]
+            pub fn $fn_name(state: $State, $key: $Key) -> $Returns {
+                let cache = &[<SELECTOR_ $fn_name:upper _LRU_CACHE>];
+
+                // Get the selector values.
+                $(
+                    let $selector_var_name = $selector_fn_name(
+                        state.clone()
+                        $( , $selector_key.clone() )?
+                    );
+                )*
+
+                let cached: Option<$Returns> = cache.with(|f| {
+                    let mut entries = f.borrow_mut();
+                    let index = entries.iter().position(|(cached_key, ..)| *cached_key == $key)?;
+                    // Take the entry out, it either moves to the front or is stale.
+                    let entry = entries.remove(index);
+                    let mut cache_matches = true;
+                    {
+                        let ( $( [<cached_ $selector_var_name>] ),* ) = &entry.1;
+                        $(
+                            if cache_matches && !$crate::selector_arg_matches!(
+                                $selector_var_name,
+                                [<cached_ $selector_var_name>]
+                            ) {
+                                cache_matches = false;
+                            }
+                        )*
+                    }
+                    if !cache_matches {
+                        return None;
+                    }
+                    let result = entry.2.clone();
+                    entries.insert(0, entry);
+                    Some(result)
+                });
+
+                // This is a cache hit, return from the cache.
+                if let Some(result) = cached {
+                    crate::state::selector_stats::record_hit(stringify!($fn_name));
+
+                    return result;
+                }
+
+                let recompute_start = std::time::Instant::now();
+                let return_value: $Returns = [<selector_ $fn_name _impl>](
+                    $key.clone(),
+                    $( $selector_var_name.clone() ),*
+                );
+
+                cache.with(|f| {
+                    let mut entries = f.borrow_mut();
+                    entries.insert(0, ($key, ( $( $selector_var_name ),* ), return_value.clone()));
+                    // Evict the least recently used entries.
+                    entries.truncate(CAPACITY);
+                });
+
+                crate::state::selector_stats::record_miss(
+                    stringify!($fn_name),
+                    recompute_start.elapsed(),
+                );
+
+                return_value
+            }
+
+            pub fn [<selector_ $fn_name _impl>](
+                $key: $Key,
+                $( $selector_var_name: $SelectorReturns ),*
+            ) -> $Returns {
+                $contents
             }
         }
-    }
+    }};
 }
 
 #[cfg(test)]
@@ -405,4 +504,58 @@ pub mod test {
             "But the pointers are now different as it was re-computed."
         );
     }
+
+    selector!(
+        pub fn get_scaled_bbox(state: Rc<TestState>, scale: i32) -> Rc<BBox<i32>> {
+            memoize(lru = 2) |bbox: get_bbox -> Rc<BBox<i32>>| {
+                Rc::from(BBox {
+                    top_left: bbox.top_left,
+                    size: Size::new(bbox.size.x * scale, bbox.size.y * scale),
+                })
+            }
+        }
+    );
+
+    #[test]
+    fn test_keyed_selector() {
+        let state = Rc::from(TestState {
+            position: Rc::new(Position::new(1, 1)),
+            size: Rc::new(Size::new(5, 7)),
+        });
+
+        let scaled_2 = get_scaled_bbox(state.clone(), 2);
+        assert_eq!(scaled_2.size, Size::new(10, 14));
+        let scaled_3 = get_scaled_bbox(state.clone(), 3);
+        assert_eq!(scaled_3.size, Size::new(15, 21));
+
+        assert!(
+            Rc::ptr_eq(&scaled_2, &get_scaled_bbox(state.clone(), 2)),
+            "Each key is cached separately."
+        );
+        assert!(Rc::ptr_eq(&scaled_3, &get_scaled_bbox(state.clone(), 3)));
+
+        // Key 2 is now the least recently used, so it gets evicted.
+        get_scaled_bbox(state.clone(), 4);
+        assert!(Rc::ptr_eq(&scaled_3, &get_scaled_bbox(state.clone(), 3)));
+        assert!(
+            !Rc::ptr_eq(&scaled_2, &get_scaled_bbox(state.clone(), 2)),
+            "The least recently used key was evicted."
+        );
+
+        // Changing the state invalidates the cached value for a key.
+        let state_2 = Rc::from(TestState {
+            position: Rc::new(Position::new(0, 0)),
+            size: state.size.clone(),
+        });
+        let scaled_2b = get_scaled_bbox(state_2.clone(), 2);
+        assert_eq!(scaled_2b.top_left, Position::new(0, 0));
+        assert!(Rc::ptr_eq(&scaled_2b, &get_scaled_bbox(state_2, 2)));
+
+        let stats = crate::selector_stats::get("get_scaled_bbox").unwrap();
+        assert_eq!(
+            (stats.hits, stats.misses),
+            (4, 5),
+            "The stats were recorded."
+        );
+    }
 }
@@ -1,20 +1,147 @@
-use std::rc::Rc;
+use std::{fmt, rc::Rc};
+use thiserror::Error;
 
-use anyhow::{bail, Result};
+use crate::{
+    block_chain::{Block, ReconcileError},
+    chain_store::{check_block, ChainStoreError, LoadError},
+    garden::GardenPlot,
+    middleware::Middleware,
+    reducers, Action, ChainAction, ChainStore, Hash, State,
+};
 
-use crate::{garden::GardenPlot, reducers, Action, ChainAction, ChainStore, Hash, State};
+/// Notable things that happened in the store, for surfacing to the user.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StoreEvent {
+    /// The chain was loaded from storage, with this many blocks.
+    Loaded(usize),
+    /// A block was added to the chain.
+    BlockAdded { hash: Hash, action: &'static str },
+    /// This many blocks were persisted.
+    Persisted(usize),
+    /// Remote blocks were applied, see `RemoteChanges`.
+    RemoteBlocks(RemoteChanges),
+}
+
+/// What changed when applying remote blocks, so that the UI can react.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteChanges {
+    /// The index of the first block that was added.
+    pub fork_index: usize,
+    /// How many local blocks were replaced by remote ones.
+    pub removed: usize,
+    /// How many remote blocks were added.
+    pub added: usize,
+}
+
+impl fmt::Display for StoreEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreEvent::Loaded(count) => write!(f, "Loaded {} blocks", count),
+            StoreEvent::BlockAdded { hash, action } => {
+                write!(f, "Added block {} ({})", hash.short(), action)
+            }
+            StoreEvent::Persisted(count) => write!(f, "Saved {} blocks", count),
+            StoreEvent::RemoteBlocks(RemoteChanges {
+                removed: 0, added, ..
+            }) => {
+                write!(f, "Received {} blocks", added)
+            }
+            StoreEvent::RemoteBlocks(RemoteChanges { removed, added, .. }) => {
+                write!(f, "Received {} blocks, replacing {}", added, removed)
+            }
+        }
+    }
+}
+
+/// Errors from applying blocks from a remote peer, see `Store::apply_remote_blocks`.
+#[derive(Error, Debug, PartialEq, Clone)]
+pub enum ApplyRemoteError {
+    #[error(transparent)]
+    Reconcile(#[from] ReconcileError),
+    #[error("the chain forked at block {fork_index}, but the state only has {count}")]
+    StateBehindChain { fork_index: usize, count: usize },
+}
+
+/// How many blocks apart the states kept for rewinding are, see `BlockStates`.
+pub const SNAPSHOT_INTERVAL: usize = 64;
+
+/// Snapshots of the state after every SNAPSHOT_INTERVAL blocks, for rewinding to a
+/// fork point. Keeping the state after every block would grow without bound, so the
+/// blocks after the nearest snapshot are reduced again from the chain instead.
+#[derive(Debug, Default)]
+struct BlockStates {
+    /// The number of blocks the state has been reduced from.
+    count: usize,
+    /// The state after SNAPSHOT_INTERVAL blocks, after twice as many, and so on.
+    snapshots: Vec<Rc<State>>,
+}
+
+impl BlockStates {
+    fn push(&mut self, state: &Rc<State>) {
+        self.count += 1;
+        // `usize::is_multiple_of` would need Rust 1.87.
+        #[allow(clippy::manual_is_multiple_of)]
+        if self.count % SNAPSHOT_INTERVAL == 0 {
+            self.snapshots.push(state.clone());
+        }
+    }
+
+    /// Forget the blocks from the index on, and return the latest snapshot before it
+    /// with the number of blocks it was reduced from.
+    fn truncate(&mut self, index: usize) -> (usize, Rc<State>) {
+        self.count = index;
+        self.snapshots.truncate(index / SNAPSHOT_INTERVAL);
+        match self.snapshots.last() {
+            Some(state) => (self.snapshots.len() * SNAPSHOT_INTERVAL, state.clone()),
+            None => (0, Rc::new(State::new())),
+        }
+    }
+}
+
+/// Called after every action with the action, and the previous and next state. The
+/// store is still dispatching, so a subscriber can only react to the state, and not
+/// persist or dispatch. Work that needs the store, like autosave, or the StoreEvents
+/// from persisting and remote blocks, is polled instead, see `take_events`.
+pub type Subscriber = Box<dyn FnMut(&Action, &Rc<State>, &Rc<State>)>;
+
+/// Returned from `Store::subscribe`, and used to unsubscribe.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SubscriptionId(usize);
 
-#[derive(Debug)]
 pub struct Store {
     pub chains: Box<dyn ChainStore<ChainAction>>,
     state: Rc<State>,
+    block_states: BlockStates,
+    events: Vec<StoreEvent>,
+    middleware: Vec<Box<dyn Middleware>>,
+    subscribers: Vec<(SubscriptionId, Subscriber)>,
+    next_subscription_id: usize,
+}
+
+impl fmt::Debug for Store {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Store")
+            .field("chains", &self.chains)
+            .field("state", &self.state)
+            .field("events", &self.events)
+            .field("middleware", &self.middleware.len())
+            .field("subscribers", &self.subscribers.len())
+            .finish()
+    }
 }
 
 impl Store {
-    pub fn try_new(chain_store: Box<dyn ChainStore<ChainAction>>) -> Result<Self> {
+    pub fn try_new(
+        chain_store: Box<dyn ChainStore<ChainAction>>,
+    ) -> Result<Self, LoadError> {
         let mut store = Self {
             chains: chain_store,
             state: Rc::new(State::new()),
+            block_states: BlockStates::default(),
+            events: Vec::new(),
+            middleware: Vec::new(),
+            subscribers: Vec::new(),
+            next_subscription_id: 0,
         };
 
         store.load_untrusted_chain_store()?;
@@ -22,54 +149,138 @@ impl Store {
         Ok(store)
     }
 
+    /// Middleware runs in the order it was added.
+    pub fn add_middleware(&mut self, middleware: Box<dyn Middleware>) {
+        self.middleware.push(middleware);
+    }
+
+    pub fn subscribe(
+        &mut self,
+        subscriber: impl FnMut(&Action, &Rc<State>, &Rc<State>) + 'static,
+    ) -> SubscriptionId {
+        let id = SubscriptionId(self.next_subscription_id);
+        self.next_subscription_id += 1;
+        self.subscribers.push((id, Box::new(subscriber)));
+        id
+    }
+
+    pub fn unsubscribe(&mut self, id: SubscriptionId) {
+        self.subscribers.retain(|(other, _)| *other != id);
+    }
+
     pub fn dispatch(&mut self, action: Action) {
-        self.state = Rc::from(self.state.reduce(&action));
-        #[cfg(feature = "store-log")]
-        {
-            if let Action::Chain(ref action) = action {
-                colour::cyan!("[dispatch] ");
-                println!("{:#?}", action);
-                colour::yellow!("[state] ");
-                println!("state {:#?}", *self.state);
-            }
+        let prev = reduce(&mut self.middleware, &mut self.state, &action);
+        if let Action::Chain(ref action) = action {
+            self.block_states.push(&self.state);
+            let block = self.chains.add(action.clone());
+            self.events.push(StoreEvent::BlockAdded {
+                hash: block.hash.clone(),
+                action: action.name(),
+            });
         }
-        if let Action::Chain(action) = action {
-            self.chains.add(action);
+        self.notify(&action, &prev);
+    }
+
+    fn notify(&mut self, action: &Action, prev: &Rc<State>) {
+        notify(
+            &mut self.middleware,
+            &mut self.subscribers,
+            action,
+            prev,
+            &self.state,
+        );
+    }
+
+    /// Persist the chain to its backing storage.
+    pub fn persist(&mut self) -> Result<(), ChainStoreError> {
+        let count = self.chains.unpersisted_block_count();
+        self.chains.persist()?;
+        if count > 0 {
+            self.events.push(StoreEvent::Persisted(count));
         }
+        Ok(())
     }
 
-    pub fn load_untrusted_chain_store(&mut self) -> Result<()> {
+    /// Take the events that have happened since the last call.
+    pub fn take_events(&mut self) -> Vec<StoreEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub fn load_untrusted_chain_store(&mut self) -> Result<(), LoadError> {
         let mut prev_hash = Hash::empty();
+        let mut count = 0;
 
-        for block in self.chains.iter_all()? {
-            if prev_hash != block.payload.parent {
-                bail!(
-                    "A block did not match the previous hash.\nPrevious: {:#?},\n{:#?}",
-                    prev_hash,
-                    block
-                )
-            }
-            if block.payload.hash() != block.hash {
-                bail!(
-                    "A block's hash did not match.\nComputed: {:#?}\nBlock:{:#?}",
-                    block.payload.hash(),
-                    block
-                )
-            }
+        for (index, block) in self.chains.iter_all()?.enumerate() {
+            count += 1;
+            check_block(index, &prev_hash, block)?;
             prev_hash = block.hash.clone();
 
-            self.state = Rc::from(self.state.reduce(&block.payload.data.clone().into()));
+            let action = block.payload.data.clone().into();
+            let prev = reduce(&mut self.middleware, &mut self.state, &action);
+            self.block_states.push(&self.state);
+            notify(
+                &mut self.middleware,
+                &mut self.subscribers,
+                &action,
+                &prev,
+                &self.state,
+            );
+        }
 
-            #[cfg(feature = "store-log")]
+        if count > 0 {
+            self.events.push(StoreEvent::Loaded(count));
+        }
+
+        Ok(())
+    }
+
+    /// Reconcile blocks from a remote peer with the chain. If they replace local
+    /// history, the state is rewound to the fork point and rebuilt from there, see
+    /// `BlockStates`. Session state, like the player position, is kept as it was.
+    pub fn apply_remote_blocks(
+        &mut self,
+        blocks: &[Block<ChainAction>],
+    ) -> Result<RemoteChanges, ApplyRemoteError> {
+        let fork_index = self.chains.reconcile(blocks)?;
+        let count = self.block_states.count;
+        let removed = count
+            .checked_sub(fork_index)
+            .ok_or(ApplyRemoteError::StateBehindChain { fork_index, count })?;
+        if removed > 0 {
+            let (snapshot_index, mut state) = self.block_states.truncate(fork_index);
+            // These blocks were already seen by the middleware and subscribers.
+            for block in self
+                .chains
+                .iter_loaded()
+                .take(fork_index)
+                .skip(snapshot_index)
             {
-                colour::cyan!("[load-block] ");
-                println!("{:#?}", block.payload.data.clone());
-                colour::yellow!("[state] ");
-                println!("state {:#?}", *self.state);
+                state = Rc::from(state.reduce(&block.payload.data.clone().into()));
             }
+            self.state = Rc::new(state.with_session_of(&self.state));
         }
 
-        Ok(())
+        let actions: Vec<Action> = self
+            .chains
+            .iter_loaded()
+            .skip(fork_index)
+            .map(|block| block.payload.data.clone().into())
+            .collect();
+        for action in &actions {
+            let prev = reduce(&mut self.middleware, &mut self.state, action);
+            self.block_states.push(&self.state);
+            self.notify(action, &prev);
+        }
+
+        let changes = RemoteChanges {
+            fork_index,
+            removed,
+            added: actions.len(),
+        };
+        if changes.added > 0 {
+            self.events.push(StoreEvent::RemoteBlocks(changes.clone()));
+        }
+        Ok(changes)
     }
 
     pub fn state(&self) -> Rc<State> {
@@ -77,14 +288,53 @@ impl Store {
     }
 }
 
+/// Reduce the action through the middleware, and return the previous state. These
+/// are functions rather than methods so that the chain store can be borrowed while
+/// loading.
+fn reduce(
+    middleware: &mut [Box<dyn Middleware>],
+    state: &mut Rc<State>,
+    action: &Action,
+) -> Rc<State> {
+    let prev = state.clone();
+    for middleware in middleware.iter_mut() {
+        middleware.before_reduce(action, &prev);
+    }
+    *state = Rc::from(prev.reduce(action));
+    prev
+}
+
+fn notify(
+    middleware: &mut [Box<dyn Middleware>],
+    subscribers: &mut [(SubscriptionId, Subscriber)],
+    action: &Action,
+    prev: &Rc<State>,
+    next: &Rc<State>,
+) {
+    for middleware in middleware.iter_mut() {
+        middleware.after_reduce(action, prev, next);
+    }
+    for (_, subscriber) in subscribers.iter_mut() {
+        subscriber(action, prev, next);
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::{
         actions,
+        block_chain::BlockChain,
         chain_store::{FsChainStore, HeadRef},
+        game::{
+            game_state::{world_view, GAME_H, GAME_W},
+            input_device::InputDevice,
+            keymap::KeyMap,
+            primitives::Position,
+        },
+        selectors, GameAction,
     };
-    use std::path::PathBuf;
+    use std::{fs, path::PathBuf};
     use tempdir::TempDir;
 
     struct StateStoreTest {
@@ -124,10 +374,7 @@ mod test {
             ..
         } = test;
         store.dispatch(actions::create_garden_plot("The Secret Garden".into()));
-        store
-            .chains
-            .persist()
-            .expect("Failed to persist chain store");
+        store.persist().expect("Failed to persist chain store");
 
         let chains = Box::new(
             FsChainStore::<ChainAction>::try_new(
@@ -141,4 +388,457 @@ mod test {
 
         assert_eq!(store.state, store2.state);
     }
+
+    #[test]
+    fn test_events() {
+        let mut test = StateStoreTest::new();
+        let store = &mut test.store;
+        assert_eq!(store.take_events(), vec![]);
+
+        store.dispatch(actions::tick_game());
+        assert_eq!(
+            store.take_events(),
+            vec![],
+            "Game actions don't add blocks."
+        );
+
+        store.dispatch(actions::create_garden_plot("The Secret Garden".into()));
+        store.persist().expect("Failed to persist chain store");
+        let events = store.take_events();
+        assert!(matches!(
+            events.as_slice(),
+            [
+                StoreEvent::BlockAdded {
+                    action: "CreatePlot",
+                    ..
+                },
+                StoreEvent::Persisted(1)
+            ]
+        ));
+        assert_eq!(store.take_events(), vec![], "The events were taken.");
+    }
+
+    #[test]
+    fn test_player_checkpoint() {
+        let mut test = StateStoreTest::new();
+        let store = &mut test.store;
+        store.dispatch(actions::create_garden_plot("The Secret Garden".into()));
+        let start = selectors::get_player_position(store.state()).unwrap();
+
+        let step = Position::new(1, 0);
+        let mut end = start;
+        for _ in 0..3 {
+            end += step;
+            store.dispatch(GameAction::MovePlayer((end, step)).into());
+        }
+        assert_eq!(
+            store.chains.unpersisted_block_count(),
+            1,
+            "Moving the player does not add blocks."
+        );
+
+        actions::save(store).expect("Failed to save");
+        assert_eq!(
+            store.chains.iter_loaded().next_back().unwrap().payload.data,
+            ChainAction::MovePlayer((end, Position::new(0, 0))),
+            "Saving records where the player left off."
+        );
+        let block_count = store.chains.iter_loaded().count();
+        actions::save(store).expect("Failed to save");
+        assert_eq!(
+            store.chains.iter_loaded().count(),
+            block_count,
+            "No checkpoint is needed when the player hasn't moved."
+        );
+
+        let chains = Box::new(
+            FsChainStore::<ChainAction>::try_new(
+                test.path.clone(),
+                test.store.chains.head_ref().clone(),
+            )
+            .expect("Failed to create ChainStore"),
+        );
+        let store2 = Store::try_new(chains).expect("Failed to create StateStore.");
+        assert_eq!(selectors::get_player_position(store2.state()), Some(end));
+    }
+
+    #[test]
+    fn test_more_plots_keep_the_player() {
+        let mut test = StateStoreTest::new();
+        let store = &mut test.store;
+        store.dispatch(actions::create_garden_plot("The Secret Garden".into()));
+        let position = selectors::get_player_position(store.state())
+            .expect("The first plot places the player.");
+        let step = Position::new(1, 0);
+        store.dispatch(GameAction::MovePlayer((position + step, step)).into());
+
+        store.dispatch(actions::create_garden_plot("The Other Garden".into()));
+        store.dispatch(actions::create_garden_plot("A Remote Garden".into()));
+        assert_eq!(
+            selectors::get_player_position(store.state()),
+            Some(position + step),
+            "The player stays where they were."
+        );
+        assert_eq!(selectors::get_plots(store.state()).len(), 3);
+    }
+
+    #[test]
+    fn test_camera_follows_the_player() {
+        let mut test = StateStoreTest::new();
+        let store = &mut test.store;
+        store.dispatch(actions::create_garden_plot("The Secret Garden".into()));
+        let block_count = store.chains.iter_loaded().count();
+
+        let step = Position::new(1, 0);
+        for _ in 0..GAME_W {
+            let position = selectors::get_player_position(store.state()).unwrap();
+            store.dispatch(GameAction::MovePlayer((position + step, step)).into());
+            actions::follow_player(store);
+        }
+        let position = selectors::get_player_position(store.state()).unwrap();
+        let camera = selectors::get_camera(store.state());
+        assert!(camera.x > 0);
+        assert!(
+            world_view().intersects_point(position - camera),
+            "The player is kept in view."
+        );
+        assert_eq!(
+            store.chains.iter_loaded().count(),
+            block_count,
+            "Moving the camera does not add blocks."
+        );
+
+        actions::save(store).expect("Failed to save");
+        assert_eq!(store.chains.iter_loaded().count(), block_count + 1);
+        let chains = Box::new(
+            FsChainStore::<ChainAction>::try_new(
+                test.path.clone(),
+                test.store.chains.head_ref().clone(),
+            )
+            .expect("Failed to create ChainStore"),
+        );
+        let store2 = Store::try_new(chains).expect("Failed to create StateStore.");
+        assert_eq!(
+            selectors::get_camera(store2.state()),
+            Position::new(0, 0),
+            "The camera is not saved."
+        );
+    }
+
+    #[test]
+    fn test_cursor() {
+        let mut test = StateStoreTest::new();
+        let store = &mut test.store;
+        store.dispatch(actions::create_garden_plot("The Secret Garden".into()));
+        store.dispatch(GameAction::MoveCamera(Position::new(5, -3)).into());
+        store.persist().expect("Failed to persist chain store");
+
+        let mut input_device = InputDevice::new(KeyMap::default());
+        input_device.mouse = Position::new(10, 10);
+        actions::maybe_move_cursor(store, &input_device);
+        assert_eq!(
+            selectors::get_cursor(store.state()),
+            Some(Position::new(15, 7)),
+            "The cursor is in the world, rather than on the screen."
+        );
+        input_device.mouse = Position::new(10, GAME_H - 1);
+        actions::maybe_move_cursor(store, &input_device);
+        assert_eq!(
+            selectors::get_cursor(store.state()),
+            None,
+            "The log is not part of the world."
+        );
+
+        assert_eq!(store.chains.unpersisted_block_count(), 0);
+        assert_eq!(selectors::get_unsaved_moves(store.state()), 0);
+        actions::save(store).expect("Failed to save");
+        assert_eq!(
+            store.chains.unpersisted_block_count(),
+            0,
+            "Moving the cursor is not saved."
+        );
+    }
+
+    #[test]
+    fn test_middleware_and_subscribers() {
+        use std::cell::RefCell;
+
+        struct Recorder(Rc<RefCell<Vec<String>>>);
+        impl Middleware for Recorder {
+            fn before_reduce(&mut self, action: &Action, _state: &Rc<State>) {
+                self.0.borrow_mut().push(format!("before {:?}", action));
+            }
+            fn after_reduce(
+                &mut self,
+                action: &Action,
+                prev: &Rc<State>,
+                next: &Rc<State>,
+            ) {
+                self.0.borrow_mut().push(format!("after {:?}", action));
+            }
+        }
+
+        let mut test = StateStoreTest::new();
+        let store = &mut test.store;
+        let log = Rc::new(RefCell::new(Vec::new()));
+        store.add_middleware(Box::new(Recorder(log.clone())));
+
+        let log2 = log.clone();
+        let id = store.subscribe(move |action, prev, next| {
+            let changed = if Rc::ptr_eq(prev, next) {
+                "same"
+            } else {
+                "changed"
+            };
+            log2.borrow_mut().push(format!("subscriber {}", changed));
+        });
+
+        let action = actions::create_garden_plot("The Secret Garden".into());
+        let action_debug = format!("{:?}", action);
+        store.dispatch(action);
+        assert_eq!(
+            *log.borrow(),
+            vec![
+                format!("before {}", action_debug),
+                format!("after {}", action_debug),
+                String::from("subscriber changed"),
+            ]
+        );
+        assert_eq!(
+            store.chains.unpersisted_block_count(),
+            1,
+            "The chain action was still added."
+        );
+
+        log.borrow_mut().clear();
+        store.unsubscribe(id);
+        store.dispatch(actions::tick_game());
+        assert_eq!(log.borrow().len(), 2, "Only the middleware was called.");
+    }
+
+    #[test]
+    fn test_apply_remote_blocks() {
+        let mut test = StateStoreTest::new();
+        let store = &mut test.store;
+        store.dispatch(actions::create_garden_plot("The Secret Garden".into()));
+        let checkpoint =
+            |x| ChainAction::MovePlayer((Position::new(x, 0), Position::new(0, 0)));
+
+        // The remote shares the first block, and then goes its own way.
+        let mut remote =
+            BlockChain::from(store.chains.iter_loaded().cloned().collect::<Vec<_>>());
+        remote.add_data(checkpoint(2));
+        remote.add_data(checkpoint(3));
+
+        store.dispatch(checkpoint(1).into());
+        store.take_events();
+
+        let changes = store
+            .apply_remote_blocks(&remote.as_block_slice()[1..])
+            .expect("Failed to apply remote blocks");
+        assert_eq!(
+            changes,
+            RemoteChanges {
+                fork_index: 1,
+                removed: 1,
+                added: 2
+            }
+        );
+        assert_eq!(
+            selectors::get_player_position(store.state()),
+            Some(Position::new(3, 0))
+        );
+        assert_eq!(store.take_events(), vec![StoreEvent::RemoteBlocks(changes)]);
+
+        // Appending to the tip doesn't rewind anything.
+        remote.add_data(checkpoint(4));
+        let changes = store
+            .apply_remote_blocks(&remote.as_block_slice()[3..])
+            .expect("Failed to apply remote blocks");
+        assert_eq!(
+            changes,
+            RemoteChanges {
+                fork_index: 3,
+                removed: 0,
+                added: 1
+            }
+        );
+
+        // The result is the same as loading the chain from scratch.
+        store.persist().expect("Failed to persist chain store");
+        let chains = Box::new(
+            FsChainStore::<ChainAction>::try_new(
+                test.path.clone(),
+                test.store.chains.head_ref().clone(),
+            )
+            .expect("Failed to create ChainStore"),
+        );
+        let store2 = Store::try_new(chains).expect("Failed to create StateStore.");
+        assert_eq!(test.store.state, store2.state);
+    }
+
+    #[test]
+    fn test_apply_remote_blocks_between_snapshots() {
+        let mut test = StateStoreTest::new();
+        let store = &mut test.store;
+        let checkpoint =
+            |x| ChainAction::MovePlayer((Position::new(x, 0), Position::new(0, 0)));
+        for x in 0..(SNAPSHOT_INTERVAL * 2 + 10) as i32 {
+            if x as usize == SNAPSHOT_INTERVAL + 2 {
+                store.dispatch(actions::create_garden_plot("Local".into()));
+            } else {
+                store.dispatch(checkpoint(x).into());
+            }
+        }
+        assert_eq!(
+            store.block_states.snapshots.len(),
+            2,
+            "Only every SNAPSHOT_INTERVAL blocks is kept."
+        );
+
+        // Fork a few blocks after the first snapshot, and after the local plot.
+        let fork_index = SNAPSHOT_INTERVAL + 5;
+        let mut remote = BlockChain::from(
+            store
+                .chains
+                .iter_loaded()
+                .take(fork_index)
+                .cloned()
+                .collect::<Vec<_>>(),
+        );
+        for _ in 0..SNAPSHOT_INTERVAL * 2 {
+            remote.add_data(ChainAction::CreatePlot(GardenPlot::new("Remote".into())));
+        }
+        let changes = store
+            .apply_remote_blocks(&remote.as_block_slice()[fork_index..])
+            .expect("Failed to apply remote blocks");
+        assert_eq!(changes.removed, SNAPSHOT_INTERVAL + 5);
+        assert_eq!(store.block_states.count, fork_index + SNAPSHOT_INTERVAL * 2);
+        assert_eq!(store.block_states.snapshots.len(), 3);
+
+        // The blocks between the snapshot and the fork point were reduced again, so
+        // apart from the session, the result is the same as loading the chain from
+        // scratch.
+        store.persist().expect("Failed to persist chain store");
+        let chains = Box::new(
+            FsChainStore::<ChainAction>::try_new(
+                test.path.clone(),
+                test.store.chains.head_ref().clone(),
+            )
+            .expect("Failed to create ChainStore"),
+        );
+        let store2 = Store::try_new(chains).expect("Failed to create StateStore.");
+        assert_eq!(
+            *test.store.state,
+            store2.state.with_session_of(&test.store.state)
+        );
+    }
+
+    #[test]
+    fn test_apply_remote_blocks_keeps_the_session() {
+        let mut test = StateStoreTest::new();
+        let store = &mut test.store;
+        store.dispatch(actions::create_garden_plot("The Secret Garden".into()));
+        let mut remote =
+            BlockChain::from(store.chains.iter_loaded().cloned().collect::<Vec<_>>());
+        remote.add_data(ChainAction::CreatePlot(GardenPlot::new("Remote 1".into())));
+        remote.add_data(ChainAction::CreatePlot(GardenPlot::new("Remote 2".into())));
+
+        store.dispatch(actions::create_garden_plot("Local".into()));
+        let position = selectors::get_player_position(store.state()).unwrap();
+        let step = Position::new(0, 1);
+        store.dispatch(GameAction::MovePlayer((position + step, step)).into());
+        store.dispatch(GameAction::MoveCamera(Position::new(2, 3)).into());
+        store.dispatch(GameAction::MoveCursor(Some(Position::new(4, 5))).into());
+        let session = store.state();
+
+        let changes = store
+            .apply_remote_blocks(&remote.as_block_slice()[1..])
+            .expect("Failed to apply remote blocks");
+        assert_eq!(changes.removed, 1, "The local plot was rewound.");
+        let state = store.state();
+        assert_eq!(selectors::get_plots(state.clone()).len(), 3);
+        assert_eq!(
+            selectors::get_player_position(state.clone()),
+            Some(position + step),
+            "The player doesn't go back to their last checkpoint."
+        );
+        assert_eq!(selectors::get_unsaved_moves(state.clone()), 1);
+        assert_eq!(
+            selectors::get_camera(state.clone()),
+            selectors::get_camera(session.clone())
+        );
+        assert_eq!(selectors::get_cursor(state), selectors::get_cursor(session));
+    }
+
+    #[test]
+    fn test_apply_remote_blocks_state_behind_chain() {
+        let mut test = StateStoreTest::new();
+        let store = &mut test.store;
+        store.dispatch(actions::create_garden_plot("The Secret Garden".into()));
+        let mut remote =
+            BlockChain::from(store.chains.iter_loaded().cloned().collect::<Vec<_>>());
+        remote.add_data(ChainAction::CreatePlot(GardenPlot::new("Remote".into())));
+
+        store.block_states.count = 0;
+        assert_eq!(
+            store.apply_remote_blocks(&remote.as_block_slice()[1..]),
+            Err(ApplyRemoteError::StateBehindChain {
+                fork_index: 1,
+                count: 0
+            })
+        );
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let test = StateStoreTest::new();
+        let StateStoreTest {
+            mut store, path, ..
+        } = test;
+        let checkpoint =
+            |x| ChainAction::MovePlayer((Position::new(x, 0), Position::new(0, 0)));
+        store.dispatch(actions::create_garden_plot("The Secret Garden".into()));
+        store.persist().expect("Failed to persist chain store");
+        store.dispatch(checkpoint(1).into());
+        store.dispatch(checkpoint(2).into());
+        store.persist().expect("Failed to persist chain store");
+
+        let chunk_path = |hash: &Hash| {
+            let hash = String::from(hash);
+            path.join("chains").join(&hash[0..2]).join(&hash[2..])
+        };
+        let hashes: Vec<Hash> =
+            store.chains.iter_loaded().map(|b| b.hash.clone()).collect();
+        let load = || {
+            let chains = FsChainStore::<ChainAction>::try_new(
+                path.clone(),
+                store.chains.head_ref().clone(),
+            )
+            .expect("Failed to create ChainStore");
+            Store::try_new(Box::new(chains))
+        };
+
+        // Tamper with a block in the second chunk.
+        let tip_path = chunk_path(&hashes[2]);
+        let original = fs::read_to_string(&tip_path).unwrap();
+        let mut blocks: serde_json::Value = serde_json::from_str(&original).unwrap();
+        blocks[0]["payload"]["timestamp"] = 1000.into();
+        fs::write(&tip_path, blocks.to_string()).unwrap();
+        match load() {
+            Err(LoadError::HashMismatch { index, actual, .. }) => {
+                assert_eq!(index, 1);
+                assert_eq!(actual, hashes[1]);
+            }
+            other => panic!("Expected a hash mismatch, got {:?}", other),
+        }
+        fs::write(&tip_path, original).unwrap();
+
+        // Remove the first chunk.
+        fs::remove_file(chunk_path(&hashes[0])).unwrap();
+        match load() {
+            Err(LoadError::MissingChunk { hash, .. }) => assert_eq!(hash, hashes[0]),
+            other => panic!("Expected a missing chunk, got {:?}", other),
+        }
+    }
 }
@@ -0,0 +1,262 @@
+//! The Store is not thread safe, as State and the selector caches are built on Rc
+//! and thread locals. Rather than making all of that Send + Sync, the Store lives on
+//! one thread inside of a StoreActor. Other threads, like the networking task, talk
+//! to it through a StoreHandle, which is a cheap to clone channel sender.
+
+use std::{
+    sync::mpsc::{self, Receiver, Sender},
+    thread::{self, JoinHandle},
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::{block_chain::Block, store::RemoteChanges, Action, ChainAction, Store};
+
+type Query = Box<dyn FnOnce(&Store) + Send>;
+
+enum Request {
+    Dispatch(Action),
+    Query(Query),
+    Persist(Sender<Result<()>>),
+    ApplyRemoteBlocks(Vec<Block<ChainAction>>, Sender<Result<RemoteChanges>>),
+}
+
+/// A handle to a StoreActor, which can be sent to other threads.
+#[derive(Clone)]
+pub struct StoreHandle {
+    sender: Sender<Request>,
+}
+
+impl std::fmt::Debug for StoreHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StoreHandle").finish()
+    }
+}
+
+impl StoreHandle {
+    fn send(&self, request: Request) -> Result<()> {
+        self.sender
+            .send(request)
+            .map_err(|_| anyhow!("The store actor is no longer running."))
+    }
+
+    /// Dispatch an action to the store. This does not wait for the action to be
+    /// processed.
+    pub fn dispatch(&self, action: Action) -> Result<()> {
+        self.send(Request::Dispatch(action))
+    }
+
+    /// Run a function against the store, and wait for the result. The result must be
+    /// sendable, so pull out owned values rather than Rc<State>.
+    pub fn query<R, F>(&self, f: F) -> Result<R>
+    where
+        R: Send + 'static,
+        F: FnOnce(&Store) -> R + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        self.send(Request::Query(Box::new(move |store| {
+            // The receiver is only gone if the caller went away, so ignore the error.
+            sender.send(f(store)).ok();
+        })))?;
+        receiver
+            .recv()
+            .map_err(|_| anyhow!("The store actor stopped before answering a query."))
+    }
+
+    /// Persist the store, and wait for it to finish.
+    pub fn persist(&self) -> Result<()> {
+        let (sender, receiver) = mpsc::channel();
+        self.send(Request::Persist(sender))?;
+        receiver
+            .recv()
+            .map_err(|_| anyhow!("The store actor stopped before persisting."))?
+    }
+
+    /// Apply blocks received from a peer, see `Store::apply_remote_blocks`.
+    pub fn apply_remote_blocks(
+        &self,
+        blocks: Vec<Block<ChainAction>>,
+    ) -> Result<RemoteChanges> {
+        let (sender, receiver) = mpsc::channel();
+        self.send(Request::ApplyRemoteBlocks(blocks, sender))?;
+        receiver.recv().map_err(|_| {
+            anyhow!("The store actor stopped before applying the remote blocks.")
+        })?
+    }
+}
+
+/// Owns a Store, and applies the requests sent through its StoreHandles.
+pub struct StoreActor {
+    store: Store,
+    receiver: Receiver<Request>,
+    sender: Sender<Request>,
+}
+
+impl StoreActor {
+    pub fn new(store: Store) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            store,
+            receiver,
+            sender,
+        }
+    }
+
+    /// Spawn a thread that owns the store. The store is created on that thread, as it
+    /// can't be sent between threads.
+    pub fn spawn<F>(make_store: F) -> Result<(StoreHandle, JoinHandle<()>)>
+    where
+        F: FnOnce() -> Result<Store> + Send + 'static,
+    {
+        let (handle_sender, handle_receiver) = mpsc::channel();
+        let join_handle = thread::spawn(move || match make_store() {
+            Ok(store) => {
+                let actor = StoreActor::new(store);
+                handle_sender.send(Ok(actor.handle())).ok();
+                actor.run();
+            }
+            Err(err) => {
+                handle_sender.send(Err(err)).ok();
+            }
+        });
+        let handle = handle_receiver
+            .recv()
+            .map_err(|_| anyhow!("The store actor thread panicked while starting."))??;
+        Ok((handle, join_handle))
+    }
+
+    pub fn handle(&self) -> StoreHandle {
+        StoreHandle {
+            sender: self.sender.clone(),
+        }
+    }
+
+    /// The owning thread can use the store directly.
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    pub fn store_mut(&mut self) -> &mut Store {
+        &mut self.store
+    }
+
+    /// Apply any pending requests without blocking, e.g. once per frame of a game
+    /// loop. Returns how many were applied.
+    pub fn process(&mut self) -> usize {
+        let mut count = 0;
+        while let Ok(request) = self.receiver.try_recv() {
+            self.apply(request);
+            count += 1;
+        }
+        count
+    }
+
+    /// Apply requests until every StoreHandle has been dropped.
+    pub fn run(self) {
+        let StoreActor {
+            mut store,
+            receiver,
+            sender,
+        } = self;
+        // Drop our own sender, or the loop would never end.
+        drop(sender);
+        for request in receiver {
+            apply(&mut store, request);
+        }
+    }
+
+    fn apply(&mut self, request: Request) {
+        apply(&mut self.store, request);
+    }
+}
+
+fn apply(store: &mut Store, request: Request) {
+    match request {
+        Request::Dispatch(action) => store.dispatch(action),
+        Request::Query(query) => query(store),
+        Request::Persist(reply) => {
+            reply.send(store.persist().map_err(Into::into)).ok();
+        }
+        Request::ApplyRemoteBlocks(blocks, reply) => {
+            reply
+                .send(store.apply_remote_blocks(&blocks).map_err(Into::into))
+                .ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        actions,
+        chain_store::{FsChainStore, HeadRef},
+        selectors,
+    };
+    use tempdir::TempDir;
+
+    fn make_store(tmp_dir: &TempDir) -> impl FnOnce() -> Result<Store> + Send {
+        let path = tmp_dir.path().to_path_buf();
+        move || {
+            let chain_store = FsChainStore::<ChainAction>::try_new(
+                path,
+                HeadRef::try_from("my-garden")?,
+            )?;
+            Ok(Store::try_new(Box::new(chain_store))?)
+        }
+    }
+
+    #[test]
+    fn test_store_actor_thread() {
+        let tmp_dir = TempDir::new("example").expect("Failed to create a temp directory");
+        let (handle, join_handle) =
+            StoreActor::spawn(make_store(&tmp_dir)).expect("Failed to spawn");
+
+        let remote = handle.clone();
+        thread::spawn(move || {
+            remote
+                .dispatch(actions::create_garden_plot("The Secret Garden".into()))
+                .unwrap();
+        })
+        .join()
+        .unwrap();
+
+        let name = handle
+            .query(|store| {
+                selectors::get_my_garden(store.state()).map(|plot| plot.name.clone())
+            })
+            .unwrap();
+        assert_eq!(name, Some(String::from("The Secret Garden")));
+
+        handle.persist().unwrap();
+        let unpersisted = handle
+            .query(|store| store.chains.unpersisted_block_count())
+            .unwrap();
+        assert_eq!(unpersisted, 0);
+
+        drop(handle);
+        join_handle
+            .join()
+            .expect("The actor finished once the handles dropped.");
+    }
+
+    #[test]
+    fn test_store_actor_process() {
+        let tmp_dir = TempDir::new("example").expect("Failed to create a temp directory");
+        let store = make_store(&tmp_dir)().unwrap();
+        let mut actor = StoreActor::new(store);
+        let handle = actor.handle();
+
+        thread::spawn(move || {
+            handle
+                .dispatch(actions::create_garden_plot("The Secret Garden".into()))
+                .unwrap();
+        })
+        .join()
+        .unwrap();
+
+        assert!(selectors::get_my_garden(actor.store().state()).is_none());
+        assert_eq!(actor.process(), 1);
+        assert!(selectors::get_my_garden(actor.store().state()).is_some());
+    }
+}
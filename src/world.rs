@@ -0,0 +1,46 @@
+//! The world a garden belongs to. It is recorded in the genesis block, the first block
+//! of every chain that `garden init` creates, so chains can be told apart by their
+//! root rather than only by having an all-zero parent.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{block_chain::Block, utils::get_timestamp, ChainAction};
+
+/// The version of the blocks a world was created with. Bump this when the meaning of
+/// the chain actions changes.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct WorldMetadata {
+    pub name: String,
+    /// The peer id of the player that created the world.
+    pub creator_key: String,
+    pub schema_version: u32,
+    /// The unix timestamp of when the world was created.
+    pub created_at: i64,
+}
+
+impl WorldMetadata {
+    pub fn new(name: String, creator_key: String) -> Self {
+        Self {
+            name,
+            creator_key,
+            schema_version: SCHEMA_VERSION,
+            created_at: get_timestamp(),
+        }
+    }
+
+    /// The world of a chain, read from its genesis block. This is None for chains that
+    /// were made before there were genesis blocks.
+    pub fn of_chain<'a>(
+        mut blocks: impl Iterator<Item = &'a Block<ChainAction>>,
+    ) -> Option<&'a WorldMetadata> {
+        match blocks.next() {
+            Some(block) if block.payload.parent.is_root() => match &block.payload.data {
+                ChainAction::Genesis(world) => Some(world),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}